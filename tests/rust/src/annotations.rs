@@ -0,0 +1,56 @@
+//! GitHub Actions workflow commands for the outcome of a run (`--github-annotations`), so that
+//! the failing S3 behaviours of a PHP server pull request show inline in the Actions UI: an
+//! error for every failure that fails the run, a warning for a tolerated one and a notice for
+//! an expected failure that passed.
+//!
+//! Annotations point at the scenario's function, by a path from the repository root.
+
+use std::fs;
+
+use crate::runner::{Gate, TestResult, Verdict};
+
+/// Where the scenario sources are, from the repository root and on this machine.
+const SCENARIOS: &str = "tests/rust/src/scenarios";
+const LOCAL_SCENARIOS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/scenarios");
+
+pub fn print(results: &[TestResult], gate: &Gate) {
+    for result in results {
+        if let Verdict::Skipped(_) = result.verdict {
+            continue;
+        }
+        let passed = matches!(result.verdict, Verdict::Passed);
+        let (level, message) = match (passed, gate.deviation(result)) {
+            (true, None) => continue,
+            (true, Some(_)) => ("notice", "passed although it is expected to fail".to_string()),
+            (false, Some(reason)) => {
+                ("warning", format!("{} (expected to fail: {})", result.verdict, reason))
+            }
+            (false, None) if gate.counts(result) => ("error", result.verdict.to_string()),
+            (false, None) => ("warning", result.verdict.to_string()),
+        };
+        let features = result.features.join(", ");
+        let title = format!("{} ({})", result.name, features);
+        println!("::{} {}::{}", level, location(result.name, &title), escape_data(&message));
+    }
+}
+
+/// The `file`, `line` and `title` properties of an annotation for the test `name`.
+fn location(name: &str, title: &str) -> String {
+    let (suite, function) = name.split_once("::").unwrap_or((name, name));
+    let file = format!("{}/{}.rs", SCENARIOS, suite);
+    let definition = format!("pub async fn {}(", function);
+    let line = fs::read_to_string(format!("{}/{}.rs", LOCAL_SCENARIOS, suite))
+        .ok()
+        .and_then(|source| source.lines().position(|l| l.contains(&definition)))
+        .map(|index| format!(",line={}", index + 1))
+        .unwrap_or_default();
+    format!("file={}{},title={}", escape_property(&file), line, escape_property(title))
+}
+
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
@@ -0,0 +1,215 @@
+//! Assertions for scenarios, as macros that return from the scenario with an error when they
+//! fail: [`expect_s3_error!`] checks that a request was refused with a given S3 error, and
+//! [`assert_bytes_eq_streamed!`] that a download streams exactly the expected bytes. Their
+//! errors say what was checked and where, with the operation, the request ID and the start of
+//! the error document, so that a failure can be reported from the run's output alone.
+//!
+//! ```ignore
+//! let resp = ctx.raw.request(Method::GET, &path).send().await?;
+//! expect_s3_error!(resp, "NoSuchKey", 404, "GET {}", path);
+//! let resp = client.get_object().bucket(bucket).key(key).send().await?;
+//! assert_bytes_eq_streamed!(resp.body, expected);
+//! ```
+
+use std::any::type_name;
+
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::primitives::ByteStream;
+
+use crate::capture;
+use crate::guard::BoxError;
+use crate::rawhttp::RawResponse;
+
+/// Error documents are cut to this many bytes in a diagnostic.
+const BODY_LIMIT: usize = 300;
+/// Bytes shown from the first difference of a download on.
+const CONTEXT: usize = 16;
+
+/// Checks that a response, a [`RawResponse`] or the result of an SDK call, is the S3 error
+/// `code` with `status`, and returns the error otherwise. It can say what was sent, like
+/// `format!`; by default that is the operation or the response expression.
+#[macro_export]
+macro_rules! expect_s3_error {
+    ($resp:expr, $code:expr, $status:expr $(,)?) => {
+        $crate::assertions::expect_error(
+            &$resp,
+            None,
+            stringify!($resp),
+            $code,
+            $status,
+            concat!(file!(), ":", line!()),
+        )?
+    };
+    ($resp:expr, $code:expr, $status:expr, $($what:tt)+) => {
+        $crate::assertions::expect_error(
+            &$resp,
+            Some(format!($($what)+)),
+            stringify!($resp),
+            $code,
+            $status,
+            concat!(file!(), ":", line!()),
+        )?
+    };
+}
+
+/// Reads a [`ByteStream`] to its end, comparing it with the expected bytes as it arrives, and
+/// returns an error at the first byte that differs or when the lengths do not match. It can
+/// say what was downloaded, like `format!`.
+#[macro_export]
+macro_rules! assert_bytes_eq_streamed {
+    ($stream:expr, $expected:expr $(,)?) => {
+        $crate::assertions::bytes_eq_streamed(
+            $stream,
+            &$expected[..],
+            stringify!($stream).to_string(),
+            concat!(file!(), ":", line!()),
+        )
+        .await?
+    };
+    ($stream:expr, $expected:expr, $($what:tt)+) => {
+        $crate::assertions::bytes_eq_streamed(
+            $stream,
+            &$expected[..],
+            format!($($what)+),
+            concat!(file!(), ":", line!()),
+        )
+        .await?
+    };
+}
+
+/// What an error check needs to know of a response.
+#[derive(Debug, Default)]
+pub struct Outcome<'a> {
+    /// The S3 operation, when the response says which.
+    operation: Option<String>,
+    /// None for a request that got no response, with why in `failure`.
+    status: Option<u16>,
+    succeeded: bool,
+    failure: Option<String>,
+    code: Option<&'a str>,
+    request_id: Option<&'a str>,
+    body: &'a [u8],
+}
+
+/// A response an error check can look at.
+pub trait S3Response {
+    fn outcome(&self) -> Outcome<'_>;
+}
+
+impl S3Response for RawResponse {
+    fn outcome(&self) -> Outcome<'_> {
+        Outcome {
+            status: Some(self.status),
+            succeeded: self.status < 300,
+            code: self.error_code(),
+            request_id: self.header("x-amz-request-id"),
+            body: &self.body,
+            ..Outcome::default()
+        }
+    }
+}
+
+impl<T, E> S3Response for Result<T, SdkError<E, HttpResponse>>
+where
+    E: ProvideErrorMetadata + std::error::Error + 'static,
+{
+    fn outcome(&self) -> Outcome<'_> {
+        let operation = Some(operation_of::<E>());
+        let e = match self {
+            Ok(_) => return Outcome { operation, succeeded: true, ..Outcome::default() },
+            Err(e) => e,
+        };
+        let raw = e.raw_response();
+        let body = raw.and_then(|r| r.body().bytes()).unwrap_or_default();
+        Outcome {
+            operation,
+            status: raw.map(|r| r.status().as_u16()),
+            failure: Some(DisplayErrorContext(e).to_string()),
+            code: e.code().or_else(|| capture::error_code(body)),
+            request_id: raw.and_then(|r| r.headers().get("x-amz-request-id")),
+            body,
+            ..Outcome::default()
+        }
+    }
+}
+
+/// `GetObject` for the SDK's `GetObjectError`.
+fn operation_of<E>() -> String {
+    let name = type_name::<E>().rsplit("::").next().unwrap_or_default();
+    name.strip_suffix("Error").unwrap_or(name).to_string()
+}
+
+/// The check of [`expect_s3_error!`].
+pub fn expect_error(
+    resp: &impl S3Response,
+    what: Option<String>,
+    expression: &str,
+    code: &str,
+    status: u16,
+    location: &str,
+) -> Result<(), BoxError> {
+    let outcome = resp.outcome();
+    if outcome.status == Some(status) && outcome.code == Some(code) {
+        return Ok(());
+    }
+    let what = what.or_else(|| outcome.operation.clone()).unwrap_or_else(|| expression.to_string());
+    let got = match (outcome.status, outcome.code) {
+        (_, _) if outcome.succeeded => format!("success{}", status_of(outcome.status)),
+        (Some(status), Some(code)) => format!("{} {}", status, code),
+        (Some(status), None) => format!("{} without an error code", status),
+        (None, _) => format!("no response: {}", outcome.failure.as_deref().unwrap_or("unknown error")),
+    };
+
+    let mut details = vec![format!("at {}", location)];
+    if let (Some(operation), true) = (&outcome.operation, outcome.operation.as_deref() != Some(&what)) {
+        details.push(format!("operation {}", operation));
+    }
+    details.extend(outcome.request_id.map(|id| format!("request ID {}", id)));
+    if !outcome.body.is_empty() {
+        let document = String::from_utf8_lossy(&outcome.body[..outcome.body.len().min(BODY_LIMIT)]);
+        let cut = if outcome.body.len() > BODY_LIMIT { "..." } else { "" };
+        details.push(format!("body {:?}{}", document, cut));
+    }
+    Err(format!("{}: expected {} {}, got {} ({})", what, status, code, got, details.join("; ")).into())
+}
+
+fn status_of(status: Option<u16>) -> String {
+    status.map(|status| format!(" {}", status)).unwrap_or_default()
+}
+
+/// The check of [`assert_bytes_eq_streamed!`].
+pub async fn bytes_eq_streamed(
+    mut stream: ByteStream,
+    expected: &[u8],
+    what: String,
+    location: &str,
+) -> Result<(), BoxError> {
+    let mut offset = 0;
+    while let Some(chunk) = stream.try_next().await? {
+        let rest = &expected[offset.min(expected.len())..];
+        if let Some(i) = chunk.iter().zip(rest).position(|(got, expected)| got != expected) {
+            let got = &chunk[i..(i + CONTEXT).min(chunk.len())];
+            let wanted = &rest[i..(i + CONTEXT).min(rest.len())];
+            let message = format!(
+                "{}: byte {} differs, expected {} got {} (at {})",
+                what,
+                offset + i,
+                hex::encode(wanted),
+                hex::encode(got),
+                location
+            );
+            return Err(message.into());
+        }
+        if chunk.len() > rest.len() {
+            let message = format!("{}: more than the {} bytes expected", what, expected.len());
+            return Err(format!("{} (at {})", message, location).into());
+        }
+        offset += chunk.len();
+    }
+    if offset < expected.len() {
+        let message = format!("{}: ended after {} of {} bytes", what, offset, expected.len());
+        return Err(format!("{} (at {})", message, location).into());
+    }
+    Ok(())
+}
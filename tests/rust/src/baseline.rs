@@ -0,0 +1,111 @@
+//! Benchmark baselines (`s3test bench --save-baseline NAME`, `--compare-baseline NAME`): the
+//! throughput and latency of every phase, kept as `NAME.json` in `--baseline-dir`, and how a
+//! later run differs from them in percent, for telling a faster PHP server from a noisy one.
+
+use std::fs;
+use std::path::PathBuf;
+
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::bench::{millis, ops_per_sec, Summary};
+use crate::cli::BenchArgs;
+use crate::guard::BoxError;
+use crate::naming::unix_now;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Baseline {
+    /// When the baseline was saved, in seconds since the epoch.
+    saved: u64,
+    phases: Vec<Phase>,
+}
+
+/// One phase of the benchmark, as far as it is worth comparing.
+#[derive(Debug, Serialize, Deserialize)]
+struct Phase {
+    op: String,
+    size: u64,
+    concurrency: usize,
+    ops_per_sec: f64,
+    p50_ms: f64,
+    p95_ms: f64,
+    p99_ms: f64,
+}
+
+impl Phase {
+    fn of(summary: &Summary) -> Phase {
+        Phase {
+            op: summary.op.name().to_string(),
+            size: summary.size,
+            concurrency: summary.concurrency,
+            ops_per_sec: ops_per_sec(summary),
+            p50_ms: millis(summary.latency(50)),
+            p95_ms: millis(summary.latency(95)),
+            p99_ms: millis(summary.latency(99)),
+        }
+    }
+}
+
+fn path(args: &BenchArgs, name: &str) -> PathBuf {
+    args.baseline_dir.join(format!("{}.json", name))
+}
+
+/// The baseline `name`, saved by an earlier run.
+pub fn load(args: &BenchArgs, name: &str) -> Result<Baseline, BoxError> {
+    let path = path(args, name);
+    let json = fs::read_to_string(&path)
+        .map_err(|e| format!("cannot read baseline {}: {}", path.display(), e))?;
+    serde_json::from_str(&json).map_err(|e| format!("baseline {} is not valid: {}", path.display(), e).into())
+}
+
+/// Saves the phases of `summaries` as the baseline `name`, replacing any earlier one.
+pub fn save(args: &BenchArgs, name: &str, summaries: &[Summary]) -> Result<(), BoxError> {
+    let path = path(args, name);
+    let baseline = Baseline { saved: unix_now(), phases: summaries.iter().map(Phase::of).collect() };
+    fs::create_dir_all(&args.baseline_dir)?;
+    fs::write(&path, serde_json::to_string_pretty(&baseline)?)?;
+    info!(path = %path.display(), phases = baseline.phases.len(), "saved benchmark baseline");
+    Ok(())
+}
+
+/// How much `new` differs from `old`, in percent of `old`.
+fn delta(old: f64, new: f64) -> String {
+    match old {
+        0.0 => "-".to_string(),
+        _ => format!("{:+.1}%", (new - old) / old * 100.0),
+    }
+}
+
+/// Prints how every phase of `summaries` differs from the same phase of `baseline`: more
+/// ops/s is faster, more milliseconds slower. Phases the baseline lacks are marked new.
+pub fn print_comparison(name: &str, baseline: &Baseline, summaries: &[Summary]) {
+    let saved = DateTime::from_secs(baseline.saved as i64).fmt(DateTimeFormat::DateTime).unwrap();
+    println!();
+    println!("compared with baseline '{}' saved {}:", name, saved);
+    println!(
+        "{:<7} {:>10} {:>5} {:>9} {:>9} {:>9} {:>9}",
+        "op", "size", "conc", "ops/s", "p50", "p95", "p99"
+    );
+    for summary in summaries {
+        let new = Phase::of(summary);
+        let old = baseline
+            .phases
+            .iter()
+            .find(|p| p.op == new.op && p.size == new.size && p.concurrency == new.concurrency);
+        let Some(old) = old else {
+            println!("{:<7} {:>10} {:>5} {:>9}", new.op, new.size, new.concurrency, "new");
+            continue;
+        };
+        println!(
+            "{:<7} {:>10} {:>5} {:>9} {:>9} {:>9} {:>9}",
+            new.op,
+            new.size,
+            new.concurrency,
+            delta(old.ops_per_sec, new.ops_per_sec),
+            delta(old.p50_ms, new.p50_ms),
+            delta(old.p95_ms, new.p95_ms),
+            delta(old.p99_ms, new.p99_ms),
+        );
+    }
+}
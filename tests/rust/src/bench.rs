@@ -0,0 +1,420 @@
+//! `s3test bench`: throughput and latency of the basic object operations.
+//!
+//! For every combination of object size and concurrency level the benchmark runs four phases
+//! against a fresh prefix: PUT `--requests` objects, GET them all back, LIST the prefix as
+//! many times, then DELETE the objects. Each request is one sample, and all of them go to the
+//! `--samples` CSV file with their start time, latency and HTTP status.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicU16, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use aws_sdk_s3::config::interceptors::BeforeDeserializationInterceptorContextRef;
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_sdk_s3::primitives::{ByteStream, DateTime, DateTimeFormat};
+use aws_sdk_s3::Client;
+use bytes::Bytes;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::baseline;
+use crate::budget;
+use crate::cli::BenchArgs;
+use crate::dashboard;
+use crate::guard::{BoxError, BucketGuard};
+use crate::histogram::Histogram;
+use crate::metrics::{self, Metrics};
+use crate::naming::RunId;
+use crate::payload::Payload;
+use crate::progress;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Put,
+    Get,
+    List,
+    Delete,
+}
+
+impl Op {
+    pub fn name(self) -> &'static str {
+        match self {
+            Op::Put => "PUT",
+            Op::Get => "GET",
+            Op::List => "LIST",
+            Op::Delete => "DELETE",
+        }
+    }
+
+    /// Whether the object body goes over the wire, which makes MiB/s meaningful.
+    fn transfers_body(self) -> bool {
+        matches!(self, Op::Put | Op::Get)
+    }
+}
+
+struct Sample {
+    started: SystemTime,
+    op: Op,
+    size: u64,
+    concurrency: usize,
+    request: usize,
+    latency: Duration,
+    /// Of the last response, None when the request never got one.
+    status: Option<u16>,
+    ok: bool,
+}
+
+/// Aggregate of one phase.
+pub struct Summary {
+    pub op: Op,
+    pub size: u64,
+    pub concurrency: usize,
+    requests: usize,
+    errors: usize,
+    elapsed: Duration,
+    /// Of the latencies of the successful requests, which every percentile is read from.
+    histogram: Histogram,
+}
+
+impl Summary {
+    /// A phase of `op` whose requests all succeeded, taking `latencies`.
+    #[cfg(test)]
+    pub fn of(op: Op, size: u64, concurrency: usize, latencies: &[Duration]) -> Summary {
+        let sample = |(request, &latency)| {
+            let (started, status, ok) = (SystemTime::UNIX_EPOCH, Some(200), true);
+            Sample { started, op, size, concurrency, request, latency, status, ok }
+        };
+        let samples: Vec<Sample> = latencies.iter().enumerate().map(sample).collect();
+        summarize(op, size, concurrency, Duration::from_secs(1), &samples)
+    }
+
+    /// The `p`th percentile latency, to three significant digits.
+    pub fn latency(&self, p: u8) -> Duration {
+        self.histogram.percentile(f64::from(p))
+    }
+}
+
+pub async fn run(client: &Client, args: &BenchArgs) -> Result<(), BoxError> {
+    let budgets = args.budgets.as_deref().map(|path| budget::load(path, args)).transpose()?;
+    let compared = args.compare_baseline.as_deref().map(|name| baseline::load(args, name)).transpose()?;
+    let run_id = RunId::generate();
+    let bucket = BucketGuard::create(client, &run_id.bucket("bench")).await?;
+    info!(bucket = bucket.name(), "created benchmark bucket");
+    let metrics = Metrics::new("bench");
+    let _exporter = metrics::serve(&args.metrics, &metrics).await?;
+    let dashboard = dashboard::show(&args.metrics, &metrics);
+
+    let mut samples = Vec::new();
+    let mut summaries = Vec::new();
+    for &size in &args.sizes {
+        let size_usize = usize::try_from(size).map_err(|_| format!("object size {} is too large", size))?;
+        let body = Payload::new(run_id.timestamp(), "bench", size_usize).bytes();
+        for &concurrency in &args.concurrency {
+            let prefix = format!("{}-c{}/", size, concurrency);
+            for op in [Op::Put, Op::Get, Op::List, Op::Delete] {
+                let (client, bucket) = (client.clone(), bucket.name().to_string());
+                let (prefix, body) = (prefix.clone(), body.clone());
+                let request = move |i: usize| {
+                    let (client, bucket, body) = (client.clone(), bucket.clone(), body.clone());
+                    let (prefix, key) = (prefix.clone(), format!("{}{}", prefix, i));
+                    async move { send(&client, &bucket, op, &prefix, &key, &body).await }
+                };
+
+                let started = Instant::now();
+                let phase = run_phase(op, args.requests, concurrency, &metrics, request);
+                // Dropping the phase aborts its workers.
+                let mut phase = tokio::select! {
+                    phase = phase => phase,
+                    _ = dashboard::interrupted(dashboard.as_ref()) => {
+                        return Err("interrupted by Ctrl-C".into());
+                    }
+                };
+                let elapsed = started.elapsed();
+                for sample in &mut phase {
+                    sample.size = size;
+                    sample.concurrency = concurrency;
+                }
+                let summary = summarize(op, size, concurrency, elapsed, &phase);
+                info!(
+                    op = op.name(),
+                    size,
+                    concurrency,
+                    errors = summary.errors,
+                    ops_per_sec = ops_per_sec(&summary),
+                    "finished phase"
+                );
+                summaries.push(summary);
+                samples.extend(phase);
+            }
+        }
+    }
+    drop(dashboard);
+
+    print_table(&summaries);
+    if let (Some(name), Some(compared)) = (&args.compare_baseline, &compared) {
+        baseline::print_comparison(name, compared, &summaries);
+    }
+    write_samples(args, &samples)?;
+    if let Some(dir) = &args.hgrm {
+        write_histograms(dir, &summaries)?;
+    }
+    if let Err(e) = bucket.cleanup().await {
+        warn!(error = ?e, "could not remove benchmark bucket");
+    }
+
+    let errors: usize = summaries.iter().map(|s| s.errors).sum();
+    if errors > 0 {
+        return Err(format!("{} benchmark request(s) failed", errors).into());
+    }
+    // Only a run without errors is worth comparing later runs against.
+    if let Some(name) = &args.save_baseline {
+        baseline::save(args, name, &summaries)?;
+    }
+    let exceeded = budget::exceeded(budgets.as_deref().unwrap_or_default(), &summaries);
+    if !exceeded.is_empty() {
+        println!();
+        for line in &exceeded {
+            println!("over budget: {}", line);
+        }
+        return Err(format!("{} latency budget(s) exceeded", exceeded.len()).into());
+    }
+    Ok(())
+}
+
+/// Interceptor keeping the HTTP status of the last response to the operation it is added to.
+#[derive(Debug, Clone, Default)]
+struct LastStatus(Arc<AtomicU16>);
+
+impl LastStatus {
+    fn get(&self) -> Option<u16> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            status => Some(status),
+        }
+    }
+}
+
+impl Intercept for LastStatus {
+    fn name(&self) -> &'static str {
+        "LastStatus"
+    }
+
+    fn read_before_deserialization(
+        &self,
+        context: &BeforeDeserializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        self.0.store(context.response().status().as_u16(), Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// One request of `op` on `key`, or on all of `prefix` for a LIST. PUT uploads `body` and GET
+/// expects to get as many bytes back. Comes back with the HTTP status of the last response,
+/// retries included, if there was one.
+pub async fn send(
+    client: &Client,
+    bucket: &str,
+    op: Op,
+    prefix: &str,
+    key: &str,
+    body: &Bytes,
+) -> (Option<u16>, Result<(), BoxError>) {
+    let status = LastStatus::default();
+    let result = send_op(client, bucket, op, prefix, key, body, &status).await;
+    (status.get(), result)
+}
+
+async fn send_op(
+    client: &Client,
+    bucket: &str,
+    op: Op,
+    prefix: &str,
+    key: &str,
+    body: &Bytes,
+    status: &LastStatus,
+) -> Result<(), BoxError> {
+    match op {
+        Op::Put => {
+            let put = client.put_object().bucket(bucket).key(key).body(ByteStream::from(body.clone()));
+            put.customize().interceptor(status.clone()).send().await?;
+        }
+        Op::Get => {
+            let get = client.get_object().bucket(bucket).key(key);
+            let resp = get.customize().interceptor(status.clone()).send().await?;
+            let len = progress::discard_download(resp.body).await?;
+            if len != body.len() as u64 {
+                return Err(format!("GET returned {} bytes, expected {}", len, body.len()).into());
+            }
+        }
+        Op::List => {
+            let list = client.list_objects_v2().bucket(bucket).prefix(prefix);
+            list.customize().interceptor(status.clone()).send().await?;
+        }
+        Op::Delete => {
+            let delete = client.delete_object().bucket(bucket).key(key);
+            delete.customize().interceptor(status.clone()).send().await?;
+        }
+    }
+    Ok(())
+}
+
+/// Sends `requests` requests of `op` with at most `concurrency` of them in flight. The samples
+/// come back with their size and concurrency still to be filled in.
+async fn run_phase<F, Fut>(
+    op: Op,
+    requests: usize,
+    concurrency: usize,
+    metrics: &Arc<Metrics>,
+    request: F,
+) -> Vec<Sample>
+where
+    F: Fn(usize) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = (Option<u16>, Result<(), BoxError>)> + Send,
+{
+    let next = Arc::new(AtomicUsize::new(0));
+    let mut workers = JoinSet::new();
+    for _ in 0..concurrency.max(1) {
+        let (next, metrics) = (next.clone(), metrics.clone());
+        let request = request.clone();
+        workers.spawn(async move {
+            let mut samples = Vec::new();
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= requests {
+                    return samples;
+                }
+                let (started, clock) = (SystemTime::now(), Instant::now());
+                metrics.start();
+                let (status, result) = request(i).await;
+                if let Err(e) = &result {
+                    warn!(request = i, ?status, error = ?e, "benchmark request failed");
+                }
+                let latency = clock.elapsed();
+                let ok = result.is_ok();
+                metrics.record(op, latency, ok);
+                let (size, concurrency) = (0, 0);
+                samples.push(Sample { started, op, size, concurrency, request: i, latency, status, ok });
+            }
+        });
+    }
+
+    let mut samples = Vec::with_capacity(requests);
+    while let Some(worker) = workers.join_next().await {
+        match worker {
+            Ok(worker_samples) => samples.extend(worker_samples),
+            Err(e) => warn!(error = %e, "benchmark worker failed"),
+        }
+    }
+    samples.sort_by_key(|s| s.request);
+    samples
+}
+
+fn summarize(op: Op, size: u64, concurrency: usize, elapsed: Duration, samples: &[Sample]) -> Summary {
+    let mut histogram = Histogram::default();
+    samples.iter().filter(|s| s.ok).for_each(|s| histogram.record(s.latency));
+    Summary {
+        op,
+        size,
+        concurrency,
+        requests: samples.len(),
+        errors: samples.iter().filter(|s| !s.ok).count(),
+        elapsed,
+        histogram,
+    }
+}
+
+/// Nearest-rank percentile of sorted latencies; zero when there are none.
+pub fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+pub fn ops_per_sec(summary: &Summary) -> f64 {
+    (summary.requests - summary.errors) as f64 / summary.elapsed.as_secs_f64()
+}
+
+fn print_table(summaries: &[Summary]) {
+    println!(
+        "{:<7} {:>10} {:>5} {:>6} {:>6} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+        "op", "size", "conc", "reqs", "errors", "ops/s", "MiB/s", "p50 ms", "p90 ms", "p95 ms", "p99 ms",
+        "p99.9 ms", "max ms"
+    );
+    for s in summaries {
+        let mib_per_sec = match s.op.transfers_body() {
+            true => format!("{:.2}", ops_per_sec(s) * s.size as f64 / (1024.0 * 1024.0)),
+            false => "-".to_string(),
+        };
+        println!(
+            "{:<7} {:>10} {:>5} {:>6} {:>6} {:>9.1} {:>9} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+            s.op.name(),
+            s.size,
+            s.concurrency,
+            s.requests,
+            s.errors,
+            ops_per_sec(s),
+            mib_per_sec,
+            millis(s.latency(50)),
+            millis(s.latency(90)),
+            millis(s.latency(95)),
+            millis(s.latency(99)),
+            millis(s.histogram.percentile(99.9)),
+            millis(s.histogram.max()),
+        );
+    }
+}
+
+pub fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// `at` as the CSV sample files have it, RFC 3339 in UTC with fractions of a second.
+pub fn csv_timestamp(at: SystemTime) -> String {
+    DateTime::from(at).fmt(DateTimeFormat::DateTime).unwrap()
+}
+
+/// The HTTP status as the CSV sample files have it, empty when there was no response.
+pub fn csv_status(status: Option<u16>) -> String {
+    status.map(|status| status.to_string()).unwrap_or_default()
+}
+
+fn write_samples(args: &BenchArgs, samples: &[Sample]) -> Result<(), BoxError> {
+    let mut csv = String::from("timestamp,op,size,concurrency,request,latency_us,status,ok\n");
+    for s in samples {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{}",
+            csv_timestamp(s.started),
+            s.op.name(),
+            s.size,
+            s.concurrency,
+            s.request,
+            s.latency.as_micros(),
+            csv_status(s.status),
+            s.ok
+        )
+        .unwrap();
+    }
+    fs::write(&args.samples, csv)?;
+    info!(path = %args.samples.display(), samples = samples.len(), "wrote benchmark samples");
+    Ok(())
+}
+
+/// Writes the latency distribution of every phase as `<op>-<size>-c<concurrency>.hgrm` into `dir`.
+fn write_histograms(dir: &Path, summaries: &[Summary]) -> Result<(), BoxError> {
+    fs::create_dir_all(dir)?;
+    for s in summaries {
+        let name = format!("{}-{}-c{}.hgrm", s.op.name(), s.size, s.concurrency);
+        fs::write(dir.join(name), s.histogram.hgrm())?;
+    }
+    info!(dir = %dir.display(), phases = summaries.len(), "wrote latency histograms");
+    Ok(())
+}
@@ -0,0 +1,235 @@
+//! Latency budgets (`s3test bench --budgets FILE`): percentiles the benchmarked operations
+//! must stay under, so that a slower PHP server turns CI red instead of printing bigger
+//! numbers nobody reads.
+//!
+//! ```json
+//! {
+//!   "budgets": [
+//!     { "op": "GET", "size": "1KiB", "p95_ms": 50 },
+//!     { "op": "PUT", "concurrency": 8, "p50_ms": 20, "p99_ms": 250 }
+//!   ]
+//! }
+//! ```
+//!
+//! A budget holds for every phase of its operation, or only for those of one object size or
+//! concurrency level when it names one; `size` is written like `--sizes`.
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::bench::{millis, Op, Summary};
+use crate::cli::{parse_size, BenchArgs};
+use crate::guard::BoxError;
+
+#[derive(Debug, Deserialize)]
+struct BudgetFile {
+    budgets: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    op: String,
+    size: Option<String>,
+    concurrency: Option<usize>,
+    p50_ms: Option<f64>,
+    p95_ms: Option<f64>,
+    p99_ms: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct Budget {
+    op: Op,
+    size: Option<u64>,
+    concurrency: Option<usize>,
+    /// Each percentile limited, with the latency it must not exceed.
+    limits: Vec<(u8, Duration)>,
+}
+
+impl Budget {
+    fn applies_to(&self, summary: &Summary) -> bool {
+        self.op == summary.op
+            && self.size.is_none_or(|size| size == summary.size)
+            && self.concurrency.is_none_or(|concurrency| concurrency == summary.concurrency)
+    }
+}
+
+/// The budgets of the file at `path`. Each has to apply to a size and concurrency level that
+/// `args` benchmark, so that a typo does not leave a phase unchecked.
+pub fn load(path: &Path, args: &BenchArgs) -> Result<Vec<Budget>, BoxError> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| format!("cannot read budget file {}: {}", path.display(), e))?;
+    let file: BudgetFile = serde_json::from_str(&json)
+        .map_err(|e| format!("budget file {} is not valid: {}", path.display(), e))?;
+
+    let mut budgets = Vec::new();
+    for entry in file.budgets {
+        let ops = [Op::Put, Op::Get, Op::List, Op::Delete];
+        let op = ops.into_iter().find(|op| op.name().eq_ignore_ascii_case(&entry.op));
+        let op = op.ok_or_else(|| format!("{}: unknown operation '{}'", path.display(), entry.op))?;
+        let size = entry.size.as_deref().map(parse_size).transpose();
+        let size = size.map_err(|e| format!("{}: {}", path.display(), e))?;
+        let (file, name) = (path.display(), op.name());
+        if size.is_some_and(|size| !args.sizes.contains(&size)) {
+            return Err(format!("{}: {} budget for a size --sizes does not include", file, name).into());
+        }
+        if entry.concurrency.is_some_and(|c| !args.concurrency.contains(&c)) {
+            let message = format!("{}: {} budget for a level --concurrency does not include", file, name);
+            return Err(message.into());
+        }
+
+        let mut limits = Vec::new();
+        for (p, ms) in [(50, entry.p50_ms), (95, entry.p95_ms), (99, entry.p99_ms)] {
+            let Some(ms) = ms else {
+                continue;
+            };
+            let limit = Duration::try_from_secs_f64(ms / 1000.0)
+                .map_err(|_| format!("{}: {} budget has a p{}_ms of {}, not a latency", file, name, p, ms))?;
+            limits.push((p, limit));
+        }
+        if limits.is_empty() {
+            return Err(format!("{}: {} budget sets none of p50_ms, p95_ms, p99_ms", file, name).into());
+        }
+        budgets.push(Budget { op, size, concurrency: entry.concurrency, limits });
+    }
+    Ok(budgets)
+}
+
+/// Every limit of `budgets` a phase of `summaries` exceeds, one line each.
+pub fn exceeded(budgets: &[Budget], summaries: &[Summary]) -> Vec<String> {
+    let mut exceeded = Vec::new();
+    for summary in summaries {
+        for budget in budgets.iter().filter(|b| b.applies_to(summary)) {
+            for &(p, limit) in &budget.limits {
+                let latency = summary.latency(p);
+                if latency > limit {
+                    exceeded.push(format!(
+                        "{} of {} B at concurrency {}: p{} {:.2} ms, budget {:.2} ms",
+                        summary.op.name(),
+                        summary.size,
+                        summary.concurrency,
+                        p,
+                        millis(latency),
+                        millis(limit)
+                    ));
+                }
+            }
+        }
+    }
+    exceeded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use clap::{Args, Command, FromArgMatches};
+
+    use super::*;
+
+    /// `bench` arguments benchmarking sizes 1KiB and 1MiB at concurrency 1 and 8.
+    fn args() -> BenchArgs {
+        let command = BenchArgs::augment_args(Command::new("bench")).mut_args(|arg| arg.env(None));
+        let matches = command.try_get_matches_from(["bench", "--sizes", "1KiB,1MiB", "--concurrency", "1,8"]);
+        BenchArgs::from_arg_matches(&matches.unwrap()).unwrap()
+    }
+
+    /// Budgets loaded from a file holding `json`, named after the test it is for.
+    fn load_json(test: &str, json: &str) -> Result<Vec<Budget>, String> {
+        let path: PathBuf = std::env::temp_dir().join(format!("s3test-{}-{}.json", test, std::process::id()));
+        fs::write(&path, json).unwrap();
+        let budgets = load(&path, &args()).map_err(|e| e.to_string());
+        fs::remove_file(&path).unwrap();
+        budgets
+    }
+
+    fn phase(op: Op, size: u64, concurrency: usize, latency_us: u64) -> Summary {
+        Summary::of(op, size, concurrency, &[Duration::from_micros(latency_us); 10])
+    }
+
+    #[test]
+    fn loads_budgets() {
+        let json = r#"{ "budgets": [
+            { "op": "get", "size": "1KiB", "p95_ms": 50 },
+            { "op": "PUT", "concurrency": 8, "p50_ms": 20, "p99_ms": 250.5 }
+        ] }"#;
+        let budgets = load_json("loads", json).unwrap();
+        assert_eq!(budgets.len(), 2);
+        assert_eq!((budgets[0].op, budgets[0].size, budgets[0].concurrency), (Op::Get, Some(1024), None));
+        assert_eq!(budgets[0].limits, [(95, Duration::from_millis(50))]);
+        assert_eq!((budgets[1].op, budgets[1].size, budgets[1].concurrency), (Op::Put, None, Some(8)));
+        assert_eq!(budgets[1].limits, [(50, Duration::from_millis(20)), (99, Duration::from_micros(250500))]);
+    }
+
+    #[test]
+    fn refuses_files_it_cannot_parse() {
+        let cases = [
+            ("syntax", "{ \"budgets\": [ "),
+            ("no-budgets", "{}"),
+            ("unknown-field", r#"{ "budgets": [{ "op": "GET", "p90_ms": 5 }] }"#),
+            ("no-op", r#"{ "budgets": [{ "p95_ms": 5 }] }"#),
+            ("limit-type", r#"{ "budgets": [{ "op": "GET", "p95_ms": "5" }] }"#),
+        ];
+        for (test, json) in cases {
+            let error = load_json(test, json).unwrap_err();
+            assert!(error.contains("is not valid"), "{}: {}", test, error);
+        }
+        let missing = Path::new("/nonexistent/s3test-budgets.json");
+        assert!(load(missing, &args()).unwrap_err().to_string().contains("cannot read budget file"));
+    }
+
+    #[test]
+    fn refuses_budgets_that_would_check_nothing() {
+        let cases = [
+            ("unknown-op", r#"{ "op": "HEAD", "p95_ms": 5 }"#, "unknown operation 'HEAD'"),
+            ("bad-size", r#"{ "op": "GET", "size": "1XB", "p95_ms": 5 }"#, "unknown size unit"),
+            ("other-size", r#"{ "op": "GET", "size": "4KiB", "p95_ms": 5 }"#, "--sizes"),
+            ("other-concurrency", r#"{ "op": "GET", "concurrency": 4, "p95_ms": 5 }"#, "--concurrency"),
+            ("no-limit", r#"{ "op": "GET" }"#, "sets none of"),
+            ("negative-limit", r#"{ "op": "GET", "p50_ms": -1 }"#, "not a latency"),
+            ("huge-limit", r#"{ "op": "GET", "p50_ms": 1e300 }"#, "not a latency"),
+        ];
+        for (test, entry, expected) in cases {
+            let error = load_json(test, &format!(r#"{{ "budgets": [{}] }}"#, entry)).unwrap_err();
+            assert!(error.contains(expected), "{}: {}", test, error);
+        }
+    }
+
+    #[test]
+    fn a_latency_at_its_budget_is_within_it() {
+        let budgets = load_json("boundary", r#"{ "budgets": [{ "op": "GET", "p95_ms": 1.5 }] }"#).unwrap();
+        assert!(exceeded(&budgets, &[phase(Op::Get, 1024, 1, 1500)]).is_empty());
+        let over = exceeded(&budgets, &[phase(Op::Get, 1024, 1, 1501)]);
+        assert_eq!(over, ["GET of 1024 B at concurrency 1: p95 1.50 ms, budget 1.50 ms"]);
+    }
+
+    #[test]
+    fn budgets_apply_to_their_phases_only() {
+        let json = r#"{ "budgets": [
+            { "op": "GET", "size": "1KiB", "p50_ms": 1 },
+            { "op": "PUT", "concurrency": 8, "p99_ms": 1 }
+        ] }"#;
+        let budgets = load_json("phases", json).unwrap();
+        let slow = 2000;
+        let phases = [
+            phase(Op::Get, 1024, 1, slow),
+            phase(Op::Get, 1 << 20, 1, slow),
+            phase(Op::Put, 1024, 1, slow),
+            phase(Op::Put, 1 << 20, 8, slow),
+            phase(Op::List, 1024, 8, slow),
+        ];
+        let over = exceeded(&budgets, &phases);
+        assert_eq!(over.len(), 2, "{:?}", over);
+        assert!(over[0].starts_with("GET of 1024 B at concurrency 1: p50"));
+        assert!(over[1].starts_with("PUT of 1048576 B at concurrency 8: p99"));
+    }
+
+    #[test]
+    fn operations_without_a_budget_are_unchecked() {
+        let budgets = load_json("unchecked", r#"{ "budgets": [] }"#).unwrap();
+        assert!(exceeded(&budgets, &[phase(Op::Delete, 1024, 1, 10_000_000)]).is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+//! Records the raw HTTP exchanges behind every SDK call, so a failing test can show exactly
+//! what was sent to the server and what came back.
+
+use std::fmt::{self, Write as _};
+use std::sync::{Arc, Mutex};
+
+use aws_sdk_s3::config::interceptors::{
+    BeforeTransmitInterceptorContextRef, FinalizerInterceptorContextRef,
+};
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_smithy_runtime_api::box_error::BoxError;
+use aws_smithy_runtime_api::client::orchestrator::Metadata;
+use aws_smithy_runtime_api::http::Headers;
+
+/// A request/response pair as seen on the wire, one per attempt.
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    pub operation: String,
+    pub method: String,
+    pub uri: String,
+    pub request_headers: Vec<(String, String)>,
+    pub request_body: CapturedBody,
+    pub response: Option<CapturedResponse>,
+    /// Set when no response was received, e.g. on connection errors.
+    pub error: Option<String>,
+    /// Sent while a guard was cleaning up after the test.
+    pub teardown: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct CapturedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: CapturedBody,
+}
+
+impl CapturedResponse {
+    /// The S3 error code from an XML error body, if there is one.
+    pub fn error_code(&self) -> Option<&str> {
+        match &self.body {
+            CapturedBody::Bytes { data, .. } => error_code(data),
+            CapturedBody::Streaming => None,
+        }
+    }
+}
+
+/// Extracts `<Code>` from an S3 XML error document.
+pub fn error_code(body: &[u8]) -> Option<&str> {
+    let body = std::str::from_utf8(body).ok()?;
+    let start = body.find("<Code>")? + "<Code>".len();
+    let end = start + body[start..].find("</Code>")?;
+    Some(&body[start..end])
+}
+
+/// The first `limit` bytes of a body. Streaming bodies are not buffered by the SDK and
+/// therefore cannot be captured.
+#[derive(Debug, Clone)]
+pub enum CapturedBody {
+    Bytes { data: Vec<u8>, total_len: usize },
+    Streaming,
+}
+
+impl CapturedBody {
+    fn new(bytes: Option<&[u8]>, limit: usize) -> Self {
+        match bytes {
+            Some(bytes) => CapturedBody::Bytes {
+                data: bytes[..bytes.len().min(limit)].to_vec(),
+                total_len: bytes.len(),
+            },
+            None => CapturedBody::Streaming,
+        }
+    }
+}
+
+impl fmt::Display for CapturedBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CapturedBody::Bytes { data, total_len } => {
+                f.write_str(&String::from_utf8_lossy(data))?;
+                if data.len() < *total_len {
+                    write!(f, "... ({} of {} bytes shown)", data.len(), total_len)?;
+                }
+                Ok(())
+            }
+            CapturedBody::Streaming => f.write_str("<streaming body not captured>"),
+        }
+    }
+}
+
+/// Interceptor that appends every exchange to a shared list.
+#[derive(Debug, Clone)]
+pub struct HttpCapture {
+    exchanges: Arc<Mutex<Vec<Exchange>>>,
+    body_limit: usize,
+}
+
+impl HttpCapture {
+    pub fn new(body_limit: usize) -> Self {
+        HttpCapture {
+            exchanges: Arc::default(),
+            body_limit,
+        }
+    }
+
+    /// Removes and returns everything captured so far.
+    pub fn take(&self) -> Vec<Exchange> {
+        std::mem::take(&mut *self.exchanges.lock().unwrap())
+    }
+}
+
+impl Intercept for HttpCapture {
+    fn name(&self) -> &'static str {
+        "HttpCapture"
+    }
+
+    fn read_before_transmit(
+        &self,
+        context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let request = context.request();
+        let exchange = Exchange {
+            operation: cfg
+                .load::<Metadata>()
+                .map(|m| m.name().to_string())
+                .unwrap_or_default(),
+            method: request.method().to_string(),
+            uri: request.uri().to_string(),
+            request_headers: header_pairs(request.headers()),
+            request_body: CapturedBody::new(request.body().bytes(), self.body_limit),
+            response: None,
+            error: None,
+            teardown: crate::guard::in_teardown(),
+        };
+        self.exchanges.lock().unwrap().push(exchange);
+        Ok(())
+    }
+
+    fn read_after_attempt(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let mut exchanges = self.exchanges.lock().unwrap();
+        // Attempts that failed before transmitting never got an exchange of their own.
+        let Some(exchange) = exchanges.last_mut().filter(|e| e.response.is_none() && e.error.is_none())
+        else {
+            return Ok(());
+        };
+        match context.response() {
+            Some(response) => {
+                exchange.response = Some(CapturedResponse {
+                    status: response.status().as_u16(),
+                    headers: header_pairs(response.headers()),
+                    body: CapturedBody::new(response.body().bytes(), self.body_limit),
+                });
+            }
+            None => {
+                exchange.error = context
+                    .output_or_error()
+                    .and_then(|result| result.err())
+                    .map(|e| format!("{:?}", e));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn header_pairs(headers: &Headers) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+impl Exchange {
+    /// A curl command line that replays the request, signature included. The signature
+    /// stays valid for the usual 15 minutes SigV4 allows for clock skew.
+    pub fn to_curl(&self) -> String {
+        let mut cmd = format!("curl -i -X {} {}", self.method, shell_quote(&self.uri));
+        for (name, value) in &self.request_headers {
+            write!(cmd, " \\\n  -H {}", shell_quote(&format!("{}: {}", name, value))).unwrap();
+        }
+        match &self.request_body {
+            CapturedBody::Bytes { data, total_len } if *total_len > 0 => {
+                if data.len() == *total_len && std::str::from_utf8(data).is_ok() {
+                    let body = String::from_utf8_lossy(data);
+                    write!(cmd, " \\\n  --data-binary {}", shell_quote(&body)).unwrap();
+                } else {
+                    write!(cmd, " \\\n  --data-binary @body.bin  # {} byte body", total_len).unwrap();
+                }
+            }
+            CapturedBody::Streaming => cmd.push_str(" \\\n  --data-binary @body.bin  # streamed body"),
+            CapturedBody::Bytes { .. } => {}
+        }
+        cmd
+    }
+}
+
+impl fmt::Display for Exchange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = if self.teardown { " (teardown)" } else { "" };
+        writeln!(f, "{} {} {}{}", self.operation, self.method, self.uri, phase)?;
+        writeln!(f, "{}", self.to_curl())?;
+        match (&self.response, &self.error) {
+            (Some(response), _) => {
+                writeln!(f, "< HTTP {}", response.status)?;
+                for (name, value) in &response.headers {
+                    writeln!(f, "< {}: {}", name, value)?;
+                }
+                write!(f, "<\n{}", response.body)
+            }
+            (None, Some(error)) => write!(f, "! {}", error),
+            (None, None) => write!(f, "! no response"),
+        }
+    }
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
@@ -0,0 +1,285 @@
+//! Record and replay of a run's HTTP traffic.
+//!
+//! `--record` writes every request and response of every test to a JSON cassette, together
+//! with the run ID and payload seed. `--replay` runs the same scenarios again with the
+//! server's side taken from the cassette, so a failure recorded in CI can be debugged locally
+//! without a server. Both the SDK client and the raw client go through the test's [`Tape`].
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use aws_sdk_s3::primitives::{ByteStream, SdkBody};
+use aws_smithy_runtime_api::client::http::{
+    HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpClient,
+    SharedHttpConnector,
+};
+use aws_smithy_runtime_api::client::orchestrator::{HttpRequest, HttpResponse};
+use aws_smithy_runtime_api::client::result::ConnectorError;
+use aws_smithy_runtime_api::client::runtime_components::RuntimeComponents;
+use aws_smithy_runtime_api::http::StatusCode;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::guard::BoxError;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cassette {
+    pub run_id: String,
+    pub seed: u64,
+    /// Interactions of each test in the order they happened.
+    pub tests: BTreeMap<String, Vec<Interaction>>,
+}
+
+impl Cassette {
+    pub fn new(run_id: String, seed: u64) -> Self {
+        Cassette { run_id, seed, tests: BTreeMap::new() }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, BoxError> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read cassette {}: {}", path.display(), e))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), BoxError> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// A cassette being written by `--record` or read by `--replay`.
+pub enum Recorder {
+    Record(Cassette),
+    Replay(Cassette),
+}
+
+impl Recorder {
+    /// A fresh tape for `test`, preloaded with its recording when replaying.
+    pub fn tape(&self, test: &str) -> Tape {
+        match self {
+            Recorder::Record(_) => Tape::record(),
+            Recorder::Replay(cassette) => Tape::replay(cassette.tests.get(test).cloned().unwrap_or_default()),
+        }
+    }
+
+    /// Files what `tape` recorded under `test`.
+    pub fn store(&mut self, test: &str, tape: &Tape) {
+        if let Recorder::Record(cassette) = self {
+            cassette.tests.insert(test.to_string(), tape.take());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub method: String,
+    /// Path and query only, so a cassette replays regardless of the endpoint it came from.
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<RecordedResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RecordedError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: RecordedBody,
+}
+
+/// Text bodies are stored as they are, so XML stays readable in the cassette.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedBody {
+    Text(String),
+    Hex(String),
+}
+
+impl RecordedBody {
+    pub fn new(data: &[u8]) -> Self {
+        match std::str::from_utf8(data) {
+            Ok(text) => RecordedBody::Text(text.to_string()),
+            Err(_) => RecordedBody::Hex(hex::encode(data)),
+        }
+    }
+
+    pub fn bytes(&self) -> Bytes {
+        match self {
+            RecordedBody::Text(text) => Bytes::from(text.clone()),
+            RecordedBody::Hex(data) => Bytes::from(hex::decode(data).unwrap_or_default()),
+        }
+    }
+}
+
+/// A transport error, kept with its kind so that timeouts replay as timeouts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedError {
+    pub timeout: bool,
+    pub message: String,
+}
+
+/// The traffic of a single test: appended to while recording, consumed while replaying.
+#[derive(Debug, Clone)]
+pub enum Tape {
+    Record(Arc<Mutex<Vec<Interaction>>>),
+    Replay(Arc<Mutex<VecDeque<Interaction>>>),
+}
+
+impl Tape {
+    pub fn record() -> Self {
+        Tape::Record(Arc::default())
+    }
+
+    pub fn replay(interactions: Vec<Interaction>) -> Self {
+        Tape::Replay(Arc::new(Mutex::new(interactions.into())))
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self, Tape::Replay(_))
+    }
+
+    pub fn push(&self, interaction: Interaction) {
+        if let Tape::Record(interactions) = self {
+            interactions.lock().unwrap().push(interaction);
+        }
+    }
+
+    /// The next recorded interaction, which must be for the same request.
+    pub fn next(&self, method: &str, uri: &str) -> Result<Interaction, BoxError> {
+        let Tape::Replay(interactions) = self else {
+            return Err("not replaying".into());
+        };
+        let interaction = interactions
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| format!("replay diverged: {} {} was never recorded", method, uri))?;
+        if interaction.method != method || interaction.uri != uri {
+            return Err(format!(
+                "replay diverged: sent {} {}, the recording has {} {}",
+                method, uri, interaction.method, interaction.uri
+            )
+            .into());
+        }
+        Ok(interaction)
+    }
+
+    /// Everything recorded so far.
+    pub fn take(&self) -> Vec<Interaction> {
+        match self {
+            Tape::Record(interactions) => std::mem::take(&mut *interactions.lock().unwrap()),
+            Tape::Replay(_) => Vec::new(),
+        }
+    }
+}
+
+/// [`HttpClient`] for the SDK: forwards to `inner` and records, or answers from the tape.
+#[derive(Debug)]
+pub struct TapeClient {
+    inner: Option<SharedHttpClient>,
+    tape: Tape,
+}
+
+impl TapeClient {
+    /// `inner` is only used while recording.
+    pub fn new(inner: Option<SharedHttpClient>, tape: Tape) -> Self {
+        TapeClient { inner, tape }
+    }
+}
+
+impl HttpClient for TapeClient {
+    fn http_connector(
+        &self,
+        settings: &HttpConnectorSettings,
+        components: &RuntimeComponents,
+    ) -> SharedHttpConnector {
+        let inner = match &self.tape {
+            Tape::Record(_) => self.inner.as_ref().map(|c| c.http_connector(settings, components)),
+            Tape::Replay(_) => None,
+        };
+        SharedHttpConnector::new(TapeConnector { inner, tape: self.tape.clone() })
+    }
+}
+
+#[derive(Debug)]
+struct TapeConnector {
+    inner: Option<SharedHttpConnector>,
+    tape: Tape,
+}
+
+impl HttpConnector for TapeConnector {
+    fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+        let method = request.method().to_string();
+        let uri = path_and_query(request.uri());
+        let tape = self.tape.clone();
+
+        if tape.is_replay() {
+            return HttpConnectorFuture::ready(replay(&tape, &method, &uri));
+        }
+        let Some(inner) = self.inner.clone() else {
+            let error = ConnectorError::other("no HTTP client to record through".into(), None);
+            return HttpConnectorFuture::ready(Err(error));
+        };
+        HttpConnectorFuture::new(async move {
+            let response = match inner.call(request).await {
+                Ok(response) => response,
+                Err(e) => {
+                    let error = RecordedError { timeout: e.is_timeout(), message: e.to_string() };
+                    tape.push(Interaction { method, uri, response: None, error: Some(error) });
+                    return Err(e);
+                }
+            };
+
+            // Buffer the body so it can be both recorded and handed on to the SDK.
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = ByteStream::new(response.into_body())
+                .collect()
+                .await
+                .map_err(|e| ConnectorError::io(e.into()))?
+                .into_bytes();
+            tape.push(Interaction {
+                method,
+                uri,
+                response: Some(RecordedResponse {
+                    status: status.as_u16(),
+                    headers: headers.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect(),
+                    body: RecordedBody::new(&body),
+                }),
+                error: None,
+            });
+
+            let mut response = HttpResponse::new(status, SdkBody::from(body));
+            *response.headers_mut() = headers;
+            Ok(response)
+        })
+    }
+}
+
+fn replay(tape: &Tape, method: &str, uri: &str) -> Result<HttpResponse, ConnectorError> {
+    let interaction = tape.next(method, uri).map_err(|e| ConnectorError::other(e, None))?;
+    match (interaction.response, interaction.error) {
+        (Some(recorded), _) => {
+            let status = StatusCode::try_from(recorded.status).map_err(|e| ConnectorError::other(e.into(), None))?;
+            let mut response = HttpResponse::new(status, SdkBody::from(recorded.body.bytes()));
+            for (name, value) in recorded.headers {
+                response.headers_mut().append(name, value);
+            }
+            Ok(response)
+        }
+        (None, Some(error)) if error.timeout => Err(ConnectorError::timeout(error.message.into())),
+        (None, Some(error)) => Err(ConnectorError::io(error.message.into())),
+        (None, None) => Err(ConnectorError::other("recorded interaction has no outcome".into(), None)),
+    }
+}
+
+/// The part of a request URI that identifies the request independently of the endpoint.
+pub fn path_and_query(uri: &str) -> String {
+    match uri.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or_else(|| "/".to_string(), |i| rest[i..].to_string()),
+        None => uri.to_string(),
+    }
+}
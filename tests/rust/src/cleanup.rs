@@ -0,0 +1,108 @@
+use std::collections::BTreeSet;
+
+use aws_sdk_s3::Client;
+use tracing::{info, warn};
+
+use crate::cli::CleanupArgs;
+use crate::guard::{list_uploads, list_versions, teardown_bucket, BoxError};
+use crate::naming::{unix_now, RunId};
+use crate::runner::TestContext;
+
+/// Deletes every bucket that follows the harness naming pattern, together with its
+/// multipart uploads, object versions and objects.
+pub async fn run(client: &Client, args: &CleanupArgs) -> Result<(), BoxError> {
+    let resp = client.list_buckets().send().await?;
+    let now = unix_now();
+
+    let mut removed = 0;
+    let mut failed = 0;
+    for bucket in resp.buckets() {
+        let Some(name) = bucket.name() else { continue };
+        let Some(run_id) = RunId::from_bucket(name) else { continue };
+        if now.saturating_sub(run_id.timestamp()) < args.older_than {
+            info!(bucket = name, %run_id, "skipping bucket of a recent run");
+            continue;
+        }
+
+        match teardown_bucket(client, name).await {
+            Ok(()) => {
+                info!(bucket = name, "removed bucket");
+                removed += 1;
+            }
+            Err(e) => {
+                warn!(bucket = name, error = ?e, "failed to remove bucket");
+                failed += 1;
+            }
+        }
+    }
+
+    info!(removed, failed, "cleanup finished");
+    if failed > 0 {
+        return Err(format!("{} bucket(s) could not be removed", failed).into());
+    }
+    Ok(())
+}
+
+/// What the run left on the server once every test has torn down: each bucket named for the
+/// run that the run's user or any other user it knows of still lists, with the objects, object
+/// versions and multipart uploads still in it. Everything a run creates is in a bucket named
+/// for it, so nothing else can be left. A PHP server that acknowledges a delete it did not
+/// carry out shows up here. Nothing has been left as far as it can tell when the server cannot
+/// be asked.
+pub async fn leftovers(ctx: &TestContext) -> Vec<String> {
+    let users = ctx.other_user.iter().chain(ctx.tenants.iter()).map(|user| ctx.client_as(user));
+    let clients: Vec<Client> = std::iter::once(ctx.client.clone()).chain(users).collect();
+
+    let mut seen = BTreeSet::new();
+    let mut leftovers = Vec::new();
+    for (i, client) in clients.iter().enumerate() {
+        let buckets = match client.list_buckets().send().await {
+            Ok(resp) => resp.buckets().iter().filter_map(|b| b.name().map(str::to_string)).collect(),
+            Err(e) if i == 0 => {
+                warn!(error = ?e, "could not check the server for buckets left after teardown");
+                return Vec::new();
+            }
+            // Another user the run knows of may not be allowed to list buckets at all.
+            Err(e) => {
+                warn!(error = ?e, "could not list the buckets of another user");
+                Vec::new()
+            }
+        };
+        for bucket in buckets {
+            if RunId::from_bucket(&bucket) != Some(ctx.run_id) || !seen.insert(bucket.clone()) {
+                continue;
+            }
+            let remains = remains(client, &bucket).await;
+            warn!(bucket, remains, "bucket left on the server after teardown");
+            leftovers.push(format!("bucket {} holding {}", bucket, remains));
+        }
+    }
+    info!(users = clients.len(), leftovers = leftovers.len(), "checked the server for leftovers");
+    leftovers
+}
+
+/// What is still in `bucket`, for a report.
+async fn remains(client: &Client, bucket: &str) -> String {
+    let mut objects = 0;
+    let mut pages = client.list_objects_v2().bucket(bucket).into_paginator().send();
+    while let Some(Ok(page)) = pages.next().await {
+        objects += page.contents().len();
+    }
+    // A server without versioning or multipart uploads rejects their listings, as in teardown.
+    let versions = list_versions(client, bucket).await.map_or(0, |versions| versions.len());
+    let uploads = list_uploads(client, bucket).await.map_or(0, |uploads| uploads.len());
+    format!("{} object(s), {} object version(s) and {} multipart upload(s)", objects, versions, uploads)
+}
+
+/// Prints what the run left behind, and fails with it if that is anything.
+pub fn report(leftovers: &[String]) -> Result<(), BoxError> {
+    if leftovers.is_empty() {
+        return Ok(());
+    }
+    println!("\n=== left on the server after teardown");
+    for leftover in leftovers {
+        println!("- {}", leftover);
+    }
+    println!("remove them with `s3test cleanup`");
+    Err(format!("{} bucket(s) of the run were left on the server", leftovers.len()).into())
+}
@@ -0,0 +1,90 @@
+use aws_sdk_s3::config::{Credentials, Region, SharedHttpClient};
+use aws_smithy_http_client::{tls, Connector};
+use aws_sdk_s3::Client;
+
+use crate::cli::{ConnectionArgs, RetryArgs};
+use crate::guard::BoxError;
+use crate::otel;
+use crate::pool::{self, CloseConnections, ConnectionLimit};
+use crate::retry::{self, RetryableOperations};
+use crate::transport::{Transport, TransportClient};
+
+/// The credentials given on the command line.
+pub fn credentials(args: &ConnectionArgs) -> Credentials {
+    Credentials::new(&args.access_key, &args.secret_key, args.session_token.clone(), None, "local")
+}
+
+/// Builds an S3 client for the endpoint, credentials and retry policy given on the command line.
+pub async fn build_client(args: &ConnectionArgs, retry_args: &RetryArgs) -> Result<Client, BoxError> {
+    let shared_config = aws_config::defaults(aws_config::BehaviorVersion::latest())
+        .endpoint_url(sdk_endpoint_url(&args.endpoint))
+        .credentials_provider(credentials(args))
+        .region(Region::new(args.region.clone()))
+        .retry_config(retry::retry_config(retry_args))
+        .http_client(http_client(args)?)
+        .load()
+        .await;
+
+    let mut config = aws_sdk_s3::config::Builder::from(&shared_config);
+    if let Some(interceptor) = RetryableOperations::from_args(retry_args) {
+        config = config.interceptor(interceptor);
+    }
+    if otel::enabled() {
+        config = config.interceptor(otel::TraceContext);
+    }
+    if args.connection_close {
+        config = config.interceptor(CloseConnections);
+    }
+
+    Ok(Client::from_conf(config.build()))
+}
+
+/// The endpoint as the SDK needs it: its endpoint rules append the bucket to the URL's path
+/// as is, so a server mounted under a path needs the trailing slash back.
+pub fn sdk_endpoint_url(endpoint: &str) -> String {
+    let has_path = endpoint.split_once("://").is_some_and(|(_, rest)| rest.contains('/'));
+    match has_path && !endpoint.ends_with('/') {
+        true => format!("{}/", endpoint),
+        false => endpoint.to_string(),
+    }
+}
+
+fn http_client(args: &ConnectionArgs) -> Result<SharedHttpClient, BoxError> {
+    let client = sdk_http_client(args)?;
+    Ok(match args.max_connections_per_host {
+        Some(max) => SharedHttpClient::new(ConnectionLimit::new(client, max)),
+        None => client,
+    })
+}
+
+/// The SDK's default HTTPS client, set explicitly so that per-test clients can wrap it, going
+/// through `--proxy` if one was given and trusting `--ca-cert` on top of the system's roots.
+fn sdk_http_client(args: &ConnectionArgs) -> Result<SharedHttpClient, BoxError> {
+    if Transport::required_for_sdk(args) {
+        return Ok(SharedHttpClient::new(TransportClient::new(Transport::new(args)?)));
+    }
+    let context = crate::tls::smithy_context(args.ca_cert.as_deref())?;
+    let idle = pool::Idle::from_args(args);
+    let Some(proxy) = &args.proxy else {
+        let mut builder = aws_smithy_http_client::Builder::new();
+        builder.set_pool_idle_timeout(idle.timeout).set_pool_max_idle_per_host(idle.max_per_host);
+        return Ok(builder.tls_provider(tls_provider()).tls_context(context).build_https());
+    };
+    let proxy = proxy.smithy_config();
+    Ok(aws_smithy_http_client::Builder::new().build_with_connector_fn(move |settings, components| {
+        let mut connector = Connector::builder().enable_tcp_nodelay(true).proxy_config(proxy.clone());
+        // The pool settings of the outer builder do not reach connectors built here.
+        connector.set_pool_idle_timeout(idle.timeout).set_pool_max_idle_per_host(idle.max_per_host);
+        if let Some(settings) = settings {
+            connector = connector.connector_settings(settings.clone());
+        }
+        if let Some(sleep) = components.and_then(|c| c.sleep_impl()) {
+            connector = connector.sleep_impl(sleep);
+        }
+        connector.tls_provider(tls_provider()).tls_context(context.clone()).build()
+    }))
+}
+
+fn tls_provider() -> tls::Provider {
+    tls::Provider::Rustls(tls::rustls_provider::CryptoMode::AwsLc)
+}
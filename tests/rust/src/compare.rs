@@ -0,0 +1,113 @@
+//! Running the suite against several endpoints in turn (`run --endpoint A --endpoint B`).
+//!
+//! Meant for checking that a new version of the PHP server behaves like the previous one: every
+//! endpoint gets the same run ID and seed, so the tests send identical requests, and the final
+//! report puts the outcomes of each test next to each other. Unlike `--reference-endpoint`, the
+//! HTTP traffic is not compared, only the verdicts.
+
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use rand::Rng;
+use tracing::info;
+
+use crate::cleanup;
+use crate::cli::{ConnectionArgs, RetryArgs, RunArgs};
+use crate::guard::BoxError;
+use crate::health;
+use crate::naming::RunId;
+use crate::runner::{self, Gate, Scenario, TestContext, TestResult, Verdict};
+
+/// Runs `scenarios` against every endpoint, then prints the outcomes side by side. Fails if a
+/// test did not end the same way everywhere, or if the run left something on an endpoint.
+pub async fn run(
+    connection: &ConnectionArgs,
+    retry: &RetryArgs,
+    args: &RunArgs,
+    scenarios: &[Scenario],
+    gate: &mut Gate<'_>,
+) -> Result<(), BoxError> {
+    let unsupported = [
+        ("--spawn-server", args.spawn.spawn_server),
+        ("--reference-endpoint", args.reference.reference_endpoint.is_some()),
+        ("--record", args.record.is_some()),
+        ("--replay", args.replay.is_some()),
+        ("--resume", args.resume),
+        ("--watch", args.watch.is_some()),
+        ("--matrix", args.matrix.is_some()),
+    ];
+    if let Some((option, _)) = unsupported.iter().find(|(_, given)| *given) {
+        return Err(format!("{} cannot be combined with more than one --endpoint", option).into());
+    }
+
+    let run_id = RunId::generate();
+    let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+    info!(%run_id, seed, endpoints = connection.endpoints.len(), "starting comparison run");
+
+    let (mut columns, mut leftovers) = (Vec::new(), Vec::new());
+    for endpoint in &connection.endpoints {
+        println!("\n##### {}", endpoint);
+        let connection = connection.with_endpoint(endpoint);
+        let ctx = TestContext::connect(&connection, retry, run_id, seed).await?;
+        let ctx = ctx.with_sts(&args.sts, &connection);
+        let budget = Duration::from_secs(args.ready_timeout);
+        let interval = Duration::from_millis(args.ready_interval_ms);
+        health::wait_until_ready(&ctx.client, endpoint, budget, interval).await?;
+        let server = runner::server_version(&ctx, scenarios, args, Some(gate)).await;
+        gate.set_server_version(server.clone());
+        let results = runner::run_all(&ctx, None, scenarios, args, server.as_ref(), None, None).await;
+        // Failures on one endpoint are reported below, next to the other endpoints' outcomes.
+        let _ = runner::summarize(&results, gate);
+        columns.push(results);
+        if !args.keep_data {
+            let left = cleanup::leftovers(&ctx).await;
+            leftovers.extend(left.into_iter().map(|leftover| format!("{} on {}", leftover, endpoint)));
+        }
+    }
+
+    let differing = report(&connection.endpoints, scenarios, &columns);
+    let left = cleanup::report(&leftovers);
+    if differing > 0 {
+        println!("replay with --seed {}", seed);
+        return Err(format!("{} test(s) ended differently across endpoints", differing).into());
+    }
+    left
+}
+
+fn outcome(verdict: &Verdict) -> &'static str {
+    match verdict {
+        Verdict::Passed => "passed",
+        Verdict::Failed(_) => "FAILED",
+        Verdict::TimedOut(_) => "TIMED OUT",
+        Verdict::Skipped(_) => "skipped",
+    }
+}
+
+/// Prints one row per test and one column per endpoint, marking the rows whose outcomes differ.
+/// Returns the number of those rows.
+fn report(endpoints: &[String], scenarios: &[Scenario], columns: &[Vec<TestResult>]) -> usize {
+    let width = endpoints.iter().map(|e| e.len()).max().unwrap_or(0).max(18);
+    println!("\n===== comparison");
+    let mut header = format!("\n{:<42}", "");
+    for endpoint in endpoints {
+        let _ = write!(header, " {:<width$}", endpoint);
+    }
+    println!("{}", header.trim_end());
+
+    let mut differing = 0;
+    for (row, scenario) in scenarios.iter().enumerate() {
+        let results: Vec<&TestResult> = columns.iter().map(|column| &column[row]).collect();
+        let same = results.windows(2).all(|w| outcome(&w[0].verdict) == outcome(&w[1].verdict));
+        if !same {
+            differing += 1;
+        }
+        let mut line = format!("{:<40} {}", scenario.name, if same { ' ' } else { '*' });
+        for result in results {
+            let cell = format!("{} {:.2}s", outcome(&result.verdict), result.duration.as_secs_f64());
+            let _ = write!(line, " {:<width$}", cell);
+        }
+        println!("{}", line.trim_end());
+    }
+    println!("\n{} of {} test(s) ended differently (marked *)", differing, scenarios.len());
+    differing
+}
@@ -0,0 +1,239 @@
+//! `s3test completions SHELL`: a completion script for bash, zsh or fish, generated from the
+//! clap definition of the command line so it never falls behind the options.
+//!
+//! Besides subcommands and options, the scripts complete the values of options with a fixed
+//! set of them (`--matrix-format`, `--orchestrator`, ...) and offer the scenario names for
+//! `--only` and the selectors for `--require` and `--allow-fail`.
+//!
+//! Install with e.g. `s3test completions bash > /etc/bash_completion.d/s3test`,
+//! `s3test completions zsh > "${fpath[1]}/_s3test"` or
+//! `s3test completions fish > ~/.config/fish/completions/s3test.fish`.
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+
+use clap::{Arg, CommandFactory, ValueEnum};
+
+use crate::cli::Cli;
+use crate::scenarios;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// An option as the scripts need it.
+struct Opt {
+    long: String,
+    help: String,
+    takes_value: bool,
+    repeatable: bool,
+    values: Vec<String>,
+}
+
+struct Subcommand {
+    name: String,
+    about: String,
+    opts: Vec<Opt>,
+    /// Possible values of its first positional argument, like the shell of `completions`.
+    values: Vec<String>,
+}
+
+/// Options accepted before any subcommand (those of `run`, the default) and every subcommand,
+/// global options included.
+struct Spec {
+    top: Vec<Opt>,
+    subcommands: Vec<Subcommand>,
+}
+
+pub fn print(shell: Shell) {
+    let mut command = Cli::command();
+    // Building propagates the global options to the subcommands.
+    command.build();
+    let spec = Spec {
+        top: options(&command),
+        subcommands: command
+            .get_subcommands()
+            .filter(|s| s.get_name() != "help")
+            .map(|s| Subcommand {
+                name: s.get_name().to_string(),
+                about: s.get_about().map(|a| a.to_string()).unwrap_or_default(),
+                opts: options(s),
+                values: s
+                    .get_positionals()
+                    .next()
+                    .map(|arg| arg.get_possible_values().iter().map(|v| v.get_name().to_string()).collect())
+                    .unwrap_or_default(),
+            })
+            .collect(),
+    };
+    let script = match shell {
+        Shell::Bash => bash(&spec),
+        Shell::Zsh => zsh(&spec),
+        Shell::Fish => fish(&spec),
+    };
+    print!("{}", script);
+}
+
+fn options(command: &clap::Command) -> Vec<Opt> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .filter_map(|arg| {
+            let long = arg.get_long()?.to_string();
+            Some(Opt {
+                help: arg.get_help().map(|h| h.to_string()).unwrap_or_default(),
+                takes_value: arg.get_action().takes_values(),
+                repeatable: matches!(arg.get_action(), clap::ArgAction::Append),
+                values: values(&long, arg),
+                long,
+            })
+        })
+        .collect()
+}
+
+/// The values worth offering for `arg`: its possible values, or for the options that pick
+/// scenarios, the scenarios.
+fn values(long: &str, arg: &Arg) -> Vec<String> {
+    let all = scenarios::all();
+    match long {
+        "only" => all.iter().map(|s| s.name.to_string()).collect(),
+        "require" | "allow-fail" => {
+            let mut selectors = BTreeSet::new();
+            for scenario in &all {
+                selectors.extend(scenario.tags.iter().map(|tag| format!("tag={}", tag)));
+                let suite = scenario.name.split("::").next().unwrap_or(scenario.name);
+                selectors.insert(format!("suite={}", suite));
+                selectors.insert(format!("test={}", scenario.name));
+            }
+            selectors.into_iter().collect()
+        }
+        _ if arg.get_action().takes_values() => {
+            arg.get_possible_values().iter().map(|v| v.get_name().to_string()).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn bash(spec: &Spec) -> String {
+    let names: Vec<&str> = spec.subcommands.iter().map(|s| s.name.as_str()).collect();
+    let longs = |opts: &[Opt]| opts.iter().map(|o| format!("--{}", o.long)).collect::<Vec<_>>().join(" ");
+
+    let mut out = String::new();
+    out.push_str("_s3test() {\n");
+    out.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    out.push_str("    local cmd=\"\" word\n");
+    out.push_str("    for word in \"${COMP_WORDS[@]:1:COMP_CWORD-1}\"; do\n");
+    let _ = writeln!(out, "        case \"$word\" in {}) cmd=\"$word\"; break ;; esac", names.join("|"));
+    out.push_str("    done\n\n");
+
+    // Every option with values to offer, whatever the subcommand; the same long name means the
+    // same thing throughout the CLI.
+    let mut valued = BTreeSet::new();
+    out.push_str("    case \"$prev\" in\n");
+    for opt in spec.top.iter().chain(spec.subcommands.iter().flat_map(|s| &s.opts)) {
+        if !opt.values.is_empty() && valued.insert(opt.long.as_str()) {
+            let _ = writeln!(
+                out,
+                "        --{}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;",
+                opt.long,
+                opt.values.join(" ")
+            );
+        }
+    }
+    out.push_str("    esac\n\n");
+
+    out.push_str("    local words\n    case \"$cmd\" in\n");
+    let _ = writeln!(out, "        \"\") words=\"{} {}\" ;;", names.join(" "), longs(&spec.top));
+    for sub in &spec.subcommands {
+        let words = format!("{} {}", sub.values.join(" "), longs(&sub.opts));
+        let _ = writeln!(out, "        {}) words=\"{}\" ;;", sub.name, words.trim_start());
+    }
+    out.push_str("    esac\n");
+    out.push_str("    COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))\n");
+    out.push_str("}\n");
+    out.push_str("complete -o default -F _s3test s3test\n");
+    out
+}
+
+fn zsh(spec: &Spec) -> String {
+    fn escape(text: &str) -> String {
+        text.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]").replace(':', "\\:")
+    }
+    fn specs(opts: &[Opt]) -> String {
+        let mut out = String::new();
+        for opt in opts {
+            let repeat = if opt.repeatable { "*" } else { "" };
+            let value = match (opt.takes_value, opt.values.is_empty()) {
+                (false, _) => String::new(),
+                (true, true) => ":value:_default".to_string(),
+                (true, false) => format!(":value:({})", opt.values.join(" ")),
+            };
+            let equals = if opt.takes_value { "=" } else { "" };
+            let help = escape(&opt.help);
+            let _ = write!(out, " \\\n        '{}--{}{}[{}]{}'", repeat, opt.long, equals, help, value);
+        }
+        out
+    }
+
+    let mut out = String::from("#compdef s3test\n\n_s3test() {\n");
+    out.push_str("    local -a commands\n    commands=(\n");
+    for sub in &spec.subcommands {
+        let _ = writeln!(out, "        '{}:{}'", sub.name, escape(&sub.about));
+    }
+    out.push_str("    )\n\n");
+    let _ = writeln!(out, "    _arguments -C{} \\", specs(&spec.top));
+    out.push_str("        '1: :->command' \\\n        '*:: :->args'\n");
+    out.push_str("    case $state in\n");
+    out.push_str("        command) _describe -t commands 'command' commands ;;\n");
+    out.push_str("        args)\n            case $words[1] in\n");
+    for sub in &spec.subcommands {
+        let mut specs = specs(&sub.opts);
+        if !sub.values.is_empty() {
+            let _ = write!(specs, " \\\n        '1:value:({})'", sub.values.join(" "));
+        }
+        let _ = writeln!(out, "                {}) _arguments{} ;;", sub.name, specs);
+    }
+    out.push_str("            esac ;;\n    esac\n}\n\n_s3test \"$@\"\n");
+    out
+}
+
+fn fish(spec: &Spec) -> String {
+    fn escape(text: &str) -> String {
+        text.replace('\\', "\\\\").replace('\'', "\\'")
+    }
+    fn line(out: &mut String, condition: &str, opt: &Opt) {
+        let _ = write!(out, "complete -c s3test -n '{}' -l {}", condition, opt.long);
+        match (opt.takes_value, opt.values.is_empty()) {
+            (false, _) => {}
+            (true, true) => out.push_str(" -r -F"),
+            (true, false) => {
+                let _ = write!(out, " -x -a '{}'", opt.values.join(" "));
+            }
+        }
+        let _ = writeln!(out, " -d '{}'", escape(&opt.help));
+    }
+
+    let mut out = String::from("complete -c s3test -f\n\n");
+    for sub in &spec.subcommands {
+        let about = escape(&sub.about);
+        let _ = writeln!(out, "complete -c s3test -n '__fish_use_subcommand' -a {} -d '{}'", sub.name, about);
+    }
+    out.push('\n');
+    for opt in &spec.top {
+        line(&mut out, "__fish_use_subcommand", opt);
+    }
+    for sub in &spec.subcommands {
+        out.push('\n');
+        let condition = format!("__fish_seen_subcommand_from {}", sub.name);
+        if !sub.values.is_empty() {
+            let _ = writeln!(out, "complete -c s3test -n '{}' -a '{}'", condition, sub.values.join(" "));
+        }
+        for opt in &sub.opts {
+            line(&mut out, &condition, opt);
+        }
+    }
+    out
+}
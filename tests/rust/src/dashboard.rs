@@ -0,0 +1,283 @@
+//! A live dashboard on the terminal for `bench`, `load` and `soak` (`--dashboard`): requests in
+//! flight, throughput over the last ten seconds, errors, and a sparkline per operation of its
+//! mean latency second by second, redrawn from the counters `/metrics` serves. While it is up,
+//! log output goes to a pane at its bottom; the lines kept are printed again when it closes.
+//! Ctrl-C closes it and interrupts the workload, whose buckets are then removed as on any error.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::future;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use console::Term;
+use tokio::runtime::Handle;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+use tracing::warn;
+
+use crate::cli::MetricsArgs;
+use crate::metrics::{Metrics, Totals};
+
+/// How often the dashboard is redrawn; each bar of a sparkline is one such step.
+const REFRESH: Duration = Duration::from_secs(1);
+/// Steps the throughput is averaged over.
+const ROLLING: usize = 10;
+/// Steps remembered, more than the widest terminal shows.
+const HISTORY: usize = 512;
+/// Log lines kept for the pane and for printing when the dashboard closes.
+const EVENT_LIMIT: usize = 200;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// Switch to the alternate screen and hide the cursor, and back.
+const ENTER: &str = "\x1b[?1049h\x1b[?25l";
+const LEAVE: &str = "\x1b[?25h\x1b[?1049l";
+/// Columns left of the sparklines.
+const LABEL_WIDTH: usize = 40;
+
+struct Events {
+    lines: VecDeque<String>,
+    dropped: usize,
+}
+
+/// Log lines while a dashboard is up, None otherwise. Drawing a frame and closing the dashboard
+/// both hold the lock, so that the terminal is never restored halfway through a frame.
+static EVENTS: Mutex<Option<Events>> = Mutex::new(None);
+
+/// Takes log output for the dashboard's pane while one is up; false when none is.
+pub fn capture(buf: &[u8]) -> bool {
+    let mut events = EVENTS.lock().unwrap();
+    let Some(events) = events.as_mut() else {
+        return false;
+    };
+    for line in String::from_utf8_lossy(buf).lines() {
+        if events.lines.len() == EVENT_LIMIT {
+            events.lines.pop_front();
+            events.dropped += 1;
+        }
+        events.lines.push_back(line.to_string());
+    }
+    true
+}
+
+/// The dashboard, drawn until dropped or until Ctrl-C.
+pub struct Dashboard {
+    task: JoinHandle<()>,
+    interrupted: watch::Receiver<bool>,
+}
+
+/// Resolves once Ctrl-C has closed `dashboard`; never without one.
+pub async fn interrupted(dashboard: Option<&Dashboard>) {
+    if let Some(dashboard) = dashboard {
+        let mut interrupted = dashboard.interrupted.clone();
+        if interrupted.wait_for(|&interrupted| interrupted).await.is_ok() {
+            return;
+        }
+    }
+    future::pending().await
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.task.abort();
+        close();
+    }
+}
+
+/// Takes over the terminal for the dashboard if `--dashboard` was given and stderr is one.
+pub fn show(args: &MetricsArgs, metrics: &Arc<Metrics>) -> Option<Dashboard> {
+    if !args.dashboard {
+        return None;
+    }
+    let term = Term::stderr();
+    if !term.is_term() {
+        warn!("--dashboard needs stderr to be a terminal, running without it");
+        return None;
+    }
+    *EVENTS.lock().unwrap() = Some(Events { lines: VecDeque::new(), dropped: 0 });
+    let _ = io::stderr().write_all(ENTER.as_bytes());
+
+    let metrics = metrics.clone();
+    let (interrupt, interrupted) = watch::channel(false);
+    let task = tokio::spawn(async move {
+        let mut history = History::new();
+        let mut ticks = time::interval_at(Instant::now() + REFRESH, REFRESH);
+        loop {
+            if !draw(&term, &metrics, &history) {
+                return;
+            }
+            tokio::select! {
+                _ = ticks.tick() => history.step(metrics.totals()),
+                // The workload stops and removes what it created, logging to the terminal.
+                _ = tokio::signal::ctrl_c() => {
+                    close();
+                    let _ = interrupt.send(true);
+                    return;
+                }
+            }
+        }
+    });
+    Some(Dashboard { task, interrupted })
+}
+
+/// Gives the terminal back and prints the log lines the pane kept.
+fn close() {
+    let Some(events) = EVENTS.lock().unwrap().take() else {
+        return;
+    };
+    // Tokio never uninstalls the SIGINT handler the dashboard listened with, which would leave
+    // Ctrl-C doing nothing for the rest of the run; this gives it back its default effect.
+    if let Ok(runtime) = Handle::try_current() {
+        runtime.spawn(async {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        });
+    }
+    let mut stderr = io::stderr();
+    let _ = stderr.write_all(LEAVE.as_bytes());
+    if events.dropped > 0 {
+        let _ = writeln!(stderr, "({} earlier log line(s) were not kept)", events.dropped);
+    }
+    for line in events.lines {
+        let _ = writeln!(stderr, "{}", line);
+    }
+}
+
+/// What the dashboard remembers of earlier steps, oldest first.
+struct History {
+    started: Instant,
+    last: BTreeMap<&'static str, Totals>,
+    /// Requests finished per step.
+    requests: VecDeque<u64>,
+    errors: VecDeque<u64>,
+    /// Mean latency of each operation per step, in seconds; None for a step without requests.
+    latency: BTreeMap<&'static str, VecDeque<Option<f64>>>,
+}
+
+fn push<T>(values: &mut VecDeque<T>, value: T) {
+    if values.len() == HISTORY {
+        values.pop_front();
+    }
+    values.push_back(value);
+}
+
+impl History {
+    fn new() -> Self {
+        History {
+            started: Instant::now(),
+            last: BTreeMap::new(),
+            requests: VecDeque::new(),
+            errors: VecDeque::new(),
+            latency: BTreeMap::new(),
+        }
+    }
+
+    fn step(&mut self, totals: BTreeMap<&'static str, Totals>) {
+        let (mut requests, mut errors) = (0, 0);
+        for (&op, now) in &totals {
+            let before = self.last.get(op).copied().unwrap_or_default();
+            let finished = now.requests - before.requests;
+            requests += finished;
+            errors += now.errors - before.errors;
+            let mean = (finished > 0).then(|| (now.latency_sum - before.latency_sum) / finished as f64);
+            // An operation first seen now had no requests in the steps before.
+            let steps = self.requests.len();
+            let latency = self.latency.entry(op).or_insert_with(|| vec![None; steps].into());
+            push(latency, mean);
+        }
+        push(&mut self.requests, requests);
+        push(&mut self.errors, errors);
+        self.last = totals;
+    }
+
+    /// Requests per second over the last [`ROLLING`] steps.
+    fn throughput(&self) -> f64 {
+        let steps = self.requests.len().min(ROLLING);
+        let requests: u64 = self.requests.iter().rev().take(steps).sum();
+        requests as f64 / (steps.max(1) as f64 * REFRESH.as_secs_f64())
+    }
+}
+
+/// The last `width` values as bars scaled to the largest of them, a gap for a missing one.
+fn sparkline(values: &VecDeque<Option<f64>>, width: usize) -> String {
+    let shown = values.iter().skip(values.len().saturating_sub(width));
+    let max = shown.clone().flatten().fold(0.0_f64, |max, &v| max.max(v));
+    shown
+        .map(|value| match value {
+            None => ' ',
+            Some(_) if max == 0.0 => SPARKS[0],
+            Some(v) => SPARKS[(v / max * (SPARKS.len() - 1) as f64).round() as usize],
+        })
+        .collect()
+}
+
+fn counts(values: &VecDeque<u64>) -> VecDeque<Option<f64>> {
+    values.iter().map(|&v| Some(v as f64)).collect()
+}
+
+fn elapsed(since: Instant) -> String {
+    let seconds = since.elapsed().as_secs();
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// The lines of one frame for a terminal of `rows` by `cols`.
+fn frame(metrics: &Metrics, history: &History, events: &Events, (rows, cols): (u16, u16)) -> Vec<String> {
+    let totals = metrics.totals();
+    let requests: u64 = totals.values().map(|t| t.requests).sum();
+    let errors: u64 = totals.values().map(|t| t.errors).sum();
+    let width = usize::from(cols).saturating_sub(LABEL_WIDTH + 1);
+    let mut lines = vec![
+        format!(
+            "s3test {}  {}  {} in flight  {:.1} req/s over {}s  {} error(s) in {} request(s)",
+            metrics.mode(),
+            elapsed(history.started),
+            metrics.in_flight(),
+            history.throughput(),
+            history.requests.len().clamp(1, ROLLING),
+            errors,
+            requests
+        ),
+        String::new(),
+    ];
+    let head = format!("{:<8} {:>10} {:>8} {:>10}", "op", "requests", "errors", "mean ms");
+    lines.push(format!("{:<width$} {}", head, "mean latency per second", width = LABEL_WIDTH));
+    for (op, totals) in &totals {
+        let mean = totals.latency_sum * 1000.0 / totals.requests.max(1) as f64;
+        let spark = history.latency.get(op).map(|l| sparkline(l, width)).unwrap_or_default();
+        let row = format!("{:<8} {:>10} {:>8} {:>10.2}", op, totals.requests, totals.errors, mean);
+        lines.push(format!("{:<width$} {}", row, spark, width = LABEL_WIDTH));
+    }
+    lines.push(String::new());
+    let rates = [("requests per second", &history.requests), ("errors per second", &history.errors)];
+    for (label, values) in rates {
+        let max = values.iter().max().copied().unwrap_or(0);
+        let label = format!("{} (at most {})", label, max);
+        lines.push(format!("{:<width$} {}", label, sparkline(&counts(values), width), width = LABEL_WIDTH));
+    }
+    lines.push(String::new());
+    lines.push("log".to_string());
+    // The last row stays empty, so that the frame never scrolls the screen.
+    let room = usize::from(rows).saturating_sub(lines.len() + 1);
+    lines.extend(events.lines.iter().skip(events.lines.len().saturating_sub(room)).cloned());
+    lines.truncate(usize::from(rows).saturating_sub(1));
+    lines
+}
+
+/// Draws a frame over the last one. False once the dashboard has been closed.
+fn draw(term: &Term, metrics: &Metrics, history: &History) -> bool {
+    let events = EVENTS.lock().unwrap();
+    let Some(events) = events.as_ref() else {
+        return false;
+    };
+    let size = term.size();
+    let mut out = String::from("\x1b[H");
+    for line in frame(metrics, history, events, size) {
+        out.push_str(&console::truncate_str(&line, usize::from(size.1), ""));
+        // A log line cut short may leave its colour on.
+        out.push_str("\x1b[0m\x1b[K\r\n");
+    }
+    out.push_str("\x1b[J");
+    let _ = io::stderr().write_all(out.as_bytes());
+    true
+}
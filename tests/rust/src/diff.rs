@@ -0,0 +1,120 @@
+//! Differential testing: compares the HTTP exchanges a test produced against the server under
+//! test with those it produced against a reference implementation.
+
+use crate::capture::{CapturedBody, CapturedResponse, Exchange};
+use crate::snapshot::canonicalize;
+
+/// Headers that legitimately differ between servers and between runs.
+const IGNORED_HEADERS: &[&str] = &[
+    "connection",
+    "date",
+    "keep-alive",
+    "server",
+    "transfer-encoding",
+    "vary",
+    "x-amz-bucket-region",
+    "x-amz-id-2",
+    "x-amz-request-id",
+    "x-amz-server-side-encryption",
+    "x-powered-by",
+];
+
+/// Headers whose values, not just their presence, must agree.
+const COMPARED_VALUES: &[&str] = &["accept-ranges", "content-range", "content-type", "etag"];
+
+/// Lists every divergence between the two exchange sequences, pairing exchanges by position.
+pub fn compare(primary: &[Exchange], reference: &[Exchange], run_id: &str) -> Vec<String> {
+    let mut divergences = Vec::new();
+    for (i, (ours, theirs)) in primary.iter().zip(reference).enumerate() {
+        let at = format!("#{} {}", i + 1, ours.operation);
+        if ours.operation != theirs.operation {
+            divergences.push(format!(
+                "{}: call sequence diverges, reference made {} here",
+                at, theirs.operation
+            ));
+            return divergences;
+        }
+        match (&ours.response, &theirs.response) {
+            (Some(ours), Some(theirs)) => compare_responses(&at, ours, theirs, run_id, &mut divergences),
+            (None, Some(theirs)) => {
+                divergences.push(format!("{}: no response, reference answered {}", at, theirs.status))
+            }
+            (Some(ours), None) => {
+                divergences.push(format!("{}: answered {}, reference did not respond", at, ours.status))
+            }
+            (None, None) => {}
+        }
+    }
+    if primary.len() != reference.len() {
+        divergences.push(format!(
+            "{} HTTP exchanges, reference needed {}",
+            primary.len(),
+            reference.len()
+        ));
+    }
+    divergences
+}
+
+fn compare_responses(
+    at: &str,
+    ours: &CapturedResponse,
+    theirs: &CapturedResponse,
+    run_id: &str,
+    divergences: &mut Vec<String>,
+) {
+    if ours.status != theirs.status {
+        divergences.push(format!("{}: status {}, reference {}", at, ours.status, theirs.status));
+    }
+
+    let relevant = |response: &CapturedResponse| -> Vec<(String, String)> {
+        let mut headers: Vec<_> = response
+            .headers
+            .iter()
+            .map(|(n, v)| (n.to_ascii_lowercase(), v.clone()))
+            .filter(|(n, _)| !IGNORED_HEADERS.contains(&n.as_str()))
+            .collect();
+        headers.sort();
+        headers
+    };
+    let (our_headers, their_headers) = (relevant(ours), relevant(theirs));
+    let find = |headers: &[(String, String)], name: &str| {
+        headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone())
+    };
+    for (name, value) in &their_headers {
+        match find(&our_headers, name) {
+            None => divergences.push(format!("{}: missing header {}", at, name)),
+            Some(ours) if (COMPARED_VALUES.contains(&name.as_str()) || name.starts_with("x-amz-meta-"))
+                && ours != *value =>
+            {
+                divergences.push(format!("{}: {} is {:?}, reference {:?}", at, name, ours, value))
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, _) in &our_headers {
+        if find(&their_headers, name).is_none() {
+            divergences.push(format!("{}: extra header {}", at, name));
+        }
+    }
+
+    if let (CapturedBody::Bytes { data: ours, .. }, CapturedBody::Bytes { data: theirs, .. }) =
+        (&ours.body, &theirs.body)
+    {
+        let as_xml = |data: &[u8]| {
+            let text = std::str::from_utf8(data).ok()?.trim();
+            text.starts_with('<').then(|| canonicalize(text, run_id).ok()).flatten()
+        };
+        let same = match (as_xml(ours), as_xml(theirs)) {
+            (Some(ours), Some(theirs)) => ours == theirs,
+            _ => ours == theirs,
+        };
+        if !same {
+            divergences.push(format!(
+                "{}: body differs\n  ours:      {}\n  reference: {}",
+                at,
+                String::from_utf8_lossy(ours),
+                String::from_utf8_lossy(theirs)
+            ));
+        }
+    }
+}
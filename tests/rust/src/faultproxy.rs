@@ -0,0 +1,124 @@
+//! A TCP proxy between the harness and the server that breaks connections on purpose.
+//!
+//! Scenarios point single requests at the proxy to have an upload cut off halfway, a response
+//! truncated or held back, and then check through the direct connection that the server was
+//! left in a consistent state. The fault is chosen per connection, when it is accepted.
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::guard::BoxError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Forward everything unchanged.
+    None,
+    /// Close the connection once this many request bytes have been forwarded.
+    DropRequestAfter(usize),
+    /// Close the connection once this many response bytes have been forwarded.
+    TruncateResponseAfter(usize),
+    /// Hold back every chunk of the response for this long.
+    DelayResponse(Duration),
+}
+
+pub struct FaultProxy {
+    addr: SocketAddr,
+    fault: Arc<Mutex<Fault>>,
+    task: JoinHandle<()>,
+}
+
+impl FaultProxy {
+    /// Listens on a free local port and forwards to `upstream`, a `host:port`.
+    pub async fn start(upstream: &str) -> Result<Self, BoxError> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let fault = Arc::new(Mutex::new(Fault::None));
+        let upstream = upstream.to_string();
+        let shared = fault.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let Ok((client, _)) = listener.accept().await else {
+                    return;
+                };
+                let fault = *shared.lock().unwrap();
+                let upstream = upstream.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = proxy(client, &upstream, fault).await {
+                        debug!(error = %e, ?fault, "proxied connection ended");
+                    }
+                });
+            }
+        });
+        Ok(FaultProxy { addr, fault, task })
+    }
+
+    /// The endpoint URL to send requests through the proxy.
+    pub fn endpoint(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Applies `fault` to connections accepted from now on.
+    pub fn set(&self, fault: Fault) {
+        *self.fault.lock().unwrap() = fault;
+    }
+}
+
+impl Drop for FaultProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn proxy(client: TcpStream, upstream: &str, fault: Fault) -> io::Result<()> {
+    let server = TcpStream::connect(upstream).await.inspect_err(|e| {
+        warn!(upstream, error = %e, "fault proxy cannot reach the server");
+    })?;
+    let (client_read, client_write) = client.into_split();
+    let (server_read, server_write) = server.into_split();
+    let (request_limit, response_limit, delay) = match fault {
+        Fault::None => (None, None, None),
+        Fault::DropRequestAfter(n) => (Some(n), None, None),
+        Fault::TruncateResponseAfter(n) => (None, Some(n), None),
+        Fault::DelayResponse(delay) => (None, None, Some(delay)),
+    };
+    // Whichever direction ends first ends the connection; dropping the halves closes both sockets.
+    tokio::select! {
+        result = pump(client_read, server_write, request_limit, None) => result,
+        result = pump(server_read, client_write, response_limit, delay) => result,
+    }
+}
+
+/// Copies `from` to `to` until EOF, or until `limit` bytes have gone through.
+async fn pump(
+    mut from: OwnedReadHalf,
+    mut to: OwnedWriteHalf,
+    limit: Option<usize>,
+    delay: Option<Duration>,
+) -> io::Result<()> {
+    let mut buf = vec![0; 16 * 1024];
+    let mut forwarded = 0;
+    loop {
+        let n = from.read(&mut buf).await?;
+        if n == 0 {
+            return to.shutdown().await;
+        }
+        if let Some(delay) = delay {
+            tokio::time::sleep(delay).await;
+        }
+        let n = limit.map_or(n, |limit| n.min(limit - forwarded));
+        to.write_all(&buf[..n]).await?;
+        forwarded += n;
+        if limit == Some(forwarded) {
+            debug!(forwarded, "fault proxy cutting the connection");
+            return Ok(());
+        }
+    }
+}
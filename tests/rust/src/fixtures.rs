@@ -0,0 +1,177 @@
+//! Small but valid image files, generated in-process so binary-content tests never depend on
+//! files lying around in the working directory.
+//!
+//! Both encoders only do what a fixture needs: the PNG is stored without compression and the
+//! JPEG is greyscale with one flat shade per 8x8 block. Any decoder opens them, and the same
+//! seed always yields the same bytes.
+
+/// An RGB PNG of `width` x `height` pixels with a gradient drawn from `seed`.
+pub fn png(width: u32, height: u32, seed: u64) -> Vec<u8> {
+    let [r0, g0, b0, ..] = seed.to_le_bytes();
+    // Every scanline starts with filter type 0 (none).
+    let mut pixels = Vec::with_capacity((height * (width * 3 + 1)) as usize);
+    for y in 0..height {
+        pixels.push(0);
+        for x in 0..width {
+            pixels.push(r0.wrapping_add((x * 255 / width.max(1)) as u8));
+            pixels.push(g0.wrapping_add((y * 255 / height.max(1)) as u8));
+            pixels.push(b0 ^ ((x ^ y) as u8));
+        }
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, colour type 2 (RGB), default compression, filtering and no interlace.
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut out, b"IHDR", &ihdr);
+    png_chunk(&mut out, b"IDAT", &zlib_stored(&pixels));
+    png_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    out.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(u8::from(blocks.peek().is_none()));
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    out
+}
+
+/// A baseline greyscale JPEG of `width` x `height` pixels, both rounded up to multiples of 8,
+/// with a shade per block drawn from `seed`.
+pub fn jpeg(width: u16, height: u16, seed: u64) -> Vec<u8> {
+    let (blocks_x, blocks_y) = (width.div_ceil(8), height.div_ceil(8));
+    let mut out = vec![0xff, 0xd8];
+
+    // Quantization table 0: all ones, so DC coefficients go into the file unscaled.
+    out.extend_from_slice(&[0xff, 0xdb, 0x00, 0x43, 0x00]);
+    out.extend_from_slice(&[1; 64]);
+
+    // Frame: 8-bit precision, one component with id 1, no subsampling, quantization table 0.
+    out.extend_from_slice(&[0xff, 0xc0, 0x00, 0x0b, 0x08]);
+    out.extend_from_slice(&(blocks_y * 8).to_be_bytes());
+    out.extend_from_slice(&(blocks_x * 8).to_be_bytes());
+    out.extend_from_slice(&[0x01, 0x01, 0x11, 0x00]);
+
+    // DC table 0: the standard luminance table from Annex K of the JPEG specification.
+    out.extend_from_slice(&[0xff, 0xc4, 0x00, 0x1f, 0x00]);
+    out.extend_from_slice(&DC_CODE_COUNTS);
+    out.extend_from_slice(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+    // AC table 0: a single one-bit code for end-of-block, since every block is flat.
+    out.extend_from_slice(&[0xff, 0xc4, 0x00, 0x14, 0x10, 1]);
+    out.extend_from_slice(&[0; 15]);
+    out.push(0x00);
+
+    // Scan header: component 1 with DC and AC table 0, then the full spectral range.
+    out.extend_from_slice(&[0xff, 0xda, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3f, 0x00]);
+
+    let mut bits = BitWriter { out: &mut out, acc: 0, len: 0 };
+    let mut previous_dc = 0i32;
+    let mut state = seed;
+    for _ in 0..u32::from(blocks_x) * u32::from(blocks_y) {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let shade = (state >> 56) as i32;
+        // The DC coefficient of a flat level-shifted block is eight times its value.
+        let dc = (shade - 128) * 8;
+        let diff = dc - previous_dc;
+        previous_dc = dc;
+
+        let category = 32 - diff.unsigned_abs().leading_zeros();
+        let (code, code_len) = dc_code(category);
+        bits.write(code, code_len);
+        // Negative differences are stored as the one's complement of their magnitude.
+        let extra = if diff < 0 { diff - 1 } else { diff };
+        bits.write(extra as u32 & ((1 << category) - 1), category);
+        // End of block.
+        bits.write(0, 1);
+    }
+    bits.flush();
+
+    out.extend_from_slice(&[0xff, 0xd9]);
+    out
+}
+
+/// Number of DC codes of each length from 1 to 16 bits.
+const DC_CODE_COUNTS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+
+/// The canonical Huffman code for a DC category under `DC_CODE_COUNTS`.
+fn dc_code(category: u32) -> (u32, u32) {
+    let mut code = 0;
+    let mut symbol = 0;
+    for (i, &count) in DC_CODE_COUNTS.iter().enumerate() {
+        for _ in 0..count {
+            if symbol == category {
+                return (code, i as u32 + 1);
+            }
+            code += 1;
+            symbol += 1;
+        }
+        code <<= 1;
+    }
+    unreachable!("DC category {} out of range", category)
+}
+
+/// Packs entropy-coded bits MSB first, stuffing a zero byte after every 0xff.
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    acc: u32,
+    len: u32,
+}
+
+impl BitWriter<'_> {
+    fn write(&mut self, value: u32, len: u32) {
+        for i in (0..len).rev() {
+            self.acc = (self.acc << 1) | ((value >> i) & 1);
+            self.len += 1;
+            if self.len == 8 {
+                self.push_byte();
+            }
+        }
+    }
+
+    /// Pads the last byte with one bits.
+    fn flush(&mut self) {
+        while self.len != 0 {
+            self.write(1, 1);
+        }
+    }
+
+    fn push_byte(&mut self) {
+        let byte = self.acc as u8;
+        self.out.push(byte);
+        if byte == 0xff {
+            self.out.push(0x00);
+        }
+        self.acc = 0;
+        self.len = 0;
+    }
+}
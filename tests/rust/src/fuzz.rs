@@ -0,0 +1,239 @@
+//! `s3test fuzz`: randomized and adversarial requests, reproducible from a seed.
+//!
+//! Every iteration builds one request from its own generator, seeded with the run's seed and
+//! the iteration number, so a single input can be sent again with `--seed S --iteration N`.
+//! Keys mix path tricks (`..`, `//`, percent signs), control characters and non-ASCII text;
+//! headers and query parameters get values S3 would never send. The server may reject all of
+//! it, but it must never answer 5xx (other than 501) or drop the connection, and it must never
+//! touch the sentinel objects stored next to the fuzzed keys.
+
+use std::fs::OpenOptions;
+use std::io::Write as _;
+
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use http::Method;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::cli::FuzzArgs;
+use crate::guard::{BoxError, BucketGuard};
+use crate::naming::RunId;
+use crate::payload::Payload;
+use crate::progress;
+use crate::rawhttp::{uri_encode, RawClient};
+
+/// Every fuzzed key starts with this, so a request that reaches a sentinel is a server bug.
+const KEY_PREFIX: &str = "fuzz/";
+const SENTINELS: usize = 4;
+
+/// Pieces keys are assembled from.
+const KEY_PIECES: &[&str] = &[
+    "a", "Z", "0", "/", "//", ".", "..", "../", "./", "~", "-", "_", " ", "+", "%", "%2F", "%00", "?", "#",
+    "&", "=", ";", "*", "\"", "'", "<", ">", "\\", "|", "{", "}", "[", "]", "\t", "\n", "\r", "\u{0}",
+    "\u{1f}", "\u{7f}", "é", "ß", "日本", "\u{202e}", "\u{feff}", "\u{fffd}", "😀", "sentinel",
+];
+
+const QUERY_NAMES: &[&str] = &[
+    "prefix", "delimiter", "max-keys", "marker", "continuation-token", "start-after", "list-type",
+    "encoding-type", "fetch-owner", "acl", "uploads", "uploadId", "partNumber", "versionId",
+    "versioning", "delete", "tagging", "response-content-type", "x-id", "",
+];
+
+const HEADER_NAMES: &[&str] = &[
+    "Content-Type", "Content-MD5", "Range", "If-Match", "If-None-Match", "If-Modified-Since",
+    "Cache-Control", "Content-Disposition", "Content-Encoding", "Expect", "x-amz-acl",
+    "x-amz-copy-source", "x-amz-storage-class", "x-amz-meta-fuzz", "x-amz-tagging",
+    "x-amz-server-side-encryption", "x-amz-date", "x-amz-content-sha256",
+];
+
+/// Values that tend to upset parsers of numbers, dates and ranges.
+const NASTY_VALUES: &[&str] = &[
+    "", "-1", "0", "99999999999999999999", "1e9", "NaN", "true", "bytes=5-1", "bytes=-0", "bytes=0-",
+    "bytes=1-2,3-4", "*", "\"\"", "Thu, 01 Jan 1970 00:00:00 GMT", "../../etc/passwd", "%zz", "null",
+];
+
+/// One generated request, as logged for a crash.
+#[derive(Debug, Serialize)]
+struct Case {
+    seed: u64,
+    iteration: u64,
+    method: String,
+    key: Option<String>,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body_len: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outcome: Option<String>,
+}
+
+pub async fn run(client: &Client, raw: &RawClient, args: &FuzzArgs) -> Result<(), BoxError> {
+    let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+    let run_id = RunId::generate();
+    info!(seed, %run_id, iterations = args.iterations, "starting fuzz run");
+    let bucket = BucketGuard::create(client, &run_id.bucket("fuzz")).await?;
+
+    let sentinels: Vec<(String, Payload)> = (0..SENTINELS)
+        .map(|i| {
+            let key = format!("sentinel/{}", i);
+            let payload = Payload::new(seed, &key, 1024);
+            (key, payload)
+        })
+        .collect();
+    for (key, payload) in &sentinels {
+        let body = ByteStream::from(payload.bytes());
+        client.put_object().bucket(bucket.name()).key(key).body(body).send().await?;
+    }
+
+    let iterations = match args.iteration {
+        Some(iteration) => iteration..iteration + 1,
+        None => 0..args.iterations,
+    };
+    let (mut sent, mut crashes, mut corruptions) = (0, 0, 0);
+    for iteration in iterations {
+        let mut case = generate(seed, iteration);
+        let mutating = case.method != "GET" && case.method != "HEAD";
+        let outcome = send(raw, bucket.name(), &case).await;
+        sent += 1;
+        if let Some(problem) = outcome {
+            error!(seed, iteration, problem, "server crashed on fuzzed input");
+            case.outcome = Some(problem);
+            log_crash(args, &case)?;
+            crashes += 1;
+        }
+        if mutating {
+            let damaged = check_sentinels(client, bucket.name(), &sentinels).await;
+            if damaged > 0 {
+                error!(seed, iteration, damaged, "fuzzed request modified sentinel objects");
+                case.outcome = Some(format!("{} sentinel object(s) modified", damaged));
+                log_crash(args, &case)?;
+                corruptions += 1;
+                // Put them back so the next damage is attributed to the right request.
+                for (key, payload) in &sentinels {
+                    let body = ByteStream::from(payload.bytes());
+                    client.put_object().bucket(bucket.name()).key(key).body(body).send().await?;
+                }
+            }
+        }
+    }
+
+    if let Err(e) = bucket.cleanup().await {
+        warn!(error = ?e, "could not remove fuzz bucket; `s3test cleanup` removes it later");
+    }
+    println!(
+        "sent {} fuzzed requests with seed {}: {} crashes, {} corruptions",
+        sent, seed, crashes, corruptions
+    );
+    if crashes + corruptions > 0 {
+        let problems = crashes + corruptions;
+        let path = args.crashes.display();
+        return Err(format!("fuzzing found {} problem(s), inputs written to {}", problems, path).into());
+    }
+    Ok(())
+}
+
+fn generate(seed: u64, iteration: u64) -> Case {
+    let mut rng = StdRng::seed_from_u64(seed ^ iteration.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    let method = ["GET", "GET", "HEAD", "PUT", "PUT", "DELETE", "POST"][rng.random_range(0..7)];
+    // A bucket-level request without a key exercises listing and the sub-resources.
+    let key = match rng.random_range(0..5) {
+        0 => None,
+        _ => Some(format!("{}{}", KEY_PREFIX, random_key(&mut rng))),
+    };
+    let query = (0..rng.random_range(0..4))
+        .map(|_| {
+            let name = match rng.random_bool(0.8) {
+                true => QUERY_NAMES[rng.random_range(0..QUERY_NAMES.len())].to_string(),
+                false => random_key(&mut rng),
+            };
+            (name, value(&mut rng))
+        })
+        .collect();
+    let headers = (0..rng.random_range(0..4))
+        .map(|_| {
+            let name = match rng.random_bool(0.8) {
+                true => HEADER_NAMES[rng.random_range(0..HEADER_NAMES.len())].to_string(),
+                false => format!("x-amz-meta-{}", rng.random_range(0..1000)),
+            };
+            // Header values are restricted to visible ASCII, or the request cannot be built.
+            let value = value(&mut rng).chars().filter(|c| c.is_ascii_graphic() || *c == ' ').collect();
+            (name, value)
+        })
+        .collect();
+    let body_len = match method {
+        "PUT" | "POST" => [0, 1, 100, 4096][rng.random_range(0..4)],
+        _ => 0,
+    };
+    Case { seed, iteration, method: method.to_string(), key, query, headers, body_len, outcome: None }
+}
+
+fn random_key(rng: &mut StdRng) -> String {
+    let mut key = String::new();
+    // Now and then a key well past the 1024-byte limit S3 puts on keys.
+    let pieces = match rng.random_bool(0.05) {
+        true => rng.random_range(300..600),
+        false => rng.random_range(1..12),
+    };
+    for _ in 0..pieces {
+        key.push_str(KEY_PIECES[rng.random_range(0..KEY_PIECES.len())]);
+    }
+    key
+}
+
+fn value(rng: &mut StdRng) -> String {
+    match rng.random_range(0..3) {
+        0 => NASTY_VALUES[rng.random_range(0..NASTY_VALUES.len())].to_string(),
+        1 => rng.random_range(i64::MIN..i64::MAX).to_string(),
+        _ => random_key(rng),
+    }
+}
+
+/// Sends the case, returning what went wrong if the server failed it.
+async fn send(raw: &RawClient, bucket: &str, case: &Case) -> Option<String> {
+    let path = match &case.key {
+        Some(key) => format!("/{}/{}", bucket, uri_encode(key, false)),
+        None => format!("/{}", bucket),
+    };
+    let method = Method::from_bytes(case.method.as_bytes()).expect("generated methods are valid");
+    let mut request = raw.request(method, &path).body(vec![b'x'; case.body_len]);
+    for (name, value) in &case.query {
+        request = request.query(name, value);
+    }
+    for (name, value) in &case.headers {
+        request = request.header(name, value);
+    }
+    match request.send().await {
+        // 501 is how the server turns down operations it does not implement, which is fine.
+        Ok(response) if response.status >= 500 && response.status != 501 => {
+            Some(format!("HTTP {}", response.status))
+        }
+        Ok(_) => None,
+        Err(e) => Some(format!("no response: {}", e)),
+    }
+}
+
+/// Counts the sentinel objects that are gone or no longer hold their payload.
+async fn check_sentinels(client: &Client, bucket: &str, sentinels: &[(String, Payload)]) -> usize {
+    let mut damaged = 0;
+    for (key, payload) in sentinels {
+        let result: Result<(), BoxError> = async {
+            let resp = client.get_object().bucket(bucket).key(key).send().await?;
+            progress::verify_download(resp.body, payload).await?;
+            Ok(())
+        }
+        .await;
+        if let Err(e) = result {
+            warn!(key, error = ?e, "sentinel object failed verification");
+            damaged += 1;
+        }
+    }
+    damaged
+}
+
+fn log_crash(args: &FuzzArgs, case: &Case) -> Result<(), BoxError> {
+    let mut file = OpenOptions::new().create(true).append(true).open(&args.crashes)?;
+    writeln!(file, "{}", serde_json::to_string(case)?)?;
+    Ok(())
+}
@@ -0,0 +1,326 @@
+//! RAII guards for server-side resources created by the harness.
+//!
+//! A guard deletes its resource when dropped, so an early `?` return or a panic no longer
+//! leaves buckets, objects and multipart uploads behind on the server. On the happy path call
+//! `cleanup` instead, which reports teardown errors to the caller rather than just logging them.
+//!
+//! With `--keep-data` neither does anything, so that the PHP server's storage directory can be
+//! inspected after the run; `s3test cleanup` removes the leftovers later.
+
+use std::error::Error;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tracing::{info, warn};
+
+pub type BoxError = Box<dyn Error + Send + Sync>;
+
+tokio::task_local! {
+    static TEARDOWN: ();
+}
+
+static KEEP_DATA: AtomicBool = AtomicBool::new(false);
+
+/// Makes every guard leave its resource on the server instead of deleting it.
+pub fn keep_data() {
+    KEEP_DATA.store(true, Ordering::Relaxed);
+}
+
+/// Whether teardown of `what` is skipped, logging it if so.
+fn kept(what: &str) -> bool {
+    let keep = KEEP_DATA.load(Ordering::Relaxed);
+    if keep {
+        info!("keeping {}", what);
+    }
+    keep
+}
+
+/// Whether the current task is tearing down resources, so capture and reporting can tell
+/// cleanup traffic apart from the requests under test.
+pub fn in_teardown() -> bool {
+    TEARDOWN.try_with(|_| ()).is_ok()
+}
+
+/// Owns a bucket created for the current run.
+pub struct BucketGuard {
+    client: Client,
+    bucket: String,
+    armed: bool,
+}
+
+impl BucketGuard {
+    pub async fn create(client: &Client, bucket: &str) -> Result<Self, BoxError> {
+        client.create_bucket().bucket(bucket).send().await?;
+        Ok(BucketGuard {
+            client: client.clone(),
+            bucket: bucket.to_string(),
+            armed: true,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Empties and deletes the bucket.
+    pub async fn cleanup(mut self) -> Result<(), BoxError> {
+        self.armed = false;
+        if kept(&format!("bucket '{}'", self.bucket)) {
+            return Ok(());
+        }
+        TEARDOWN.scope((), teardown_bucket(&self.client, &self.bucket)).await
+    }
+}
+
+impl Drop for BucketGuard {
+    fn drop(&mut self) {
+        if self.armed {
+            let what = format!("bucket '{}'", self.bucket);
+            if kept(&what) {
+                return;
+            }
+            block_on_teardown(&what, teardown_bucket(&self.client, &self.bucket));
+        }
+    }
+}
+
+/// Owns an object uploaded during the current run.
+pub struct ObjectGuard {
+    client: Client,
+    bucket: String,
+    key: String,
+    armed: bool,
+}
+
+impl ObjectGuard {
+    pub async fn put(
+        client: &Client,
+        bucket: &str,
+        key: &str,
+        body: ByteStream,
+    ) -> Result<Self, BoxError> {
+        client
+            .put_object()
+            .bucket(bucket)
+            .key(key)
+            .body(body)
+            .send()
+            .await?;
+        Ok(ObjectGuard {
+            client: client.clone(),
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            armed: true,
+        })
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Deletes the object.
+    pub async fn cleanup(mut self) -> Result<(), BoxError> {
+        self.armed = false;
+        if kept(&format!("object '{}/{}'", self.bucket, self.key)) {
+            return Ok(());
+        }
+        TEARDOWN.scope((), delete_object(&self.client, &self.bucket, &self.key)).await
+    }
+}
+
+impl Drop for ObjectGuard {
+    fn drop(&mut self) {
+        if self.armed {
+            let what = format!("object '{}/{}'", self.bucket, self.key);
+            if kept(&what) {
+                return;
+            }
+            block_on_teardown(&what, delete_object(&self.client, &self.bucket, &self.key));
+        }
+    }
+}
+
+/// Owns a multipart upload started during the current run, which is aborted unless completed.
+pub struct MultipartGuard {
+    client: Client,
+    bucket: String,
+    key: String,
+    upload_id: String,
+    armed: bool,
+}
+
+impl MultipartGuard {
+    pub async fn create(client: &Client, bucket: &str, key: &str) -> Result<Self, BoxError> {
+        let upload = client.create_multipart_upload().bucket(bucket).key(key).send().await?;
+        let upload_id = upload.upload_id().ok_or("CreateMultipartUpload returned no upload ID")?;
+        Ok(MultipartGuard {
+            client: client.clone(),
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            upload_id: upload_id.to_string(),
+            armed: true,
+        })
+    }
+
+    pub fn upload_id(&self) -> &str {
+        &self.upload_id
+    }
+
+    /// Leaves the upload be once CompleteMultipartUpload has turned it into an object, which
+    /// is then the bucket's to remove.
+    pub fn completed(mut self) {
+        self.armed = false;
+    }
+
+    /// Aborts the upload.
+    pub async fn cleanup(mut self) -> Result<(), BoxError> {
+        self.armed = false;
+        if kept(&format!("upload '{}' of '{}/{}'", self.upload_id, self.bucket, self.key)) {
+            return Ok(());
+        }
+        TEARDOWN.scope((), abort_upload(&self.client, &self.bucket, &self.key, &self.upload_id)).await
+    }
+}
+
+impl Drop for MultipartGuard {
+    fn drop(&mut self) {
+        if self.armed {
+            let what = format!("upload '{}' of '{}/{}'", self.upload_id, self.bucket, self.key);
+            if kept(&what) {
+                return;
+            }
+            block_on_teardown(&what, abort_upload(&self.client, &self.bucket, &self.key, &self.upload_id));
+        }
+    }
+}
+
+async fn delete_object(client: &Client, bucket: &str, key: &str) -> Result<(), BoxError> {
+    client.delete_object().bucket(bucket).key(key).send().await?;
+    Ok(())
+}
+
+async fn abort_upload(client: &Client, bucket: &str, key: &str, upload_id: &str) -> Result<(), BoxError> {
+    client.abort_multipart_upload().bucket(bucket).key(key).upload_id(upload_id).send().await?;
+    Ok(())
+}
+
+/// Aborts in-progress multipart uploads, deletes every object version and object and finally
+/// removes the bucket.
+pub async fn teardown_bucket(client: &Client, bucket: &str) -> Result<(), BoxError> {
+    // Servers without multipart support reject the listing; there is nothing to abort then.
+    if let Ok(uploads) = list_uploads(client, bucket).await {
+        for (key, upload_id) in uploads {
+            abort_upload(client, bucket, &key, &upload_id).await?;
+        }
+    }
+
+    // Same for versioning: without it the plain object listing below covers everything.
+    if let Ok(versions) = list_versions(client, bucket).await {
+        for (key, version_id) in versions {
+            client
+                .delete_object()
+                .bucket(bucket)
+                .key(key)
+                .version_id(version_id)
+                .send()
+                .await?;
+        }
+    }
+
+    let mut pages = client
+        .list_objects_v2()
+        .bucket(bucket)
+        .into_paginator()
+        .send();
+    let mut keys = Vec::new();
+    while let Some(page) = pages.next().await {
+        keys.extend(page?.contents().iter().filter_map(|o| o.key().map(str::to_string)));
+    }
+    for key in keys {
+        delete_object(client, bucket, &key).await?;
+    }
+
+    client.delete_bucket().bucket(bucket).send().await?;
+    Ok(())
+}
+
+/// Lists `(key, upload id)` pairs of every multipart upload in progress in the bucket.
+pub async fn list_uploads(client: &Client, bucket: &str) -> Result<Vec<(String, String)>, BoxError> {
+    let mut uploads = Vec::new();
+    let mut key_marker = None;
+    let mut upload_id_marker = None;
+    loop {
+        let resp = client
+            .list_multipart_uploads()
+            .bucket(bucket)
+            .set_key_marker(key_marker)
+            .set_upload_id_marker(upload_id_marker)
+            .send()
+            .await?;
+
+        for upload in resp.uploads() {
+            if let (Some(key), Some(upload_id)) = (upload.key(), upload.upload_id()) {
+                uploads.push((key.to_string(), upload_id.to_string()));
+            }
+        }
+
+        key_marker = resp.next_key_marker().map(str::to_string);
+        upload_id_marker = resp.next_upload_id_marker().map(str::to_string);
+        if !resp.is_truncated().unwrap_or(false) || key_marker.is_none() {
+            return Ok(uploads);
+        }
+    }
+}
+
+/// Lists `(key, version id)` pairs of every object version and delete marker in the bucket.
+pub async fn list_versions(client: &Client, bucket: &str) -> Result<Vec<(String, String)>, BoxError> {
+    let mut versions = Vec::new();
+    let mut key_marker = None;
+    let mut version_id_marker = None;
+    loop {
+        let resp = client
+            .list_object_versions()
+            .bucket(bucket)
+            .set_key_marker(key_marker)
+            .set_version_id_marker(version_id_marker)
+            .send()
+            .await?;
+
+        let entries = resp
+            .versions()
+            .iter()
+            .map(|v| (v.key(), v.version_id()))
+            .chain(resp.delete_markers().iter().map(|m| (m.key(), m.version_id())));
+        for (key, version_id) in entries {
+            if let (Some(key), Some(version_id)) = (key, version_id) {
+                versions.push((key.to_string(), version_id.to_string()));
+            }
+        }
+
+        key_marker = resp.next_key_marker().map(str::to_string);
+        version_id_marker = resp.next_version_id_marker().map(str::to_string);
+        if !resp.is_truncated().unwrap_or(false) || key_marker.is_none() {
+            return Ok(versions);
+        }
+    }
+}
+
+/// Runs an async teardown from `Drop`. Needs the multi-threaded runtime, since the current
+/// worker is blocked while the future completes.
+fn block_on_teardown<F>(what: &str, teardown: F)
+where
+    F: Future<Output = Result<(), BoxError>>,
+{
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            match tokio::task::block_in_place(|| handle.block_on(TEARDOWN.scope((), teardown))) {
+                Ok(()) => info!("cleaned up {}", what),
+                Err(e) => warn!(error = ?e, "cleanup of {} failed", what),
+            }
+        }
+        _ => warn!("cleanup of {} skipped: no multi-threaded runtime", what),
+    }
+}
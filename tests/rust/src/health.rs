@@ -0,0 +1,61 @@
+//! Waits for the endpoint to come up before any scenario runs, so an unreachable server fails
+//! the run with one clear message instead of a connection error in every test.
+
+use std::time::{Duration, Instant};
+
+use aws_sdk_s3::config::retry::RetryConfig;
+use aws_sdk_s3::config::timeout::TimeoutConfig;
+use aws_sdk_s3::error::{DisplayErrorContext, SdkError};
+use aws_sdk_s3::Client;
+use tracing::{debug, info};
+
+use crate::guard::BoxError;
+
+/// How long a single probe may take before it counts as a failed attempt.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Sends ListBuckets until the endpoint answers or `budget` runs out. Any HTTP response counts,
+/// including errors like AccessDenied: the point is reachability, and the tests themselves
+/// report bad credentials better.
+pub async fn wait_until_ready(
+    client: &Client,
+    endpoint: &str,
+    budget: Duration,
+    interval: Duration,
+) -> Result<(), BoxError> {
+    let deadline = Instant::now() + budget;
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let result = client
+            .list_buckets()
+            .customize()
+            .config_override(
+                aws_sdk_s3::config::Builder::default()
+                    .retry_config(RetryConfig::disabled())
+                    .timeout_config(TimeoutConfig::builder().operation_timeout(PROBE_TIMEOUT).build()),
+            )
+            .send()
+            .await;
+        let error = match result {
+            Ok(_) | Err(SdkError::ServiceError(_) | SdkError::ResponseError(_)) => {
+                info!(endpoint, attempts, "endpoint is ready");
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+        if Instant::now() + interval >= deadline {
+            return Err(format!(
+                "endpoint {} is not reachable after {:?} ({} attempts), is the server running? \
+                 (--spawn-server starts one in a container): {}",
+                endpoint,
+                budget,
+                attempts,
+                DisplayErrorContext(&error)
+            )
+            .into());
+        }
+        debug!(endpoint, error = %DisplayErrorContext(&error), "endpoint not ready yet");
+        tokio::time::sleep(interval).await;
+    }
+}
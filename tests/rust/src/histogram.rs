@@ -0,0 +1,236 @@
+//! Latency histograms after HdrHistogram: counts in bins whose width grows with the value, so
+//! that every latency is kept to three significant digits at a memory cost that does not grow
+//! with the number of samples, however long the tail. The `.hgrm` percentile distributions
+//! they write are in HdrHistogram's format and load in its plotter.
+
+use std::fmt::Write as _;
+use std::time::Duration;
+
+/// Bins per bucket, as a power of two: 2048 keep three significant digits.
+const SUB_BUCKET_BITS: u32 = 11;
+const SUB_BUCKET_MASK: u64 = (1 << SUB_BUCKET_BITS) - 1;
+const SUB_BUCKET_HALF_BITS: u32 = SUB_BUCKET_BITS - 1;
+const SUB_BUCKET_HALF: usize = 1 << SUB_BUCKET_HALF_BITS;
+/// Percentile steps of the `.hgrm` output per halving of the distance to 100%.
+const TICKS_PER_HALF_DISTANCE: f64 = 5.0;
+/// Latencies above a day are counted as a day: a request that slow has hung anyway, and the
+/// bins of the largest values would overflow a u64.
+const HIGHEST: u64 = 24 * 3600 * 1_000_000;
+
+/// Latencies recorded in microseconds.
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+    min: u64,
+    max: u64,
+    sum: f64,
+    sum_of_squares: f64,
+}
+
+/// The bin `value` is counted in.
+fn index_of(value: u64) -> usize {
+    let bucket = 64 - (value | SUB_BUCKET_MASK).leading_zeros() - SUB_BUCKET_BITS;
+    let sub_bucket = (value >> bucket) as usize;
+    ((bucket as usize + 1) << SUB_BUCKET_HALF_BITS) + sub_bucket - SUB_BUCKET_HALF
+}
+
+/// The largest value counted in bin `index`.
+fn highest_of(index: usize) -> u64 {
+    let bucket = (index >> SUB_BUCKET_HALF_BITS).saturating_sub(1);
+    let sub_bucket = index + SUB_BUCKET_HALF - ((bucket + 1) << SUB_BUCKET_HALF_BITS);
+    ((sub_bucket as u64) << bucket) + (1 << bucket) - 1
+}
+
+impl Histogram {
+    pub fn record(&mut self, latency: Duration) {
+        let value = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX).min(HIGHEST);
+        let index = index_of(value);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+        self.min = if self.total == 0 { value } else { self.min.min(value) };
+        self.total += 1;
+        self.max = self.max.max(value);
+        self.sum += value as f64;
+        self.sum_of_squares += (value as f64) * (value as f64);
+    }
+
+    pub fn len(&self) -> u64 {
+        self.total
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// The latency `p` percent of the samples are at or below, to three significant digits;
+    /// zero when there are none.
+    pub fn percentile(&self, p: f64) -> Duration {
+        let target = ((p / 100.0) * self.total as f64).ceil().max(1.0) as u64;
+        let mut cumulative = 0;
+        for (index, &count) in self.counts.iter().enumerate() {
+            cumulative += count;
+            if cumulative >= target {
+                return Duration::from_micros(highest_of(index).min(self.max));
+            }
+        }
+        Duration::ZERO
+    }
+
+    /// The shortest latency recorded, exactly; zero when there are none.
+    pub fn min(&self) -> Duration {
+        Duration::from_micros(self.min)
+    }
+
+    /// The mean latency, exactly to the microsecond; zero when there are none.
+    pub fn mean(&self) -> Duration {
+        Duration::from_secs_f64(self.sum / self.total.max(1) as f64 / 1e6)
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max)
+    }
+
+    /// The percentile distribution in HdrHistogram's `.hgrm` format, in milliseconds.
+    pub fn hgrm(&self) -> String {
+        let ms = |value: u64| value as f64 / 1000.0;
+        let columns = ("Value", "Percentile", "TotalCount", "1/(1-Percentile)");
+        let mut out = format!("{:>12} {:>14} {:>10} {:>14}\n\n", columns.0, columns.1, columns.2, columns.3);
+        let (mut level, mut cumulative) = (0.0_f64, 0);
+        for (index, &count) in self.counts.iter().enumerate().filter(|&(_, &count)| count > 0) {
+            cumulative += count;
+            let reached = cumulative as f64 * 100.0 / self.total as f64;
+            let value = ms(highest_of(index).min(self.max));
+            while level <= reached {
+                let (fraction, inverse) = (level / 100.0, 100.0 / (100.0 - level));
+                writeln!(out, "{:12.3} {:2.12} {:10} {:14.2}", value, fraction, cumulative, inverse).unwrap();
+                let halvings = inverse.log2().floor() as i32;
+                level += 100.0 / (TICKS_PER_HALF_DISTANCE * 2f64.powi(halvings + 1));
+                // The last bin gets one line before the closing 100%, as in HdrHistogram.
+                if cumulative == self.total {
+                    break;
+                }
+            }
+        }
+        if self.total > 0 {
+            writeln!(out, "{:12.3} {:2.12} {:10}", ms(self.max), 1.0, self.total).unwrap();
+        }
+
+        let n = self.total.max(1) as f64;
+        let mean = self.sum / n;
+        let deviation = (self.sum_of_squares / n - mean * mean).max(0.0).sqrt();
+        let (mean, deviation) = (mean / 1000.0, deviation / 1000.0);
+        let buckets = (self.counts.len() >> SUB_BUCKET_HALF_BITS).max(1);
+        let sub_buckets = 1 << SUB_BUCKET_BITS;
+        writeln!(out, "#[Mean    = {:12.3}, StdDeviation   = {:12.3}]", mean, deviation).unwrap();
+        writeln!(out, "#[Max     = {:12.3}, Total count    = {:12}]", ms(self.max), self.total).unwrap();
+        writeln!(out, "#[Buckets = {:12}, SubBuckets     = {:12}]", buckets, sub_buckets).unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn of_micros(values: impl IntoIterator<Item = u64>) -> Histogram {
+        let mut histogram = Histogram::default();
+        values.into_iter().for_each(|value| histogram.record(Duration::from_micros(value)));
+        histogram
+    }
+
+    #[test]
+    fn bins_are_exact_below_2048() {
+        for value in [0, 1, 1023, 1024, 2047] {
+            assert_eq!(index_of(value), value as usize);
+            assert_eq!(highest_of(value as usize), value);
+        }
+    }
+
+    #[test]
+    fn bins_widen_from_2048() {
+        assert_eq!(index_of(2048), 2048);
+        assert_eq!(index_of(2049), 2048);
+        assert_eq!(highest_of(2048), 2049);
+        assert_eq!(index_of(2050), 2049);
+        assert_eq!(index_of(4095), 3071);
+        assert_eq!(highest_of(3071), 4095);
+        assert_eq!(index_of(4096), 3072);
+        assert_eq!(highest_of(3072), 4099);
+    }
+
+    #[test]
+    fn bins_follow_each_other() {
+        for index in 0..index_of(HIGHEST) {
+            let highest = highest_of(index);
+            assert_eq!(index_of(highest), index);
+            assert_eq!(index_of(highest + 1), index + 1);
+        }
+    }
+
+    #[test]
+    fn records_a_latency_of_any_length() {
+        let histogram = of_micros([]);
+        assert_eq!(histogram.percentile(50.0), Duration::ZERO);
+
+        let mut histogram = of_micros([5]);
+        histogram.record(Duration::MAX);
+        assert_eq!(histogram.max(), Duration::from_micros(HIGHEST));
+        assert_eq!(histogram.percentile(100.0), Duration::from_micros(HIGHEST));
+        assert!(histogram.hgrm().contains("86400000.000"));
+    }
+
+    #[test]
+    fn min_and_mean_are_exact() {
+        let histogram = of_micros([]);
+        assert_eq!((histogram.min(), histogram.mean()), (Duration::ZERO, Duration::ZERO));
+
+        let histogram = of_micros([70001, 30002, 50003]);
+        assert_eq!(histogram.min(), Duration::from_micros(30002));
+        assert_eq!(histogram.mean(), Duration::from_micros(50002));
+        assert_eq!(histogram.max(), Duration::from_micros(70001));
+    }
+
+    #[test]
+    fn percentiles() {
+        let histogram = of_micros(1..=10);
+        assert_eq!(histogram.len(), 10);
+        assert_eq!(histogram.percentile(0.0), Duration::from_micros(1));
+        assert_eq!(histogram.percentile(50.0), Duration::from_micros(5));
+        assert_eq!(histogram.percentile(91.0), Duration::from_micros(10));
+
+        // 50ms is counted in a bin 32 microseconds wide, 99ms in one 64 wide; a percentile is the
+        // top of its bin.
+        let histogram = of_micros((1..=100).map(|ms| ms * 1000));
+        assert_eq!(histogram.percentile(50.0), Duration::from_micros(50015));
+        assert_eq!(histogram.percentile(99.0), Duration::from_micros(99007));
+        assert_eq!(histogram.percentile(100.0), Duration::from_micros(100000));
+    }
+
+    #[test]
+    fn hgrm() {
+        let hgrm = of_micros(1..=4).hgrm();
+        let expected = "       Value     Percentile TotalCount 1/(1-Percentile)
+
+       0.001 0.000000000000          1           1.00
+       0.001 0.100000000000          1           1.11
+       0.001 0.200000000000          1           1.25
+       0.002 0.300000000000          2           1.43
+       0.002 0.400000000000          2           1.67
+       0.002 0.500000000000          2           2.00
+       0.003 0.550000000000          3           2.22
+       0.003 0.600000000000          3           2.50
+       0.003 0.650000000000          3           2.86
+       0.003 0.700000000000          3           3.33
+       0.003 0.750000000000          3           4.00
+       0.004 0.775000000000          4           4.44
+       0.004 1.000000000000          4
+#[Mean    =        0.003, StdDeviation   =        0.001]
+#[Max     =        0.004, Total count    =            4]
+#[Buckets =            1, SubBuckets     =         2048]
+";
+        assert_eq!(hgrm, expected);
+    }
+}
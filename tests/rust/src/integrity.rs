@@ -0,0 +1,323 @@
+//! End-to-end integrity of the objects every test writes through the SDK client, whatever the
+//! test itself checks: PutObject bodies are hashed with SHA-256 as they are sent, and every
+//! full GetObject of a key the test wrote is hashed as it is read and compared with what was
+//! written last. A mismatch fails the test and is listed in the summary, so silent corruption
+//! cannot hide in a test that only looks at status codes.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use aws_sdk_s3::config::interceptors::{
+    BeforeDeserializationInterceptorContextMut, BeforeSerializationInterceptorContextMut,
+    FinalizerInterceptorContextRef,
+};
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_sdk_s3::operation::complete_multipart_upload::CompleteMultipartUploadInput;
+use aws_sdk_s3::operation::copy_object::{CopyObjectInput, CopyObjectOutput};
+use aws_sdk_s3::operation::delete_object::DeleteObjectInput;
+use aws_sdk_s3::operation::delete_objects::DeleteObjectsInput;
+use aws_sdk_s3::operation::get_object::GetObjectInput;
+use aws_sdk_s3::operation::put_object::{PutObjectInput, PutObjectOutput};
+use aws_sdk_s3::primitives::SdkBody;
+use aws_smithy_runtime_api::box_error::BoxError;
+use aws_smithy_types::config_bag::{Storable, StoreReplace};
+use bytes::Bytes;
+use http_body::{Body, Frame, SizeHint};
+use sha2::{Digest, Sha256};
+
+type Object = (String, String);
+type Sha = [u8; 32];
+
+/// A download whose content is not what was uploaded.
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub bucket: String,
+    pub key: String,
+    pub expected: Sha,
+    pub actual: Sha,
+    pub bytes: u64,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "GetObject '{}/{}' returned {} bytes with SHA-256 {}, PutObject sent {}",
+            self.bucket,
+            self.key,
+            self.bytes,
+            hex::encode(self.actual),
+            hex::encode(self.expected)
+        )
+    }
+}
+
+/// The last content written to an object.
+#[derive(Debug, Clone)]
+struct Written {
+    sha: Sha,
+    /// The ETag the write was answered with. A GET answered with another one is of content
+    /// written some other way (a raw or presigned request), and is not compared.
+    etag: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    written: HashMap<Object, Written>,
+    mismatches: Vec<Mismatch>,
+}
+
+/// Interceptor that hashes what the client it is added to uploads and downloads.
+#[derive(Debug, Clone, Default)]
+pub struct Integrity {
+    state: Arc<Mutex<State>>,
+}
+
+impl Integrity {
+    /// Removes and returns the mismatches found so far.
+    pub fn take(&self) -> Vec<Mismatch> {
+        std::mem::take(&mut self.state.lock().unwrap().mismatches)
+    }
+
+    fn written(&self, object: &Object) -> Option<Written> {
+        self.state.lock().unwrap().written.get(object).cloned()
+    }
+}
+
+/// What the operation under way does to the objects it names.
+#[derive(Debug, Clone)]
+enum Tracked {
+    Put { object: Object, sent: Arc<Mutex<Option<Sha>>> },
+    Get { object: Object, expected: Written },
+    Copy { object: Object, source: Option<Sha> },
+    /// Objects whose content is no longer known: deleted, or written some other way.
+    Forget(Vec<Object>),
+}
+
+impl Storable for Tracked {
+    type Storer = StoreReplace<Self>;
+}
+
+fn object(bucket: &Option<String>, key: &Option<String>) -> Option<Object> {
+    Some((bucket.clone()?, key.clone()?))
+}
+
+/// The object `CopySource` names, `bucket/key` with the key URL-encoded.
+fn copy_source(source: &str) -> Option<Object> {
+    let source = source.trim_start_matches('/');
+    let (bucket, key) = source.split_once('/')?;
+    if key.contains("?versionId=") {
+        return None;
+    }
+    let key = percent_decode(key)?;
+    Some((bucket.to_string(), key))
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'%' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                out.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            b => out.push(b),
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+impl Intercept for Integrity {
+    fn name(&self) -> &'static str {
+        "Integrity"
+    }
+
+    fn modify_before_serialization(
+        &self,
+        context: &mut BeforeSerializationInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let input = context.input_mut();
+        let tracked = if let Some(put) = input.downcast_mut::<PutObjectInput>() {
+            let Some(object) = object(&put.bucket, &put.key) else {
+                return Ok(());
+            };
+            let sent = Arc::new(Mutex::new(None));
+            match put.body.bytes() {
+                Some(bytes) => *sent.lock().unwrap() = Some(Sha256::digest(bytes).into()),
+                // Hashed on its way out, so that large bodies are never held in memory.
+                None => {
+                    let slot = sent.clone();
+                    put.body = std::mem::take(&mut put.body).map(move |body| {
+                        let slot = slot.clone();
+                        let hashing = Hashing::new(body, move |sha, _| *slot.lock().unwrap() = Some(sha));
+                        SdkBody::from_body_1_x(hashing)
+                    });
+                }
+            }
+            Tracked::Put { object, sent }
+        } else if let Some(get) = input.downcast_ref::<GetObjectInput>() {
+            // Parts, ranges and older versions are not what was written last.
+            if get.range.is_some() || get.part_number.is_some() || get.version_id.is_some() {
+                return Ok(());
+            }
+            let Some(object) = object(&get.bucket, &get.key) else {
+                return Ok(());
+            };
+            match self.written(&object) {
+                Some(expected) => Tracked::Get { object, expected },
+                None => return Ok(()),
+            }
+        } else if let Some(copy) = input.downcast_ref::<CopyObjectInput>() {
+            let Some(object) = object(&copy.bucket, &copy.key) else {
+                return Ok(());
+            };
+            let source = copy.copy_source.as_deref().and_then(copy_source);
+            let source = source.and_then(|s| self.written(&s)).map(|w| w.sha);
+            Tracked::Copy { object, source }
+        } else if let Some(delete) = input.downcast_ref::<DeleteObjectInput>() {
+            Tracked::Forget(object(&delete.bucket, &delete.key).into_iter().collect())
+        } else if let Some(delete) = input.downcast_ref::<DeleteObjectsInput>() {
+            let (Some(bucket), Some(objects)) = (&delete.bucket, &delete.delete) else {
+                return Ok(());
+            };
+            let keys = objects.objects().iter().map(|o| (bucket.clone(), o.key().to_string()));
+            Tracked::Forget(keys.collect())
+        } else if let Some(complete) = input.downcast_ref::<CompleteMultipartUploadInput>() {
+            Tracked::Forget(object(&complete.bucket, &complete.key).into_iter().collect())
+        } else {
+            return Ok(());
+        };
+        cfg.interceptor_state().store_put(tracked);
+        Ok(())
+    }
+
+    fn modify_before_deserialization(
+        &self,
+        context: &mut BeforeDeserializationInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let Some(Tracked::Get { object, expected }) = cfg.load::<Tracked>().cloned() else {
+            return Ok(());
+        };
+        let response = context.response_mut();
+        if response.status().as_u16() != 200 {
+            return Ok(());
+        }
+        let etag = response.headers().get("etag");
+        if expected.etag.as_deref().is_some_and(|written| etag != Some(written)) {
+            return Ok(());
+        }
+        let expected = expected.sha;
+        let state = self.state.clone();
+        let body = response.take_body();
+        *response.body_mut() = SdkBody::from_body_1_x(Hashing::new(body, move |actual, bytes| {
+            if actual != expected {
+                let (bucket, key) = object.clone();
+                let mismatch = Mismatch { bucket, key, expected, actual, bytes };
+                state.lock().unwrap().mismatches.push(mismatch);
+            }
+        }));
+        Ok(())
+    }
+
+    fn read_after_execution(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let Some(tracked) = cfg.load::<Tracked>() else {
+            return Ok(());
+        };
+        let output = context.output_or_error().and_then(|result| result.ok());
+        let mut state = self.state.lock().unwrap();
+        // After a failed write the server may or may not have stored it, so nothing is known.
+        match tracked {
+            Tracked::Put { object, sent } => {
+                let put = output.and_then(|o| o.downcast_ref::<PutObjectOutput>());
+                match (put, *sent.lock().unwrap()) {
+                    (Some(put), Some(sha)) => {
+                        let etag = put.e_tag.clone();
+                        state.written.insert(object.clone(), Written { sha, etag });
+                    }
+                    _ => drop(state.written.remove(object)),
+                }
+            }
+            Tracked::Copy { object, source } => {
+                let copy = output.and_then(|o| o.downcast_ref::<CopyObjectOutput>());
+                match (copy, source) {
+                    (Some(copy), Some(sha)) => {
+                        let etag = copy.copy_object_result.as_ref().and_then(|r| r.e_tag.clone());
+                        state.written.insert(object.clone(), Written { sha: *sha, etag });
+                    }
+                    _ => drop(state.written.remove(object)),
+                }
+            }
+            Tracked::Forget(objects) => {
+                for object in objects {
+                    state.written.remove(object);
+                }
+            }
+            Tracked::Get { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+/// A body hashed as it is read; `done` gets the SHA-256 and length once it ends cleanly.
+struct Hashing<F> {
+    inner: SdkBody,
+    hasher: Sha256,
+    bytes: u64,
+    done: Option<F>,
+}
+
+impl<F: FnOnce(Sha, u64)> Hashing<F> {
+    fn new(inner: SdkBody, done: F) -> Self {
+        Hashing { inner, hasher: Sha256::new(), bytes: 0, done: Some(done) }
+    }
+}
+
+impl<F: FnOnce(Sha, u64) + Unpin> Body for Hashing<F> {
+    type Data = Bytes;
+    type Error = <SdkBody as Body>::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let this = &mut *self;
+        let polled = Pin::new(&mut this.inner).poll_frame(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    this.hasher.update(data);
+                    this.bytes += data.len() as u64;
+                }
+            }
+            Poll::Ready(None) => {
+                if let Some(done) = this.done.take() {
+                    done(std::mem::take(&mut this.hasher).finalize().into(), this.bytes);
+                }
+            }
+            _ => {}
+        }
+        polled
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        Body::size_hint(&self.inner)
+    }
+}
+
@@ -0,0 +1,127 @@
+//! Wall-clock time of every S3 operation the tests make, so that a regression in a single
+//! endpoint of the PHP server shows up in the report even when whole tests take about as long
+//! as before.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use aws_sdk_s3::config::interceptors::{
+    BeforeSerializationInterceptorContextRef, FinalizerInterceptorContextRef,
+};
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_smithy_runtime_api::box_error::BoxError;
+use aws_smithy_runtime_api::client::orchestrator::Metadata;
+use aws_smithy_types::config_bag::{Storable, StoreReplace};
+
+use crate::bench::millis;
+use crate::histogram::Histogram;
+use crate::runner::TestResult;
+
+/// How long one operation took, from the SDK call to its result, retries included.
+#[derive(Debug, Clone)]
+pub struct OpTiming {
+    pub operation: String,
+    pub duration: Duration,
+}
+
+#[derive(Debug)]
+struct Started(Instant);
+
+impl Storable for Started {
+    type Storer = StoreReplace<Self>;
+}
+
+/// Interceptor that times every operation of the client it is added to.
+#[derive(Debug, Clone, Default)]
+pub struct OpTimer {
+    timings: Arc<Mutex<Vec<OpTiming>>>,
+}
+
+impl OpTimer {
+    /// Removes and returns the timings recorded so far.
+    pub fn take(&self) -> Vec<OpTiming> {
+        std::mem::take(&mut *self.timings.lock().unwrap())
+    }
+}
+
+impl Intercept for OpTimer {
+    fn name(&self) -> &'static str {
+        "OpTimer"
+    }
+
+    fn read_before_execution(
+        &self,
+        _context: &BeforeSerializationInterceptorContextRef<'_>,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        cfg.interceptor_state().store_put(Started(Instant::now()));
+        Ok(())
+    }
+
+    fn read_after_execution(
+        &self,
+        _context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let Some(Started(started)) = cfg.load::<Started>() else {
+            return Ok(());
+        };
+        let timing = OpTiming {
+            operation: cfg.load::<Metadata>().map(|m| m.name().to_string()).unwrap_or_default(),
+            duration: started.elapsed(),
+        };
+        self.timings.lock().unwrap().push(timing);
+        Ok(())
+    }
+}
+
+/// The latencies of every operation, over all tests.
+fn histograms(results: &[TestResult]) -> BTreeMap<&str, Histogram> {
+    let mut by_operation: BTreeMap<&str, Histogram> = BTreeMap::new();
+    for timing in results.iter().flat_map(|r| &r.operations) {
+        by_operation.entry(&timing.operation).or_default().record(timing.duration);
+    }
+    by_operation
+}
+
+/// Prints the latency distribution per operation over all tests, up to the tail averages hide.
+pub fn print_breakdown(results: &[TestResult]) {
+    let by_operation = histograms(results);
+    if by_operation.is_empty() {
+        return;
+    }
+
+    println!(
+        "\n{:<28} {:>6} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+        "operation", "calls", "min ms", "avg ms", "p50 ms", "p90 ms", "p95 ms", "p99 ms", "p99.9 ms", "max ms"
+    );
+    for (operation, histogram) in by_operation {
+        println!(
+            "{:<28} {:>6} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+            operation,
+            histogram.len(),
+            millis(histogram.min()),
+            millis(histogram.mean()),
+            millis(histogram.percentile(50.0)),
+            millis(histogram.percentile(90.0)),
+            millis(histogram.percentile(95.0)),
+            millis(histogram.percentile(99.0)),
+            millis(histogram.percentile(99.9)),
+            millis(histogram.max()),
+        );
+    }
+}
+
+/// Writes the latency distribution of every operation as `<operation>.hgrm` into `dir`.
+pub fn write_histograms(dir: &Path, results: &[TestResult]) -> std::io::Result<usize> {
+    let by_operation = histograms(results);
+    fs::create_dir_all(dir)?;
+    for (operation, histogram) in &by_operation {
+        fs::write(dir.join(format!("{}.hgrm", operation)), histogram.hgrm())?;
+    }
+    Ok(by_operation.len())
+}
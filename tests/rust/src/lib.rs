@@ -0,0 +1,61 @@
+//! The s3test harness as a library: everything the `s3test` binary is made of,
+//! [`S3ConformanceSuite`] for running the conformance scenarios from other Rust code, and
+//! [`testing`], which runs them as `cargo test` integration tests.
+
+pub mod annotations;
+pub mod assertions;
+pub mod baseline;
+pub mod bench;
+pub mod budget;
+pub mod capture;
+pub mod cassette;
+pub mod cleanup;
+pub mod cli;
+pub mod client;
+pub mod compare;
+pub mod completions;
+pub mod dashboard;
+pub mod diff;
+pub mod faultproxy;
+pub mod fixtures;
+pub mod fuzz;
+pub mod guard;
+pub mod health;
+pub mod histogram;
+pub mod integrity;
+pub mod latency;
+pub mod load;
+pub mod logging;
+pub mod matrix;
+pub mod memory;
+pub mod metrics;
+pub mod naming;
+pub mod otel;
+pub mod payload;
+pub mod pipe;
+pub mod pool;
+pub mod profile;
+pub mod progress;
+pub mod proxy;
+pub mod rawhttp;
+pub mod repl;
+pub mod retry;
+pub mod runner;
+pub mod scenarios;
+pub mod snapshot;
+pub mod soak;
+pub mod spawn;
+pub mod state;
+pub mod suite;
+pub mod sync;
+pub mod testing;
+pub mod throttle;
+pub mod tls;
+pub mod transport;
+pub mod triage;
+pub mod version;
+pub mod watch;
+pub mod wire;
+pub mod xfail;
+
+pub use suite::{S3ConformanceSuite, SuiteReport};
@@ -0,0 +1,358 @@
+//! `s3test load`: a mixed workload at a steady request rate, for sizing a deployment.
+//!
+//! Requests are started on a fixed schedule (open loop), so a slow server shows up as growing
+//! latency and, once `--max-in-flight` requests are outstanding, as dropped requests, rather
+//! than as a quietly lower rate. Results are reported per time window, which makes latency
+//! drift over the run visible, and every request goes to the `--samples` CSV file.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use rand::Rng;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::time::{self, Instant, MissedTickBehavior};
+use tracing::{info, warn};
+
+use crate::bench::{self, csv_status, csv_timestamp, millis, percentile, Op};
+use crate::cli::LoadArgs;
+use crate::dashboard;
+use crate::guard::{BoxError, BucketGuard};
+use crate::metrics::{self, Metrics};
+use crate::naming::RunId;
+use crate::payload::Payload;
+
+const PREFIX: &str = "load/";
+
+/// Relative weights of the operations in the workload.
+#[derive(Debug, Clone)]
+pub struct Mix(Vec<(Op, u32)>);
+
+impl FromStr for Mix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut weights = Vec::new();
+        for part in s.split(',') {
+            let (op, weight) =
+                part.split_once('=').ok_or_else(|| format!("expected op=weight, got '{}'", part))?;
+            let op = match op.trim().to_ascii_lowercase().as_str() {
+                "get" => Op::Get,
+                "put" => Op::Put,
+                "list" => Op::List,
+                other => return Err(format!("unknown operation '{}', expected get, put or list", other)),
+            };
+            let weight = weight.trim().parse().map_err(|_| format!("invalid weight in '{}'", part))?;
+            if weights.iter().any(|&(seen, _)| seen == op) {
+                return Err(format!("{} is weighted more than once", op.name()));
+            }
+            weights.push((op, weight));
+        }
+        // Picking rolls below the total, which has to fit a u32.
+        let total = weights.iter().try_fold(0_u32, |total, &(_, weight)| total.checked_add(weight));
+        match total {
+            None => Err("the weights of the mix add up to too much".to_string()),
+            Some(0) => Err("the mix needs at least one operation with a non-zero weight".to_string()),
+            Some(_) => Ok(Mix(weights)),
+        }
+    }
+}
+
+impl Mix {
+    fn pick(&self, rng: &mut impl Rng) -> Op {
+        let total: u32 = self.0.iter().map(|&(_, weight)| weight).sum();
+        let mut roll = rng.random_range(0..total);
+        for &(op, weight) in &self.0 {
+            if roll < weight {
+                return op;
+            }
+            roll -= weight;
+        }
+        unreachable!("roll below the total weight")
+    }
+}
+
+struct Sample {
+    /// When the request was started, relative to the start of the load.
+    at: Duration,
+    op: Op,
+    latency: Duration,
+    /// Of the last response, None when the request never got one.
+    status: Option<u16>,
+    ok: bool,
+}
+
+/// The time between two requests at `rate` requests per second.
+fn period(rate: f64) -> Result<Duration, String> {
+    if !rate.is_finite() || rate <= 0.0 {
+        return Err(format!("--rate must be a number of requests per second above zero, not {}", rate));
+    }
+    // `time::interval` refuses a period that rounds down to zero.
+    match Duration::try_from_secs_f64(1.0 / rate) {
+        Ok(Duration::ZERO) => Err(format!("--rate {} is more than one request per nanosecond", rate)),
+        Ok(period) => Ok(period),
+        Err(_) => Err(format!("--rate {} is too low to ever send a request", rate)),
+    }
+}
+
+pub async fn run(client: &Client, args: &LoadArgs) -> Result<(), BoxError> {
+    let period = period(args.rate)?;
+    if args.objects == 0 || args.window == 0 {
+        return Err("--objects and --window must be greater than zero".into());
+    }
+    let run_id = RunId::generate();
+    let bucket = BucketGuard::create(client, &run_id.bucket("load")).await?;
+    let len = usize::try_from(args.object_size).map_err(|_| "--object-size is too large")?;
+    let body = Payload::new(run_id.timestamp(), "load", len).bytes();
+
+    // Every GET must find an object, so the whole working set exists before the clock starts.
+    for i in 0..args.objects {
+        let key = format!("{}{}", PREFIX, i);
+        client.put_object().bucket(bucket.name()).key(key).body(ByteStream::from(body.clone())).send().await?;
+    }
+    let metrics = Metrics::new("load");
+    let _exporter = metrics::serve(&args.metrics, &metrics).await?;
+    let dashboard = dashboard::show(&args.metrics, &metrics);
+    info!(bucket = bucket.name(), rate = args.rate, duration = args.duration, "starting load");
+
+    let (samples_tx, mut samples_rx) = mpsc::unbounded_channel();
+    let in_flight = Arc::new(Semaphore::new(args.max_in_flight));
+    let mut dropped = Vec::new();
+    let mut ticks = time::interval(period);
+    // Catch up after a stall instead of silently lowering the rate.
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Burst);
+    let (started, started_at) = (Instant::now(), SystemTime::now());
+    let end = started + Duration::from_secs(args.duration);
+    let mut interrupted = false;
+    loop {
+        let due = tokio::select! {
+            due = ticks.tick() => due,
+            _ = dashboard::interrupted(dashboard.as_ref()) => {
+                interrupted = true;
+                break;
+            }
+        };
+        if due >= end {
+            break;
+        }
+        let Ok(permit) = in_flight.clone().try_acquire_owned() else {
+            dropped.push(due - started);
+            continue;
+        };
+        let op = args.mix.pick(&mut rand::rng());
+        let key = format!("{}{}", PREFIX, rand::rng().random_range(0..args.objects));
+        let (client, bucket, body, samples_tx) =
+            (client.clone(), bucket.name().to_string(), body.clone(), samples_tx.clone());
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let request_started = Instant::now();
+            metrics.start();
+            let (status, result) = bench::send(&client, &bucket, op, PREFIX, &key, &body).await;
+            if let Err(e) = &result {
+                warn!(op = op.name(), key, ?status, error = ?e, "load request failed");
+            }
+            let (at, latency) = (request_started - started, request_started.elapsed());
+            metrics.record(op, latency, result.is_ok());
+            let _ = samples_tx.send(Sample { at, op, latency, status, ok: result.is_ok() });
+            drop(permit);
+        });
+    }
+    // Wait for the requests still in flight.
+    drop(samples_tx);
+    let mut samples = Vec::new();
+    while let Some(sample) = samples_rx.recv().await {
+        samples.push(sample);
+    }
+    drop(dashboard);
+    if interrupted {
+        return Err("interrupted by Ctrl-C".into());
+    }
+
+    samples.sort_by_key(|s| s.at);
+    print_report(args, &samples, &dropped);
+    write_samples(args, started_at, &samples)?;
+    if let Err(e) = bucket.cleanup().await {
+        warn!(error = ?e, "could not remove load bucket");
+    }
+
+    let errors = samples.iter().filter(|s| !s.ok).count();
+    if errors > 0 || !dropped.is_empty() {
+        return Err(format!(
+            "{} of {} requests failed and {} were dropped",
+            errors,
+            samples.len() + dropped.len(),
+            dropped.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Latency percentiles of the successful requests among `samples`.
+fn latencies<'a>(samples: impl Iterator<Item = &'a Sample>) -> [Duration; 3] {
+    let mut latencies: Vec<Duration> = samples.filter(|s| s.ok).map(|s| s.latency).collect();
+    latencies.sort();
+    [50.0, 95.0, 99.0].map(|p| percentile(&latencies, p))
+}
+
+fn error_rate(samples: &[&Sample]) -> f64 {
+    match samples.len() {
+        0 => 0.0,
+        n => samples.iter().filter(|s| !s.ok).count() as f64 * 100.0 / n as f64,
+    }
+}
+
+fn print_report(args: &LoadArgs, samples: &[Sample], dropped: &[Duration]) {
+    let index = |at: Duration| (at.as_secs() / args.window) as usize;
+    let mut windows: BTreeMap<usize, Vec<&Sample>> = BTreeMap::new();
+    for sample in samples {
+        windows.entry(index(sample.at)).or_default().push(sample);
+    }
+
+    println!(
+        "{:>9} {:>6} {:>8} {:>7} {:>7} {:>9} {:>9} {:>9}",
+        "window", "reqs", "req/s", "errors", "dropped", "p50 ms", "p95 ms", "p99 ms"
+    );
+    for (&i, window_samples) in &windows {
+        // The last window is cut short when the duration is not a multiple of the window.
+        let start = i as u64 * args.window;
+        let length = args.window.min(args.duration.saturating_sub(start)).max(1);
+        let [p50, p95, p99] = latencies(window_samples.iter().copied());
+        println!(
+            "{:>8}s {:>6} {:>8.1} {:>6.1}% {:>7} {:>9.2} {:>9.2} {:>9.2}",
+            start,
+            window_samples.len(),
+            window_samples.len() as f64 / length as f64,
+            error_rate(window_samples),
+            dropped.iter().filter(|&&at| index(at) == i).count(),
+            millis(p50),
+            millis(p95),
+            millis(p99),
+        );
+    }
+
+    println!();
+    println!("{:<7} {:>6} {:>7} {:>9} {:>9} {:>9}", "op", "reqs", "errors", "p50 ms", "p95 ms", "p99 ms");
+    for op in [Op::Get, Op::Put, Op::List] {
+        let op_samples: Vec<&Sample> = samples.iter().filter(|s| s.op == op).collect();
+        if op_samples.is_empty() {
+            continue;
+        }
+        let [p50, p95, p99] = latencies(op_samples.iter().copied());
+        println!(
+            "{:<7} {:>6} {:>6.1}% {:>9.2} {:>9.2} {:>9.2}",
+            op.name(),
+            op_samples.len(),
+            error_rate(&op_samples),
+            millis(p50),
+            millis(p95),
+            millis(p99)
+        );
+    }
+
+    let achieved = samples.len() as f64 / Duration::from_secs(args.duration).as_secs_f64();
+    println!();
+    println!("target {:.1} req/s, achieved {:.1} req/s, {} dropped", args.rate, achieved, dropped.len());
+    if let (Some(first), Some(last)) = (windows.values().next(), windows.values().last()) {
+        if windows.len() > 1 {
+            let [first_p50, _, first_p99] = latencies(first.iter().copied());
+            let [last_p50, _, last_p99] = latencies(last.iter().copied());
+            println!(
+                "latency drift from the first to the last window: p50 {:+.2} ms, p99 {:+.2} ms",
+                millis(last_p50) - millis(first_p50),
+                millis(last_p99) - millis(first_p99)
+            );
+        }
+    }
+}
+
+fn write_samples(args: &LoadArgs, started_at: SystemTime, samples: &[Sample]) -> Result<(), BoxError> {
+    let mut csv = String::from("timestamp,op,size,latency_us,status,ok\n");
+    for s in samples {
+        // PUT and GET move an object; a LIST of the working set has no size of its own.
+        let size = match s.op {
+            Op::Put | Op::Get => args.object_size.to_string(),
+            _ => String::new(),
+        };
+        writeln!(
+            csv,
+            "{},{},{},{},{},{}",
+            csv_timestamp(started_at + s.at),
+            s.op.name(),
+            size,
+            s.latency.as_micros(),
+            csv_status(s.status),
+            s.ok
+        )
+        .unwrap();
+    }
+    fs::write(&args.samples, csv)?;
+    info!(path = %args.samples.display(), samples = samples.len(), "wrote load samples");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mix(s: &str) -> Result<Vec<(Op, u32)>, String> {
+        s.parse::<Mix>().map(|mix| mix.0)
+    }
+
+    #[test]
+    fn mix_parses() {
+        assert_eq!(mix("get=70,put=20,list=10"), Ok(vec![(Op::Get, 70), (Op::Put, 20), (Op::List, 10)]));
+        assert_eq!(mix(" GET = 1 , list=0"), Ok(vec![(Op::Get, 1), (Op::List, 0)]));
+    }
+
+    #[test]
+    fn mix_refuses() {
+        for s in ["", "get", "get=", "get=-1", "get=1.5", "delete=1", "head=1", "get=1;put=1"] {
+            assert!(mix(s).is_err(), "{:?} parsed", s);
+        }
+    }
+
+    #[test]
+    fn mix_refuses_a_zero_total() {
+        assert!(mix("get=0").is_err());
+        assert!(mix("get=0,put=0,list=0").is_err());
+    }
+
+    #[test]
+    fn mix_refuses_an_operation_weighted_twice() {
+        assert!(mix("get=70,put=30,get=10").is_err());
+        assert!(mix("get=1,GET=1").is_err());
+    }
+
+    #[test]
+    fn mix_refuses_weights_too_large_to_add_up() {
+        assert!(mix("get=4294967295,put=1").is_err());
+        assert_eq!(mix("get=4294967294,put=1").map(|weights| weights.len()), Ok(2));
+    }
+
+    #[test]
+    fn mix_picks_only_weighted_operations() {
+        let mix: Mix = "get=0,put=1".parse().unwrap();
+        let mut rng = rand::rng();
+        assert!((0..100).all(|_| mix.pick(&mut rng) == Op::Put));
+    }
+
+    #[test]
+    fn period_of_a_rate() {
+        assert_eq!(period(50.0), Ok(Duration::from_millis(20)));
+        assert_eq!(period(0.5), Ok(Duration::from_secs(2)));
+        assert_eq!(period(1e9), Ok(Duration::from_nanos(1)));
+    }
+
+    #[test]
+    fn period_refuses_rates_it_cannot_keep() {
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1e10, f64::MAX, 1e-300] {
+            assert!(period(rate).is_err(), "{}", rate);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::fs::File;
+use std::sync::Mutex;
+
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer};
+
+use crate::cli::LogArgs;
+use crate::guard::BoxError;
+use crate::otel::{self, Tracer};
+use crate::progress;
+use crate::triage;
+
+/// Installs the global subscriber: human-readable output on stderr plus, with `--log-file`,
+/// one JSON object per event in the file. `RUST_LOG` takes precedence over `-v` when set.
+/// With `--otlp-endpoint` spans are exported as well, whatever the log level; the returned
+/// tracer flushes them. Each test's events are also kept for its triage bundle.
+pub fn init(args: &LogArgs) -> Result<Option<Tracer>, BoxError> {
+    // The harness logs as s3test from the binary and as s3_rust_client from the library.
+    let default_directives = match args.verbose {
+        0 => "warn,s3test=info,s3_rust_client=info",
+        1 => "warn,s3test=debug,s3_rust_client=debug",
+        2 => "info,s3test=trace,s3_rust_client=trace",
+        _ => "trace",
+    };
+    // Each output gets its own filter, so that the exporter sees spans the logs leave out.
+    let filter = || EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_directives));
+
+    let file_layer = match &args.log_file {
+        Some(path) => Some(
+            fmt::layer()
+                .json()
+                .with_writer(Mutex::new(File::create(path)?))
+                .with_filter(filter()),
+        ),
+        None => None,
+    };
+
+    if args.progress {
+        progress::enable();
+    }
+    let (otlp_layer, tracer) = match &args.otlp_endpoint {
+        Some(endpoint) => {
+            let (layer, tracer) = otel::layer(endpoint, &args.otel_service_name)?;
+            (Some(layer), Some(tracer))
+        }
+        None => (None, None),
+    };
+    tracing_subscriber::registry()
+        .with(fmt::layer().with_writer(|| progress::LogWriter).with_filter(filter()))
+        .with(file_layer)
+        .with(otlp_layer)
+        .with(triage::layer())
+        .try_init()?;
+    Ok(tracer)
+}
@@ -1,114 +1,292 @@
-use aws_sdk_s3::{Client, config::Region};
-use aws_sdk_s3::primitives::ByteStream;
-use std::fs::File;
-use std::io::{Read, Write};
 use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let bucket = "mybucket";
-    let endpoint = "http://localhost";
-    let access_key = "FAKEACCESS";
-    let secret_key = "FAKESECRET";
-
-    let shared_config = aws_config::defaults(aws_config::BehaviorVersion::latest())
-        .endpoint_url(endpoint)
-        .credentials_provider(aws_sdk_s3::config::Credentials::new(
-            access_key,
-            secret_key,
-            None,
-            None,
-            "local",
-        ))
-        .region(Region::new("us-east-1"))
-        .load()
-        .await;
+use rand::Rng;
+use tracing::{info, warn};
 
-    let client = Client::new(&shared_config);
+use s3_rust_client::cassette::{Cassette, Recorder};
+use s3_rust_client::cli::{self, Cli, Command, RunArgs};
+use s3_rust_client::guard::{self, BoxError};
+use s3_rust_client::naming::RunId;
+use s3_rust_client::runner::{self, Gate, Scenario, TestContext};
+use s3_rust_client::spawn::SpawnedServer;
+use s3_rust_client::state::StateFile;
+use s3_rust_client::{
+    annotations, bench, cleanup, client, compare, completions, fuzz, health, latency, load, logging, matrix,
+    memory, pipe, rawhttp, repl, scenarios, soak, sync, triage, watch,
+};
 
-    // Create bucket
-    if let Err(e) = client.create_bucket().bucket(bucket).send().await {
-        println!("Bucket create error: {:?}", e);
-    } else {
-        println!("Bucket '{}' created.", bucket);
+#[tokio::main]
+async fn main() -> Result<(), BoxError> {
+    let cli = Cli::parse_args();
+    let tracer = logging::init(&cli.log)?;
+    if let Some(limit) = cli.max_rss {
+        memory::limit(limit);
     }
+    let result = dispatch(cli).await;
+    if let Some(tracer) = tracer {
+        tracer.shutdown().await;
+    }
+    result
+}
 
-    // Upload hello.txt
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs()
-        .to_string();
-
-    let text_key = format!("{}_hello.txt", timestamp);
-    client
-        .put_object()
-        .bucket(bucket)
-        .key(&text_key)
-        .body(ByteStream::from_static(b"Hello World from Rust"))
-        .send()
-        .await?;
-    println!("Uploaded: {}", text_key);
-
-    // Upload sample.png and sample.jpg
-    for file_name in ["sample.png", "sample.jpg"] {
-        if Path::new(file_name).exists() {
-            let key = format!("{}_{}", timestamp, file_name);
-            let mut file = File::open(file_name)?;
-            let mut data = Vec::new();
-            file.read_to_end(&mut data)?;
-            client
-                .put_object()
-                .bucket(bucket)
-                .key(&key)
-                .body(ByteStream::from(data))
-                .send()
-                .await?;
-            println!("Uploaded: {}", key);
-        } else {
-            println!("Warning: File '{}' not found. Skipping upload.", file_name);
+async fn dispatch(cli: Cli) -> Result<(), BoxError> {
+    match cli.command.unwrap_or(Command::Run(cli.run)) {
+        Command::Run(args) => {
+            let scenarios = scenarios::select(&args.only, &args.opted_in())?;
+            run(&cli.connection, &cli.retry, &args, scenarios).await
+        }
+        Command::RerunFailed(args) => rerun_failed(&cli.connection, &cli.retry, args).await,
+        Command::Cleanup(args) => {
+            let client = client::build_client(&cli.connection, &cli.retry).await?;
+            cleanup::run(&client, &args).await
+        }
+        Command::Bench(args) => {
+            let client = client::build_client(&cli.connection, &cli.retry).await?;
+            bench::run(&client, &args).await
+        }
+        Command::Load(args) => {
+            let client = client::build_client(&cli.connection, &cli.retry).await?;
+            load::run(&client, &args).await
+        }
+        Command::Soak(args) => {
+            let client = client::build_client(&cli.connection, &cli.retry).await?;
+            soak::run(&client, &args).await
+        }
+        Command::Fuzz(args) => {
+            let client = client::build_client(&cli.connection, &cli.retry).await?;
+            fuzz::run(&client, &rawhttp::RawClient::new(&cli.connection)?, &args).await
+        }
+        Command::Repl => {
+            let client = client::build_client(&cli.connection, &cli.retry).await?;
+            repl::run(&client).await
+        }
+        Command::Put(args) => {
+            let client = client::build_client(&cli.connection, &cli.retry).await?;
+            pipe::put(&client, &args).await
+        }
+        Command::Get(args) => {
+            let client = client::build_client(&cli.connection, &cli.retry).await?;
+            pipe::get(&client, &args).await
+        }
+        Command::Sync(args) => {
+            let client = client::build_client(&cli.connection, &cli.retry).await?;
+            sync::run(&client, &args).await
+        }
+        Command::ListTests => {
+            runner::print_list(&scenarios::all());
+            Ok(())
+        }
+        Command::Completions { shell } => {
+            completions::print(shell);
+            Ok(())
         }
     }
+}
 
-    // List objects
-    let resp = client.list_objects_v2().bucket(bucket).send().await?;
-    println!("Objects in bucket:");
-    if let Some(contents) = resp.contents.as_ref() {
-        for obj in contents {
-            if let Some(key) = obj.key() {
-                println!("- {}", key);
-            }
+async fn run(
+    connection: &cli::ConnectionArgs,
+    retry: &cli::RetryArgs,
+    args: &RunArgs,
+    mut scenarios: Vec<Scenario>,
+) -> Result<(), BoxError> {
+    let mut gate = Gate::new(args, &scenarios)?;
+    if args.dry_run {
+        runner::print_plan(&scenarios);
+        return Ok(());
+    }
+    if args.keep_data {
+        guard::keep_data();
+    }
+    if connection.endpoints.len() > 1 {
+        return compare::run(connection, retry, args, &scenarios, &mut gate).await;
+    }
+    if let Some(path) = &args.replay {
+        return replay(connection, retry, args, &scenarios, &gate, path).await;
+    }
+
+    let run_id = RunId::generate();
+    let mut state = match args.resume {
+        true => StateFile::resume(&args.state_file, &connection.endpoint)?,
+        false => {
+            let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+            StateFile::create(&args.state_file, &connection.endpoint, seed)?
         }
+    };
+    let seed = state.seed();
+    info!(%run_id, seed, resume = args.resume, "starting run");
+    if !args.in_order {
+        scenarios::shuffle(&mut scenarios, seed);
+        println!("running {} test(s) shuffled with --seed {}", scenarios.len(), seed);
+    }
 
-        // Download each file
-        for obj in contents {
-            if let Some(key) = obj.key() {
-                let resp = client.get_object().bucket(bucket).key(key).send().await?;
-                let data = resp.body.collect().await?.into_bytes();
+    let server = if args.spawn.spawn_server {
+        Some(SpawnedServer::start(&args.spawn, connection, run_id).await?)
+    } else {
+        None
+    };
+    let connection = &match &server {
+        Some(server) => connection.with_endpoint(server.endpoint()),
+        None => connection.clone(),
+    };
 
-                let local_file_name = format!("downloaded_{}", Path::new(key).file_name().unwrap().to_str().unwrap());
-                let mut out_file = File::create(&local_file_name)?;
-                out_file.write_all(&data)?;
-                println!("Downloaded: {}", local_file_name);
-            }
+    let interval = Duration::from_millis(args.ready_interval_ms);
+    let ctx = TestContext::connect(connection, retry, run_id, seed).await?;
+    let ctx = ctx.with_sts(&args.sts, connection);
+    let budget = Duration::from_secs(match &server {
+        Some(_) => args.spawn.server_start_timeout,
+        None => args.ready_timeout,
+    });
+    if let Err(e) = health::wait_until_ready(&ctx.client, &connection.endpoint, budget, interval).await {
+        if let Some(server) = &server {
+            server.print_logs().await;
         }
+        return Err(e);
+    }
 
-        // Delete all objects
-        for obj in contents {
-            if let Some(key) = obj.key() {
-                client.delete_object().bucket(bucket).key(key).send().await?;
-                println!("Deleted: {}", key);
+    let reference = match args.reference.connection(connection) {
+        Some(reference) => {
+            info!(endpoint = %reference.endpoint, "comparing against reference");
+            let reference_ctx = TestContext::connect(&reference, retry, run_id, seed).await?;
+            let budget = Duration::from_secs(args.ready_timeout);
+            health::wait_until_ready(&reference_ctx.client, &reference.endpoint, budget, interval).await?;
+            Some(reference_ctx)
+        }
+        None => None,
+    };
+    let server_version = runner::server_version(&ctx, &scenarios, args, Some(&gate)).await;
+    gate.set_server_version(server_version.clone());
+    let mut recorder = args.record.as_ref().map(|_| Recorder::Record(Cassette::new(run_id.to_string(), seed)));
+    let outcome = loop {
+        let results = runner::run_all(
+            &ctx,
+            reference.as_ref(),
+            &scenarios,
+            args,
+            server_version.as_ref(),
+            recorder.as_mut(),
+            Some(&mut state),
+        )
+        .await;
+        let leftovers = match args.keep_data {
+            true => Vec::new(),
+            false => cleanup::leftovers(&ctx).await,
+        };
+        if let (Some(path), Some(Recorder::Record(cassette))) = (&args.record, &recorder) {
+            cassette.save(path)?;
+            info!(path = %path.display(), "recorded cassette");
+        }
+        if let Some(path) = &args.matrix {
+            matrix::write(path, args.matrix_format, &results)?;
+            info!(path = %path.display(), "wrote conformance matrix");
+        }
+        if let Some(dir) = &args.hgrm {
+            let operations = latency::write_histograms(dir, &results)?;
+            info!(dir = %dir.display(), operations, "wrote latency histograms");
+        }
+        let outcome = runner::summarize(&results, &gate).and(cleanup::report(&leftovers));
+        if args.github_annotations {
+            annotations::print(&results, &gate);
+        }
+        if outcome.is_err() {
+            println!("replay with --seed {}", seed);
+        }
+        let run = triage::RunInfo {
+            endpoint: &connection.endpoint,
+            region: &connection.region,
+            run_id,
+            seed,
+        };
+        match triage::write(&args.triage_dir, &results, &run) {
+            Ok(0) => {}
+            Ok(bundles) => {
+                println!("triage bundles for {} test(s) in {}", bundles, args.triage_dir.display())
             }
+            Err(e) => warn!(error = %e, "could not write triage bundles"),
         }
+        let Some(dir) = &args.watch else {
+            break outcome;
+        };
+        if !watch::wait_for_change(dir).await? {
+            break outcome;
+        }
+        // Every rerun is a fresh run with the same seed, not a resumption of the last one.
+        state = StateFile::create(&args.state_file, &connection.endpoint, seed)?;
+    };
+    if let Some(server) = server {
+        if outcome.is_err() {
+            server.print_logs().await;
+        }
+        server.stop().await?;
     }
+    outcome
+}
 
-    // Delete bucket
-    if let Err(e) = client.delete_bucket().bucket(bucket).send().await {
-        println!("DeleteBucket error: {:?}", e);
-    } else {
-        println!("Bucket '{}' deleted.", bucket);
+/// Runs the tests that failed in the saved run again with its seed, then tells the ones that
+/// fail every time from the ones that passed this time and are therefore flaky. The state file
+/// records the rerun, so that repeating the command narrows things down further.
+async fn rerun_failed(
+    connection: &cli::ConnectionArgs,
+    retry: &cli::RetryArgs,
+    mut args: RunArgs,
+) -> Result<(), BoxError> {
+    if args.seed.is_some() || args.resume {
+        return Err("rerun-failed takes the seed from the state file and cannot resume".into());
+    }
+    let previous = StateFile::resume(&args.state_file, &connection.endpoint)?;
+    let failed = previous.failed();
+    if failed.is_empty() {
+        println!("no failed tests in {}", args.state_file.display());
+        return Ok(());
+    }
+    args.seed = Some(previous.seed());
+    let scenarios: Vec<_> = scenarios::select(&args.only, &args.opted_in())?
+        .into_iter()
+        .filter(|s| failed.iter().any(|name| name == s.name))
+        .collect();
+    if scenarios.is_empty() {
+        return Err("none of the failed tests is among the selected scenarios".into());
     }
+    info!(tests = scenarios.len(), seed = previous.seed(), "rerunning failed tests");
 
-    Ok(())
+    let outcome = run(connection, retry, &args, scenarios).await;
+    let rerun = StateFile::resume(&args.state_file, &connection.endpoint)?;
+    println!();
+    for test in &failed {
+        match rerun.passed(test) {
+            Some(_) => println!("{:<40} flaky: passed this time", test),
+            None if rerun.failed().contains(test) => println!("{:<40} failed again", test),
+            None => {}
+        }
+    }
+    outcome
+}
+
+/// Runs the scenarios against a recorded cassette, with the run ID and seed of the recording so
+/// that every request matches the recorded one.
+async fn replay(
+    connection: &cli::ConnectionArgs,
+    retry: &cli::RetryArgs,
+    args: &RunArgs,
+    scenarios: &[Scenario],
+    gate: &Gate<'_>,
+    path: &Path,
+) -> Result<(), BoxError> {
+    let cassette = Cassette::load(path)?;
+    let run_id = RunId::parse(&cassette.run_id)
+        .ok_or_else(|| format!("cassette has an invalid run ID '{}'", cassette.run_id))?;
+    info!(%run_id, seed = cassette.seed, path = %path.display(), "replaying cassette");
+
+    let ctx = TestContext::connect(connection, retry, run_id, cassette.seed).await?;
+    let ctx = ctx.with_sts(&args.sts, connection);
+    let mut recorder = Recorder::Replay(cassette);
+    // The cassette only answers the requests of the tests, so the server is neither asked its
+    // version nor checked for leftovers: a replay creates nothing on it.
+    let server = args.server_version.as_ref();
+    let results = runner::run_all(&ctx, None, scenarios, args, server, Some(&mut recorder), None).await;
+    let outcome = runner::summarize(&results, gate);
+    if args.github_annotations {
+        annotations::print(&results, gate);
+    }
+    outcome
 }
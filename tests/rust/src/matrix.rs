@@ -0,0 +1,175 @@
+//! Conformance matrix: rolls test outcomes up into a support level per S3 API or feature.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::guard::BoxError;
+use crate::runner::{TestResult, Verdict};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MatrixFormat {
+    Markdown,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Support {
+    /// Every test covering the feature passed.
+    Supported,
+    /// Some tests passed, others failed.
+    PartiallySupported,
+    /// Every test failed, and the server said it does not implement the request, or the test
+    /// found it lacks the protocol feature ([`Unsupported`]).
+    NotImplemented,
+    /// Every test failed for another reason.
+    Broken,
+}
+
+impl Support {
+    fn label(self) -> &'static str {
+        match self {
+            Support::Supported => "Supported",
+            Support::PartiallySupported => "Partially supported",
+            Support::NotImplemented => "Not implemented",
+            Support::Broken => "Broken",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeatureRow {
+    pub feature: String,
+    pub support: Support,
+    pub tests: Vec<TestOutcome>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TestOutcome {
+    pub test: String,
+    pub passed: bool,
+    pub verdict: String,
+}
+
+/// Groups results by the features their scenarios cover, in alphabetical order.
+pub fn build(results: &[TestResult]) -> Vec<FeatureRow> {
+    let mut by_feature: BTreeMap<&str, Vec<&TestResult>> = BTreeMap::new();
+    for result in results {
+        for feature in result.features {
+            by_feature.entry(feature).or_default().push(result);
+        }
+    }
+
+    by_feature
+        .into_iter()
+        .map(|(feature, results)| {
+            let passed = results
+                .iter()
+                .filter(|r| matches!(r.verdict, Verdict::Passed))
+                .count();
+            let support = if passed == results.len() {
+                Support::Supported
+            } else if passed > 0 {
+                Support::PartiallySupported
+            } else if results.iter().all(|r| not_implemented(r)) {
+                Support::NotImplemented
+            } else {
+                Support::Broken
+            };
+            FeatureRow {
+                feature: feature.to_string(),
+                support,
+                tests: results
+                    .iter()
+                    .map(|r| TestOutcome {
+                        test: r.name.to_string(),
+                        passed: matches!(r.verdict, Verdict::Passed),
+                        verdict: r.verdict.to_string(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// A test's finding that the server lacks a protocol feature it never gets to answer for with
+/// a status code, such as HTTP/2; counted as [`Support::NotImplemented`].
+pub struct Unsupported(pub String);
+
+impl Unsupported {
+    /// Starts the failure reason, which is all the matrix gets to see of the error.
+    const PREFIX: &'static str = "unsupported: ";
+}
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", Unsupported::PREFIX, self.0)
+    }
+}
+
+impl std::fmt::Debug for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
+/// Whether the server rejected one of the test's requests as unsupported, or the test found it
+/// does not support what it covers, or it was skipped as the server's release lacks it.
+/// Teardown probes for multipart uploads and versions don't count.
+fn not_implemented(result: &TestResult) -> bool {
+    if matches!(&result.verdict, Verdict::Failed(reason) if reason.starts_with(Unsupported::PREFIX)) {
+        return true;
+    }
+    if let Verdict::Skipped(_) = result.verdict {
+        return true;
+    }
+    result
+        .exchanges
+        .iter()
+        .filter(|e| !e.teardown)
+        .filter_map(|e| e.response.as_ref())
+        .any(|response| {
+            matches!(response.status, 405 | 501)
+                || matches!(
+                    response.error_code(),
+                    Some("NotImplemented" | "MethodNotAllowed")
+                )
+        })
+}
+
+pub fn render_markdown(rows: &[FeatureRow]) -> String {
+    let mut out = String::from("| Feature | Support | Tests |\n|---|---|---|\n");
+    for row in rows {
+        let tests = row
+            .tests
+            .iter()
+            .map(|t| format!("{} {}", if t.passed { "✓" } else { "✗" }, t.test))
+            .collect::<Vec<_>>()
+            .join("<br>");
+        writeln!(
+            out,
+            "| {} | {} | {} |",
+            row.feature,
+            row.support.label(),
+            tests
+        )
+        .unwrap();
+    }
+    out
+}
+
+pub fn write(path: &Path, format: MatrixFormat, results: &[TestResult]) -> Result<(), BoxError> {
+    let rows = build(results);
+    let contents = match format {
+        MatrixFormat::Markdown => render_markdown(&rows),
+        MatrixFormat::Json => serde_json::to_string_pretty(&rows)?,
+    };
+    fs::write(path, contents)?;
+    Ok(())
+}
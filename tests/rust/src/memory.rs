@@ -0,0 +1,37 @@
+//! The harness's own resident memory (`--max-rss`). Downloads are verified as they stream in,
+//! so memory stays flat whatever the object size; one that ends up buffered whole fails the
+//! test it is part of here, instead of showing as swapping or an OOM kill on a multi-GB GET.
+
+use std::fs;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::guard::BoxError;
+
+/// Resident memory allowed, in bytes; 0 when there is no limit.
+static LIMIT: AtomicU64 = AtomicU64::new(0);
+
+/// Makes [`check`] fail once the harness holds more than `bytes` of memory.
+pub fn limit(bytes: u64) {
+    LIMIT.store(bytes, Ordering::Relaxed);
+}
+
+/// Resident memory of this process, from `/proc`; None where there is no `/proc`.
+pub fn resident() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kib: u64 = line.trim_start_matches("VmRSS:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Fails if the process is over the `--max-rss` limit, naming `what` it was doing.
+pub fn check(what: &str) -> Result<(), BoxError> {
+    let limit = LIMIT.load(Ordering::Relaxed);
+    match resident() {
+        Some(rss) if limit > 0 && rss > limit => {
+            let (rss, limit) = (rss / (1024 * 1024), limit / (1024 * 1024));
+            let message = format!("{}: the harness holds {} MiB, over --max-rss {} MiB", what, rss, limit);
+            Err(message.into())
+        }
+        _ => Ok(()),
+    }
+}
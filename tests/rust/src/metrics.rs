@@ -0,0 +1,199 @@
+//! Live Prometheus metrics for `bench`, `load` and `soak` (`--metrics-listen`).
+//!
+//! Every request the workload sends is counted per operation, with its latency in a histogram,
+//! and `GET /metrics` renders the totals so far in the text exposition format. Long runs can
+//! then be watched in Grafana next to the PHP server's own dashboards, or without one on the
+//! terminal dashboard that draws the same counters.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::{Method, Request, Response, StatusCode};
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+
+use crate::bench::Op;
+use crate::cli::MetricsArgs;
+use crate::guard::BoxError;
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const BUCKETS: [f64; 14] =
+    [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+#[derive(Default)]
+struct OpStats {
+    requests: u64,
+    errors: u64,
+    /// Requests per bucket, not yet cumulative.
+    buckets: [u64; BUCKETS.len()],
+    latency_sum: f64,
+}
+
+/// What has been counted of one operation so far.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Totals {
+    pub requests: u64,
+    pub errors: u64,
+    /// Of every request, in seconds.
+    pub latency_sum: f64,
+}
+
+/// Request counters of one run, labelled with the mode that produced them.
+pub struct Metrics {
+    mode: &'static str,
+    ops: Mutex<BTreeMap<&'static str, OpStats>>,
+    in_flight: AtomicUsize,
+}
+
+impl Metrics {
+    pub fn new(mode: &'static str) -> Arc<Self> {
+        Arc::new(Metrics { mode, ops: Mutex::new(BTreeMap::new()), in_flight: AtomicUsize::new(0) })
+    }
+
+    pub fn mode(&self) -> &'static str {
+        self.mode
+    }
+
+    /// Counts a request as in flight until it is recorded, which every request has to be.
+    pub fn start(&self) {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// The totals of every operation so far, by name.
+    pub fn totals(&self) -> BTreeMap<&'static str, Totals> {
+        let ops = self.ops.lock().unwrap();
+        ops.iter()
+            .map(|(&op, stats)| {
+                let totals =
+                    Totals { requests: stats.requests, errors: stats.errors, latency_sum: stats.latency_sum };
+                (op, totals)
+            })
+            .collect()
+    }
+
+    pub fn record(&self, op: Op, latency: Duration, ok: bool) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        let mut ops = self.ops.lock().unwrap();
+        let stats = ops.entry(op.name()).or_default();
+        stats.requests += 1;
+        if !ok {
+            stats.errors += 1;
+        }
+        let seconds = latency.as_secs_f64();
+        stats.latency_sum += seconds;
+        if let Some(i) = BUCKETS.iter().position(|&bound| seconds <= bound) {
+            stats.buckets[i] += 1;
+        }
+    }
+
+    fn render(&self) -> String {
+        let ops = self.ops.lock().unwrap();
+        let labels = |op: &str| format!("mode=\"{}\",op=\"{}\"", self.mode, op);
+        let mut out = String::new();
+        out.push_str("# HELP s3test_requests_total Requests sent by the workload.\n");
+        out.push_str("# TYPE s3test_requests_total counter\n");
+        for (op, stats) in ops.iter() {
+            let _ = writeln!(out, "s3test_requests_total{{{}}} {}", labels(op), stats.requests);
+        }
+        out.push_str("# HELP s3test_errors_total Requests that failed or returned wrong data.\n");
+        out.push_str("# TYPE s3test_errors_total counter\n");
+        for (op, stats) in ops.iter() {
+            let _ = writeln!(out, "s3test_errors_total{{{}}} {}", labels(op), stats.errors);
+        }
+        out.push_str("# HELP s3test_requests_in_flight Requests sent and not yet answered.\n");
+        out.push_str("# TYPE s3test_requests_in_flight gauge\n");
+        let _ = writeln!(out, "s3test_requests_in_flight{{mode=\"{}\"}} {}", self.mode, self.in_flight());
+        out.push_str("# HELP s3test_request_duration_seconds Latency of the workload's requests.\n");
+        out.push_str("# TYPE s3test_request_duration_seconds histogram\n");
+        for (op, stats) in ops.iter() {
+            let labels = labels(op);
+            let mut cumulative = 0;
+            for (bound, count) in BUCKETS.iter().zip(stats.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "s3test_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "s3test_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, stats.requests
+            );
+            let _ = writeln!(out, "s3test_request_duration_seconds_sum{{{}}} {}", labels, stats.latency_sum);
+            let _ = writeln!(out, "s3test_request_duration_seconds_count{{{}}} {}", labels, stats.requests);
+        }
+        out
+    }
+}
+
+/// Serves `/metrics` until dropped.
+pub struct Exporter {
+    task: JoinHandle<()>,
+}
+
+impl Drop for Exporter {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Starts serving `metrics` if `--metrics-listen` was given.
+pub async fn serve(args: &MetricsArgs, metrics: &Arc<Metrics>) -> Result<Option<Exporter>, BoxError> {
+    let Some(addr) = args.metrics_listen else {
+        return Ok(None);
+    };
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| format!("cannot listen for metrics on {}: {}", addr, e))?;
+    info!(addr = %listener.local_addr()?, "serving Prometheus metrics on /metrics");
+    let metrics = metrics.clone();
+    let task = tokio::spawn(async move {
+        loop {
+            let Ok((stream, peer)) = listener.accept().await else {
+                return;
+            };
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |request| respond(metrics.clone(), request));
+                let connection = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service);
+                if let Err(e) = connection.await {
+                    debug!(%peer, error = %e, "metrics connection ended");
+                }
+            });
+        }
+    });
+    Ok(Some(Exporter { task }))
+}
+
+async fn respond(
+    metrics: Arc<Metrics>,
+    request: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => Response::builder()
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(Full::new(Bytes::from(metrics.render()))),
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Full::new(Bytes::from_static(b"not found\n"))),
+    };
+    Ok(response.expect("static response parts are valid"))
+}
+
@@ -0,0 +1,159 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+
+/// Prefix shared by every bucket the harness creates.
+pub const BUCKET_PREFIX: &str = "s3test-";
+
+/// Identifier of a single harness run: the start time in seconds plus a random suffix,
+/// so concurrent runs against the same server never share bucket names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunId {
+    timestamp: u64,
+    suffix: u32,
+}
+
+impl RunId {
+    pub fn generate() -> Self {
+        RunId {
+            timestamp: unix_now(),
+            suffix: rand::rng().random(),
+        }
+    }
+
+    /// Recovers the run ID from a bucket name produced by [`RunId::bucket`].
+    pub fn from_bucket(bucket: &str) -> Option<Self> {
+        let mut parts = bucket.strip_prefix(BUCKET_PREFIX)?.splitn(3, '-');
+        let timestamp = parts.next()?;
+        let suffix = parts.next()?;
+        parts.next()?;
+        Self::from_parts(timestamp, suffix)
+    }
+
+    /// Parses the `Display` form, `<timestamp>-<suffix>`.
+    pub fn parse(id: &str) -> Option<Self> {
+        let (timestamp, suffix) = id.split_once('-')?;
+        Self::from_parts(timestamp, suffix)
+    }
+
+    fn from_parts(timestamp: &str, suffix: &str) -> Option<Self> {
+        if suffix.len() != 8
+            || !suffix.bytes().all(|b| b.is_ascii_hexdigit())
+            || !timestamp.bytes().all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        Some(RunId {
+            timestamp: timestamp.parse().ok()?,
+            suffix: u32::from_str_radix(suffix, 16).ok()?,
+        })
+    }
+
+    /// Start of the run in seconds since the Unix epoch.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Bucket name for this run, e.g. `s3test-1718900000-1a2b3c4d-main`.
+    pub fn bucket(&self, name: &str) -> String {
+        format!("{}{}-{}", BUCKET_PREFIX, self, name)
+    }
+}
+
+impl fmt::Display for RunId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{:08x}", self.timestamp, self.suffix)
+    }
+}
+
+/// Current time in seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: RunId = RunId { timestamp: 1718900000, suffix: 0x1a2b3c4d };
+
+    #[test]
+    fn round_trips() {
+        assert_eq!(ID.to_string(), "1718900000-1a2b3c4d");
+        assert_eq!(RunId::parse(&ID.to_string()), Some(ID));
+        assert_eq!(ID.bucket("main"), "s3test-1718900000-1a2b3c4d-main");
+        assert_eq!(RunId::from_bucket(&ID.bucket("main")), Some(ID));
+
+        let generated = RunId::generate();
+        assert_eq!(RunId::from_bucket(&generated.bucket("x")), Some(generated));
+        // A suffix with leading zeros keeps its eight digits.
+        let small = RunId { timestamp: 1, suffix: 0xab };
+        assert_eq!(RunId::parse(&small.to_string()), Some(small));
+    }
+
+    #[test]
+    fn the_name_after_the_id_may_have_dashes() {
+        assert_eq!(RunId::from_bucket("s3test-1718900000-1a2b3c4d-multi-part-name"), Some(ID));
+    }
+
+    #[test]
+    fn foreign_buckets_are_not_a_run() {
+        for bucket in [
+            "s3test",
+            "s3test-",
+            "s3test-backups",
+            "s3test-prod-data",
+            "s3test-2024-archive-eu",
+            "s3test-1718900000-main",
+            "s3tests-1718900000-1a2b3c4d-main",
+            "my-s3test-1718900000-1a2b3c4d-main",
+            "S3TEST-1718900000-1a2b3c4d-main",
+        ] {
+            assert_eq!(RunId::from_bucket(bucket), None, "{}", bucket);
+        }
+    }
+
+    #[test]
+    fn malformed_ids_are_refused() {
+        for id in [
+            "",
+            "-",
+            "1718900000",
+            "1718900000-",
+            "-1a2b3c4d",
+            "1718900000-1a2b3c4",
+            "1718900000-1a2b3c4d5",
+            "1718900000-1a2b3c4g",
+            "1718900000-+a2b3c4d",
+            "171890000x-1a2b3c4d",
+            "+1718900000-1a2b3c4d",
+            "99999999999999999999-1a2b3c4d",
+            "1718900000-1a2b3c4d-main",
+        ] {
+            assert_eq!(RunId::parse(id), None, "{}", id);
+        }
+    }
+
+    /// A bucket cut short, or one whose suffix runs on into what follows, is not mistaken for
+    /// the run whose prefix it shares.
+    #[test]
+    fn truncated_ids_do_not_collide() {
+        for bucket in [
+            "s3test-1718900000-1a2b3c4d",
+            "s3test-1718900000-1a2b3c4",
+            "s3test-1718900000-1a2b3c4-d",
+            "s3test-1718900000-1a2b3c4dmain",
+            "s3test-1718900000-1a2b3c4d0-main",
+            "s3test-17189000001a2b3c4d-main",
+            "s3test-171890000-01a2b3c4d-main",
+        ] {
+            assert_eq!(RunId::from_bucket(bucket), None, "{}", bucket);
+        }
+        let shorter = RunId::from_bucket("s3test-171890000-1a2b3c4d-main");
+        assert_ne!(shorter, Some(ID));
+    }
+}
@@ -0,0 +1,534 @@
+//! OpenTelemetry trace export (`--otlp-endpoint`).
+//!
+//! A tracing layer turns the spans of each test, each S3 operation and each attempt at it
+//! into OTLP spans and posts them in batches, JSON-encoded, to a collector's `/v1/traces`.
+//! Every request the SDK sends carries a W3C `traceparent` header naming its attempt span, so
+//! a PHP server with tracing of its own continues the same trace.
+//!
+//! The OpenTelemetry SDK crates are not among the dependencies this builds with, so the
+//! exporter is written here against the OTLP JSON encoding. It connects to the collector over
+//! plain HTTP itself, without `--proxy` or TLS.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aws_sdk_s3::config::interceptors::BeforeTransmitInterceptorContextMut;
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use bytes::Bytes;
+use http::{Request, Uri};
+use http_body_util::{BodyExt, Full};
+use hyper_util::rt::TokioIo;
+use rand::Rng;
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{debug, warn, Event, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{filter, Registry};
+
+use crate::guard::BoxError;
+
+const MAX_BATCH: usize = 512;
+const EXPORT_INTERVAL: Duration = Duration::from_secs(2);
+/// Finished spans waiting for export; more than this while the collector is slow are dropped.
+const QUEUE: usize = 8192;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether spans are being exported, so that clients know to propagate the trace context.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+enum Message {
+    Span(Value),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Handle on the exporter, to send off what is still queued before the process exits.
+pub struct Tracer {
+    tx: mpsc::Sender<Message>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Tracer {
+    pub async fn shutdown(self) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.tx.send(Message::Flush(done_tx)).await.is_ok() {
+            let _ = time::timeout(Duration::from_secs(5), done_rx).await;
+        }
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        if dropped > 0 {
+            warn!(dropped, "some spans were dropped because the collector did not keep up");
+        }
+    }
+}
+
+/// The layer and its handle, with the exporter running in the background. Must be called
+/// from within the runtime.
+pub fn layer<S>(endpoint: &str, service_name: &str) -> Result<(impl Layer<S>, Tracer), BoxError>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    // `--otlp-endpoint` only takes http:// URLs, which `post` connects to directly.
+    let traces: Uri = format!("{}/v1/traces", endpoint.trim_end_matches('/')).parse()?;
+    let (tx, rx) = mpsc::channel(QUEUE);
+    let dropped = Arc::new(AtomicU64::new(0));
+    tokio::spawn(export(traces, service_name.to_string(), rx));
+    ENABLED.store(true, Ordering::Relaxed);
+    let layer = OtlpLayer { tx: tx.clone(), dropped: dropped.clone() };
+    Ok((layer.with_filter(filter::filter_fn(exported)), Tracer { tx, dropped }))
+}
+
+/// Spans of the harness and the SDK's operation and attempt spans, whatever their level, and
+/// the harness's notable events within them.
+fn exported(meta: &Metadata<'_>) -> bool {
+    // The binary's target and the library's.
+    let ours = ["s3test", "s3_rust_client"].iter().any(|t| meta.target().starts_with(t));
+    if meta.is_span() {
+        ours || is_sdk_span(meta)
+    } else {
+        (ours && *meta.level() <= Level::INFO) || *meta.level() <= Level::WARN
+    }
+}
+
+fn is_sdk_span(meta: &Metadata<'_>) -> bool {
+    meta.name().starts_with("S3.") || meta.name() == "try_attempt"
+}
+
+/// What the layer keeps in a span's extensions while it is open.
+struct SpanData {
+    trace_id: u128,
+    span_id: u64,
+    parent_span_id: Option<u64>,
+    name: &'static str,
+    client: bool,
+    start: u64,
+    attributes: Vec<Value>,
+    events: Vec<Value>,
+}
+
+struct OtlpLayer {
+    tx: mpsc::Sender<Message>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let parent = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<SpanData>().map(|p| (p.trace_id, p.span_id)));
+        let mut rng = rand::rng();
+        let mut data = SpanData {
+            trace_id: parent.map_or_else(|| rng.random_range(1..=u128::MAX), |(trace_id, _)| trace_id),
+            span_id: rng.random_range(1..=u64::MAX),
+            parent_span_id: parent.map(|(_, span_id)| span_id),
+            name: attrs.metadata().name(),
+            client: is_sdk_span(attrs.metadata()),
+            start: unix_nanos(),
+            attributes: Vec::new(),
+            events: Vec::new(),
+        };
+        attrs.record(&mut FieldVisitor(&mut data.attributes));
+        span.extensions_mut().insert(data);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+                values.record(&mut FieldVisitor(&mut data.attributes));
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(data) = extensions.get_mut::<SpanData>() else {
+            return;
+        };
+        let level = event.metadata().level().as_str();
+        let mut attributes = vec![attribute("level", json!({ "stringValue": level }))];
+        event.record(&mut FieldVisitor(&mut attributes));
+        // The message becomes the event's name rather than one of its attributes.
+        let name = match attributes.iter().position(|a| a["key"] == "message") {
+            Some(i) => attributes.remove(i)["value"]["stringValue"].as_str().unwrap_or_default().to_string(),
+            None => event.metadata().name().to_string(),
+        };
+        let time = unix_nanos().to_string();
+        data.events.push(json!({ "timeUnixNano": time, "name": name, "attributes": attributes }));
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(data) = span.extensions_mut().remove::<SpanData>() else {
+            return;
+        };
+        if self.tx.try_send(Message::Span(otlp_span(data, unix_nanos()))).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A span in the OTLP JSON encoding, which writes IDs in hex and 64-bit integers as strings.
+fn otlp_span(data: SpanData, end: u64) -> Value {
+    let mut otlp = json!({
+        "traceId": format!("{:032x}", data.trace_id),
+        "spanId": format!("{:016x}", data.span_id),
+        "name": data.name,
+        // SPAN_KIND_CLIENT for requests to the server, SPAN_KIND_INTERNAL for the rest.
+        "kind": if data.client { 3 } else { 1 },
+        "startTimeUnixNano": data.start.to_string(),
+        "endTimeUnixNano": end.to_string(),
+        "attributes": data.attributes,
+        "events": data.events,
+    });
+    if let Some(parent) = data.parent_span_id {
+        otlp["parentSpanId"] = json!(format!("{:016x}", parent));
+    }
+    otlp
+}
+
+/// Collects span or event fields as OTLP attributes.
+struct FieldVisitor<'a>(&'a mut Vec<Value>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push(attribute(field.name(), json!({ "stringValue": value })));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.push(attribute(field.name(), json!({ "intValue": value.to_string() })));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.push(attribute(field.name(), json!({ "intValue": value.to_string() })));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.push(attribute(field.name(), json!({ "boolValue": value })));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.push(attribute(field.name(), json!({ "doubleValue": value })));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.push(attribute(field.name(), json!({ "stringValue": format!("{:?}", value) })));
+    }
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    json!({ "key": key, "value": value })
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// Sends finished spans to the collector in batches, until the channel closes.
+async fn export(traces: Uri, service_name: String, mut rx: mpsc::Receiver<Message>) {
+    let mut batch = Vec::new();
+    let mut failing = false;
+    let mut ticks = time::interval(EXPORT_INTERVAL);
+    loop {
+        let flushed = tokio::select! {
+            message = rx.recv() => match message {
+                Some(Message::Span(span)) => {
+                    batch.push(span);
+                    if batch.len() < MAX_BATCH {
+                        continue;
+                    }
+                    None
+                }
+                Some(Message::Flush(done)) => Some(done),
+                None => return,
+            },
+            _ = ticks.tick() => None,
+        };
+        if !batch.is_empty() {
+            let spans = std::mem::take(&mut batch);
+            let count = spans.len();
+            match post(&traces, &service_name, spans).await {
+                Ok(()) => failing = false,
+                // Say so once; the collector may be down for the whole run.
+                Err(e) if !failing => {
+                    warn!(endpoint = %traces, error = %e, count, "could not export spans");
+                    failing = true;
+                }
+                Err(e) => debug!(error = %e, count, "could not export spans"),
+            }
+        }
+        if let Some(done) = flushed {
+            let _ = done.send(());
+        }
+    }
+}
+
+/// The body of an OTLP/HTTP ExportTraceServiceRequest carrying `spans`.
+fn export_request(service_name: &str, spans: Vec<Value>) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [attribute("service.name", json!({ "stringValue": service_name }))],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "s3test", "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+async fn post(traces: &Uri, service_name: &str, spans: Vec<Value>) -> Result<(), BoxError> {
+    let body = export_request(service_name, spans);
+    let authority = traces.authority().ok_or("OTLP endpoint has no host")?.as_str();
+    let target = match traces.port_u16() {
+        Some(_) => authority.to_string(),
+        None => format!("{}:80", authority),
+    };
+    let stream = TcpStream::connect(&target).await?;
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(connection);
+    let request = Request::post(traces.path())
+        .header("Host", authority)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_vec(&body)?)))?;
+    let response = sender.send_request(request).await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.into_body().collect().await?.to_bytes();
+        return Err(format!("collector answered {}: {}", status, String::from_utf8_lossy(&body)).into());
+    }
+    Ok(())
+}
+
+/// Adds a `traceparent` header naming the current attempt's span to every SDK request.
+#[derive(Debug)]
+pub struct TraceContext;
+
+impl Intercept for TraceContext {
+    fn name(&self) -> &'static str {
+        "TraceContext"
+    }
+
+    fn modify_before_transmit(
+        &self,
+        context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), aws_smithy_runtime_api::box_error::BoxError> {
+        if let Some((trace_id, span_id)) = current_ids() {
+            let traceparent = format!("00-{:032x}-{:016x}-01", trace_id, span_id);
+            context.request_mut().headers_mut().insert("traceparent", traceparent);
+        }
+        Ok(())
+    }
+}
+
+/// Trace and span ID of the innermost exported span around the caller.
+fn current_ids() -> Option<(u128, u64)> {
+    tracing::Span::current()
+        .with_subscriber(|(id, dispatch)| {
+            let registry = dispatch.downcast_ref::<Registry>()?;
+            let span = registry.span(id)?;
+            span.scope().find_map(|s| s.extensions().get::<SpanData>().map(|d| (d.trace_id, d.span_id)))
+        })
+        .flatten()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use tracing::{debug_span, info, info_span};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    /// Checks `value` against the OTLP JSON encoding of `ExportTraceServiceRequest`, from
+    /// opentelemetry-proto: only the fields the messages define, lowerCamelCase, IDs in hex
+    /// and 64-bit integers as decimal strings.
+    fn check_request(value: &Value) {
+        check_fields(value, &["resourceSpans"]);
+        for resource_spans in value["resourceSpans"].as_array().unwrap() {
+            check_fields(resource_spans, &["resource", "scopeSpans", "schemaUrl"]);
+            check_fields(&resource_spans["resource"], &["attributes", "droppedAttributesCount"]);
+            check_attributes(&resource_spans["resource"]["attributes"]);
+            for scope_spans in resource_spans["scopeSpans"].as_array().unwrap() {
+                check_fields(scope_spans, &["scope", "spans", "schemaUrl"]);
+                let scope = &scope_spans["scope"];
+                check_fields(scope, &["name", "version", "attributes", "droppedAttributesCount"]);
+                assert!(scope["name"].is_string() && scope["version"].is_string());
+                scope_spans["spans"].as_array().unwrap().iter().for_each(check_span);
+            }
+        }
+    }
+
+    fn check_span(span: &Value) {
+        check_fields(
+            span,
+            &[
+                "traceId",
+                "spanId",
+                "traceState",
+                "parentSpanId",
+                "flags",
+                "name",
+                "kind",
+                "startTimeUnixNano",
+                "endTimeUnixNano",
+                "attributes",
+                "droppedAttributesCount",
+                "events",
+                "droppedEventsCount",
+                "links",
+                "droppedLinksCount",
+                "status",
+            ],
+        );
+        check_id(&span["traceId"], 32);
+        check_id(&span["spanId"], 16);
+        if !span["parentSpanId"].is_null() {
+            check_id(&span["parentSpanId"], 16);
+        }
+        assert!(span["name"].is_string());
+        assert!((0..=5).contains(&span["kind"].as_u64().unwrap()), "kind in {}", span);
+        let start = check_nanos(&span["startTimeUnixNano"]);
+        assert!(check_nanos(&span["endTimeUnixNano"]) >= start);
+        check_attributes(&span["attributes"]);
+        for event in span["events"].as_array().unwrap() {
+            check_fields(event, &["timeUnixNano", "name", "attributes", "droppedAttributesCount"]);
+            check_nanos(&event["timeUnixNano"]);
+            assert!(event["name"].is_string());
+            check_attributes(&event["attributes"]);
+        }
+    }
+
+    fn check_fields(value: &Value, allowed: &[&str]) {
+        for key in value.as_object().unwrap().keys() {
+            assert!(allowed.contains(&key.as_str()), "{} is not a field of {}", key, value);
+        }
+    }
+
+    fn check_id(id: &Value, digits: usize) {
+        let id = id.as_str().unwrap();
+        assert_eq!(id.len(), digits, "{}", id);
+        assert!(id.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)), "{}", id);
+        assert!(id.bytes().any(|b| b != b'0'), "an all-zero ID is invalid");
+    }
+
+    fn check_nanos(nanos: &Value) -> u64 {
+        nanos.as_str().unwrap().parse().unwrap()
+    }
+
+    /// KeyValues whose AnyValue holds exactly one of the scalar kinds.
+    fn check_attributes(attributes: &Value) {
+        for attribute in attributes.as_array().unwrap() {
+            check_fields(attribute, &["key", "value"]);
+            assert!(attribute["key"].is_string());
+            let value = attribute["value"].as_object().unwrap();
+            assert_eq!(value.len(), 1, "{}", attribute);
+            let (kind, value) = value.iter().next().unwrap();
+            match kind.as_str() {
+                "stringValue" => assert!(value.is_string()),
+                "boolValue" => assert!(value.is_boolean()),
+                "intValue" => {
+                    value.as_str().unwrap().parse::<i64>().unwrap();
+                }
+                "doubleValue" => assert!(value.is_number()),
+                other => panic!("{} is not a scalar AnyValue", other),
+            }
+        }
+    }
+
+    fn attribute_of<'a>(attributes: &'a Value, key: &str) -> &'a Value {
+        let attributes = attributes.as_array().unwrap();
+        &attributes.iter().find(|a| a["key"] == key).unwrap_or_else(|| panic!("no {}", key))["value"]
+    }
+
+    /// The spans `f` produces, in the order they closed, and the IDs it saw as current inside
+    /// its innermost span.
+    fn spans_of(f: impl FnOnce() -> Option<(u128, u64)>) -> (Vec<Value>, Option<(u128, u64)>) {
+        let (tx, mut rx) = mpsc::channel(QUEUE);
+        let layer = OtlpLayer { tx, dropped: Arc::new(AtomicU64::new(0)) };
+        let current = tracing::subscriber::with_default(Registry::default().with(layer), f);
+        let mut spans = Vec::new();
+        while let Ok(Message::Span(span)) = rx.try_recv() {
+            spans.push(span);
+        }
+        (spans, current)
+    }
+
+    #[test]
+    fn spans_follow_the_otlp_schema() {
+        let (spans, _) = spans_of(|| {
+            let test = info_span!("test", name = "crud::round_trip", attempt = 1_u64, seed = -3_i64);
+            let _test = test.enter();
+            let operation = debug_span!("S3.PutObject", ok = tracing::field::Empty, ratio = 0.5);
+            let _operation = operation.enter();
+            info!(status = 200_u64, key = ?"a b", "sent the request");
+            operation.record("ok", true);
+            None
+        });
+        assert_eq!(spans.len(), 2);
+        spans.iter().for_each(check_span);
+        check_request(&export_request("s3test", spans.clone()));
+
+        let (operation, test) = (&spans[0], &spans[1]);
+        assert_eq!(test["name"], "test");
+        assert_eq!(test["kind"], 1);
+        assert!(test.get("parentSpanId").is_none());
+        assert_eq!(attribute_of(&test["attributes"], "name"), &json!({ "stringValue": "crud::round_trip" }));
+        assert_eq!(attribute_of(&test["attributes"], "attempt"), &json!({ "intValue": "1" }));
+        assert_eq!(attribute_of(&test["attributes"], "seed"), &json!({ "intValue": "-3" }));
+
+        assert_eq!(operation["name"], "S3.PutObject");
+        assert_eq!(operation["kind"], 3);
+        assert_eq!(operation["traceId"], test["traceId"]);
+        assert_eq!(operation["parentSpanId"], test["spanId"]);
+        assert_eq!(attribute_of(&operation["attributes"], "ok"), &json!({ "boolValue": true }));
+        assert_eq!(attribute_of(&operation["attributes"], "ratio"), &json!({ "doubleValue": 0.5 }));
+
+        let events = operation["events"].as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["name"], "sent the request");
+        assert_eq!(attribute_of(&events[0]["attributes"], "level"), &json!({ "stringValue": "INFO" }));
+        assert_eq!(attribute_of(&events[0]["attributes"], "status"), &json!({ "intValue": "200" }));
+        assert_eq!(attribute_of(&events[0]["attributes"], "key"), &json!({ "stringValue": "\"a b\"" }));
+    }
+
+    #[test]
+    fn separate_tests_get_separate_traces() {
+        let (spans, _) = spans_of(|| {
+            info_span!("test").in_scope(|| {});
+            info_span!("test").in_scope(|| {});
+            None
+        });
+        assert_eq!(spans.len(), 2);
+        assert_ne!(spans[0]["traceId"], spans[1]["traceId"]);
+    }
+
+    /// The `traceparent` a request carries names the span it is sent from.
+    #[test]
+    fn trace_context_names_the_current_span() {
+        let (spans, current) = spans_of(|| info_span!("try_attempt").in_scope(current_ids));
+        let (trace_id, span_id) = current.unwrap();
+        assert_eq!(spans[0]["traceId"], format!("{:032x}", trace_id));
+        assert_eq!(spans[0]["spanId"], format!("{:016x}", span_id));
+    }
+}
@@ -0,0 +1,138 @@
+//! Deterministic test payloads.
+//!
+//! Every object body is a pseudo-random byte stream derived from the run's seed and a label,
+//! usually the object key. Nothing needs to be kept around to verify a download: the expected
+//! bytes are generated again and compared, and `--seed` replays a failing run byte for byte.
+//! Payloads too large to hold in memory are generated and checked chunk by chunk.
+
+use bytes::Bytes;
+
+/// Size of the pieces `chunks` generates; a multiple of the generator's 8-byte output.
+const CHUNK: usize = 1024 * 1024;
+
+/// A body of `len` bytes that can be regenerated from `seed` and `label` at any time.
+#[derive(Debug, Clone)]
+pub struct Payload {
+    seed: u64,
+    len: usize,
+}
+
+impl Payload {
+    pub fn new(run_seed: u64, label: &str, len: usize) -> Self {
+        Payload { seed: run_seed ^ fnv1a(label.as_bytes()), len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn bytes(&self) -> Bytes {
+        let mut data = Vec::with_capacity(self.len + 8);
+        let mut rng = SplitMix64(self.seed);
+        while data.len() < self.len {
+            data.extend_from_slice(&rng.next().to_le_bytes());
+        }
+        data.truncate(self.len);
+        Bytes::from(data)
+    }
+
+    /// The payload in pieces of at most 1 MiB, generated as they are asked for.
+    pub fn chunks(&self) -> Chunks {
+        Chunks { rng: SplitMix64(self.seed), remaining: self.len }
+    }
+
+    /// Checks a downloaded body against the payload, naming the first differing offset.
+    pub fn verify(&self, actual: &[u8]) -> Result<(), String> {
+        let mut verifier = self.verifier();
+        verifier.update(actual)?;
+        verifier.finish()
+    }
+
+    /// Like `verify`, for a body that arrives in pieces.
+    pub fn verifier(&self) -> Verifier {
+        Verifier { len: self.len, rng: SplitMix64(self.seed), pending: Vec::new(), received: 0 }
+    }
+}
+
+pub struct Chunks {
+    rng: SplitMix64,
+    remaining: usize,
+}
+
+impl Iterator for Chunks {
+    type Item = Bytes;
+
+    fn next(&mut self) -> Option<Bytes> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let len = self.remaining.min(CHUNK);
+        let mut data = Vec::with_capacity(len + 8);
+        while data.len() < len {
+            data.extend_from_slice(&self.rng.next().to_le_bytes());
+        }
+        data.truncate(len);
+        self.remaining -= len;
+        Some(Bytes::from(data))
+    }
+}
+
+/// Compares a body with its payload piece by piece.
+pub struct Verifier {
+    len: usize,
+    rng: SplitMix64,
+    /// Generated bytes not yet compared, at most one word's worth between pieces.
+    pending: Vec<u8>,
+    received: usize,
+}
+
+impl Verifier {
+    pub fn update(&mut self, piece: &[u8]) -> Result<(), String> {
+        let start = self.received;
+        self.received += piece.len();
+        // Bytes past the end of the payload only matter for the length check in `finish`.
+        let piece = &piece[..piece.len().min(self.len.saturating_sub(start))];
+        while self.pending.len() < piece.len() {
+            self.pending.extend_from_slice(&self.rng.next().to_le_bytes());
+        }
+        if let Some(i) = self.pending.iter().zip(piece).position(|(e, a)| e != a) {
+            return Err(format!(
+                "body differs from the payload at byte {} of {}: expected {:#04x}, got {:#04x}",
+                start + i,
+                self.len,
+                self.pending[i],
+                piece[i]
+            ));
+        }
+        self.pending.drain(..piece.len());
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        if self.received != self.len {
+            return Err(format!("body has {} bytes, the payload {}", self.received, self.len));
+        }
+        Ok(())
+    }
+}
+
+/// SplitMix64, spelled out here so payloads stay the same whatever `rand` version is in use.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3))
+}
@@ -0,0 +1,125 @@
+//! `s3test put --stdin` and `s3test get --stdout`: pushing piped data through the server and
+//! back, to see how it copes with bodies of unknown length streamed in and out, e.g.
+//! `head -c 2G /dev/urandom | tee in | s3test put --stdin b/k && s3test get b/k --stdout | cmp in`.
+//!
+//! Only one part of the input is held at a time, so data much larger than memory goes through.
+//! What is said about the transfer goes to the log, on stderr, leaving stdout to the data.
+
+use std::io;
+
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use aws_sdk_s3::Client;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tracing::{info, warn};
+
+use crate::cli::{GetArgs, PutArgs};
+use crate::guard::BoxError;
+use crate::repl::object_path;
+
+/// Smallest part S3 accepts, except for the last one.
+const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Reads up to `size` bytes, fewer only at the end of the input.
+async fn read_part(input: &mut (impl AsyncRead + Unpin), size: u64) -> Result<Vec<u8>, BoxError> {
+    let mut part = Vec::with_capacity(size as usize);
+    input.take(size).read_to_end(&mut part).await.map_err(|e| format!("cannot read stdin: {}", e))?;
+    Ok(part)
+}
+
+/// Uploads stdin with a single PutObject when it fits in one part, and as a multipart upload
+/// otherwise, since its length is not known up front.
+pub async fn put(client: &Client, args: &PutArgs) -> Result<(), BoxError> {
+    let (bucket, key) = object_path(&args.object)?;
+    if args.part_size < MIN_PART_SIZE {
+        return Err(format!("--part-size must be at least 5 MiB, got {} bytes", args.part_size).into());
+    }
+    let mut stdin = tokio::io::stdin();
+    let first = read_part(&mut stdin, args.part_size).await?;
+    if (first.len() as u64) < args.part_size {
+        let bytes = first.len();
+        let body = ByteStream::from(first);
+        let resp = client.put_object().bucket(bucket).key(key).body(body).send().await?;
+        info!(bucket, key, bytes, etag = resp.e_tag().unwrap_or_default(), "uploaded stdin");
+        return Ok(());
+    }
+
+    let upload = client.create_multipart_upload().bucket(bucket).key(key).send().await?;
+    let upload_id = upload.upload_id().ok_or("CreateMultipartUpload returned no upload ID")?;
+    let result = put_parts(client, bucket, key, upload_id, first, &mut stdin, args.part_size).await;
+    if result.is_err() {
+        let abort = client.abort_multipart_upload().bucket(bucket).key(key).upload_id(upload_id);
+        if let Err(e) = abort.send().await {
+            warn!(upload_id, error = %e, "could not abort the multipart upload");
+        }
+    }
+    result
+}
+
+async fn put_parts(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    first: Vec<u8>,
+    stdin: &mut tokio::io::Stdin,
+    part_size: u64,
+) -> Result<(), BoxError> {
+    let mut parts = Vec::new();
+    let mut bytes = 0;
+    let mut part = first;
+    while !part.is_empty() {
+        let number = parts.len() as i32 + 1;
+        let length = part.len();
+        let resp = client
+            .upload_part()
+            .bucket(bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .part_number(number)
+            .body(ByteStream::from(part))
+            .send()
+            .await?;
+        bytes += length;
+        info!(part = number, bytes = length, "uploaded part");
+        parts.push(CompletedPart::builder().part_number(number).set_e_tag(resp.e_tag).build());
+        part = read_part(stdin, part_size).await?;
+    }
+    let count = parts.len();
+    let resp = client
+        .complete_multipart_upload()
+        .bucket(bucket)
+        .key(key)
+        .upload_id(upload_id)
+        .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
+        .send()
+        .await?;
+    info!(bucket, key, bytes, parts = count, etag = resp.e_tag().unwrap_or_default(), "uploaded stdin");
+    Ok(())
+}
+
+/// Streams the object to stdout as it arrives, and fails if it ends short of or beyond the
+/// Content-Length it was announced with.
+pub async fn get(client: &Client, args: &GetArgs) -> Result<(), BoxError> {
+    let (bucket, key) = object_path(&args.object)?;
+    let mut resp = client.get_object().bucket(bucket).key(key).send().await?;
+    let mut stdout = tokio::io::stdout();
+    let mut written = 0u64;
+    while let Some(chunk) = resp.body.try_next().await? {
+        match stdout.write_all(&chunk).await {
+            Ok(()) => written += chunk.len() as u64,
+            // Whatever reads the output has seen enough, as with `| head`.
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                info!(bucket, key, bytes = written, "stdout was closed, download stopped");
+                return Ok(());
+            }
+            Err(e) => return Err(format!("cannot write to stdout: {}", e).into()),
+        }
+    }
+    stdout.flush().await.map_err(|e| format!("cannot write to stdout: {}", e))?;
+    if let Some(length) = resp.content_length().filter(|&l| l as u64 != written) {
+        return Err(format!("received {} bytes, Content-Length announced {}", written, length).into());
+    }
+    info!(bucket, key, bytes = written, etag = resp.e_tag().unwrap_or_default(), "downloaded to stdout");
+    Ok(())
+}
@@ -0,0 +1,138 @@
+//! How the SDK reuses connections (`--max-connections-per-host`, `--connection-idle-timeout`,
+//! `--connection-close`), to see how the PHP server and the FPM or Apache front end before it
+//! cope with many requests over a few long-lived connections, or with a new connection for
+//! every request.
+//!
+//! Hyper's pool bounds idle connections, not open ones, so the cap on connections holds back
+//! requests instead: one permit per request, kept until its response body has been read, which
+//! over HTTP/1.1 is one connection each. Every client talks to a single endpoint, so the cap
+//! is per host. Connections the harness opens itself ([`crate::transport`]) are never reused,
+//! which leaves only the cap and `Connection: close` to apply to them.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use aws_sdk_s3::config::interceptors::BeforeTransmitInterceptorContextMut;
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents, SharedHttpClient};
+use aws_sdk_s3::primitives::SdkBody;
+use aws_smithy_runtime_api::client::http::{
+    HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpConnector,
+};
+use aws_smithy_runtime_api::client::orchestrator::HttpRequest;
+use aws_smithy_runtime_api::client::result::ConnectorError;
+use bytes::Bytes;
+use http_body::{Body, Frame, SizeHint};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::cli::ConnectionArgs;
+
+/// Settings for the idle connections of the SDK's pool; None keeps hyper's default.
+pub struct Idle {
+    pub timeout: Option<Option<Duration>>,
+    pub max_per_host: Option<usize>,
+}
+
+impl Idle {
+    pub fn from_args(args: &ConnectionArgs) -> Self {
+        Idle {
+            timeout: args.connection_idle_timeout.map(|secs| Some(Duration::from_secs(secs))),
+            max_per_host: match args.connection_close {
+                true => Some(0),
+                false => args.max_connections_per_host.map(|max| max as usize),
+            },
+        }
+    }
+}
+
+/// [`HttpClient`] letting at most a given number of requests be in flight at once.
+#[derive(Debug)]
+pub struct ConnectionLimit {
+    inner: SharedHttpClient,
+    permits: Arc<Semaphore>,
+}
+
+impl ConnectionLimit {
+    pub fn new(inner: SharedHttpClient, max: u32) -> Self {
+        ConnectionLimit { inner, permits: Arc::new(Semaphore::new(max as usize)) }
+    }
+}
+
+impl HttpClient for ConnectionLimit {
+    fn http_connector(
+        &self,
+        settings: &HttpConnectorSettings,
+        components: &RuntimeComponents,
+    ) -> SharedHttpConnector {
+        SharedHttpConnector::new(LimitedConnector {
+            inner: self.inner.http_connector(settings, components),
+            permits: self.permits.clone(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct LimitedConnector {
+    inner: SharedHttpConnector,
+    permits: Arc<Semaphore>,
+}
+
+impl HttpConnector for LimitedConnector {
+    fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+        let (inner, permits) = (self.inner.clone(), self.permits.clone());
+        HttpConnectorFuture::new(async move {
+            let permit = permits.acquire_owned().await.map_err(|e| ConnectorError::other(e.into(), None))?;
+            let mut response = inner.call(request).await?;
+            let body = response.take_body();
+            *response.body_mut() = SdkBody::from_body_1_x(Held { body, _permit: permit });
+            Ok(response)
+        })
+    }
+}
+
+/// A response body holding on to its request's permit until it is dropped.
+struct Held {
+    body: SdkBody,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Body for Held {
+    type Data = Bytes;
+    type Error = aws_smithy_runtime_api::box_error::BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        Pin::new(&mut self.body).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        Body::size_hint(&self.body)
+    }
+}
+
+/// Asks the server to close the connection after every response (`--connection-close`).
+#[derive(Debug)]
+pub struct CloseConnections;
+
+impl Intercept for CloseConnections {
+    fn name(&self) -> &'static str {
+        "CloseConnections"
+    }
+
+    fn modify_before_transmit(
+        &self,
+        context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), aws_smithy_runtime_api::box_error::BoxError> {
+        context.request_mut().headers_mut().insert("connection", "close");
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+//! Compatibility profiles (`--profile`): what a given kind of server is held to. A profile
+//! lists the known deviations of that kind of server, tests it is known to fail and why; their
+//! failures are reported but, like those of `--allow-fail`, do not fail the run.
+//!
+//! `aws-strict` has none and is the default. The others are for checking a server against its
+//! own known limits, so that CI catches regressions without being red about what was never
+//! implemented.
+
+use clap::ValueEnum;
+
+use crate::scenarios::Selector;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Profile {
+    /// Everything AWS S3 does is asserted
+    AwsStrict,
+    /// MinIO's known deviations are tolerated
+    Minio,
+    /// This repository's PHP server's known deviations are tolerated
+    PhpS3Server,
+}
+
+/// Tests a server is known to fail, from a profile or an xfail file ([`crate::xfail`]).
+pub struct Deviation {
+    pub selector: Selector,
+    pub reason: String,
+}
+
+impl Profile {
+    pub fn deviations(self) -> Vec<Deviation> {
+        let known: &[(&str, &str)] = match self {
+            Profile::AwsStrict => &[],
+            Profile::Minio => &[
+                ("test=http2::probe", "MinIO only speaks HTTP/2 over TLS, not h2c"),
+                (
+                    "test=auth::session_token",
+                    "MinIO checks session tokens, so the made-up one used without --session-token is refused",
+                ),
+            ],
+            Profile::PhpS3Server => &[
+                ("test=faults::dropped_part", "no multipart uploads: the server answers POST with 405"),
+                (
+                    "test=multipart::concurrent_parts",
+                    "no multipart uploads: the server answers POST with 405",
+                ),
+                (
+                    "test=presigned::post_policy",
+                    "no browser-based uploads: the server answers POST with 405",
+                ),
+                ("test=http2::probe", "PHP's built-in web server only speaks HTTP/1.x"),
+                ("suite=sts", "no STS endpoint"),
+                (
+                    "test=auth::signing_region",
+                    "the server creates a bucket whatever its LocationConstraint; it verifies signatures \
+                     for us-east-1 alone, refusing the other regions with 403 SignatureDoesNotMatch",
+                ),
+            ],
+        };
+        known
+            .iter()
+            .map(|(selector, reason)| Deviation {
+                selector: selector.parse().expect("profile selectors are valid"),
+                reason: reason.to_string(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,202 @@
+//! Opt-in progress bars (`--progress`) for transfers and long-running loops.
+//!
+//! All bars hang off one process-wide [`MultiProgress`] that stays hidden unless progress was
+//! enabled and stderr is a terminal. Log output is routed through [`LogWriter`] so that events
+//! are printed above the bars instead of tearing them.
+
+use std::io::{self, IsTerminal, Write};
+use std::pin::Pin;
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+
+use aws_sdk_s3::primitives::{ByteStream, SdkBody};
+use bytes::Bytes;
+use http_body::{Body, Frame, SizeHint};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use crate::dashboard;
+use crate::guard::BoxError;
+use crate::memory;
+use crate::payload::{Chunks, Payload};
+
+/// Transfers smaller than this finish too quickly for a bar to be of any use.
+const MIN_TRANSFER: u64 = 1024 * 1024;
+/// Size of the pieces an upload body is handed to the HTTP client in.
+const UPLOAD_CHUNK: usize = 64 * 1024;
+/// Payloads above this size are generated while they are uploaded rather than up front.
+const IN_MEMORY_LIMIT: usize = 256 * 1024 * 1024;
+/// How often a download checks the harness's memory against `--max-rss`.
+const MEMORY_CHECK_EVERY: u64 = 64 * 1024 * 1024;
+
+fn multi() -> &'static MultiProgress {
+    static MULTI: OnceLock<MultiProgress> = OnceLock::new();
+    MULTI.get_or_init(|| MultiProgress::with_draw_target(ProgressDrawTarget::hidden()))
+}
+
+/// Shows bars from now on, if stderr is a terminal.
+pub fn enable() {
+    if io::stderr().is_terminal() {
+        multi().set_draw_target(ProgressDrawTarget::stderr());
+    }
+}
+
+/// A byte-counting bar for a transfer of `total` bytes, hidden for small transfers.
+pub fn transfer(label: &str, total: u64) -> ProgressBar {
+    if total < MIN_TRANSFER {
+        return ProgressBar::hidden();
+    }
+    let style = ProgressStyle::with_template(
+        "{msg:30!} [{bar:30}] {bytes}/{total_bytes} {binary_bytes_per_sec} eta {eta}",
+    )
+    .unwrap()
+    .progress_chars("=> ");
+    multi().add(ProgressBar::new(total).with_style(style).with_message(label.to_string()))
+}
+
+/// An upload body that advances `bar` as the HTTP client reads it. Retries start the bar over.
+///
+/// A streamed body makes the SDK switch to aws-chunked encoding, so that only happens when the
+/// bar is actually drawn; otherwise the request goes out exactly as without `--progress`.
+pub fn upload(data: Bytes, bar: &ProgressBar) -> ByteStream {
+    if bar.is_hidden() {
+        return ByteStream::from(data);
+    }
+    let bar = bar.clone();
+    ByteStream::new(SdkBody::retryable(move || {
+        bar.set_position(0);
+        SdkBody::from_body_1_x(ProgressBody { data: data.clone(), bar: bar.clone() })
+    }))
+}
+
+/// Like `upload`, for a generated payload. One too large to hold in memory is generated while
+/// the HTTP client reads it, which means aws-chunked encoding whether or not a bar is drawn.
+pub fn upload_payload(payload: &Payload, bar: &ProgressBar) -> ByteStream {
+    if payload.len() <= IN_MEMORY_LIMIT {
+        return upload(payload.bytes(), bar);
+    }
+    let (payload, bar) = (payload.clone(), bar.clone());
+    ByteStream::new(SdkBody::retryable(move || {
+        bar.set_position(0);
+        SdkBody::from_body_1_x(PayloadBody {
+            chunks: payload.chunks(),
+            remaining: payload.len() as u64,
+            bar: bar.clone(),
+        })
+    }))
+}
+
+/// Reads a download to the end, advancing `bar` with every chunk and checking it against
+/// `payload` as it arrives, so that large objects never have to fit in memory; the memory
+/// that takes is checked against `--max-rss` along the way.
+pub async fn download_verified(
+    mut body: ByteStream,
+    bar: &ProgressBar,
+    payload: &Payload,
+) -> Result<(), BoxError> {
+    let mut verifier = payload.verifier();
+    let (mut received, mut next_check) = (0, MEMORY_CHECK_EVERY);
+    while let Some(chunk) = body.try_next().await? {
+        bar.inc(chunk.len() as u64);
+        verifier.update(&chunk)?;
+        received += chunk.len() as u64;
+        if received >= next_check {
+            memory::check(&format!("after {} MiB of a download", received / (1024 * 1024)))?;
+            next_check += MEMORY_CHECK_EVERY;
+        }
+    }
+    memory::check("at the end of a download")?;
+    Ok(verifier.finish()?)
+}
+
+/// [`download_verified`] without a bar, for downloads that are one request among many.
+pub async fn verify_download(body: ByteStream, payload: &Payload) -> Result<(), BoxError> {
+    download_verified(body, &ProgressBar::hidden(), payload).await
+}
+
+/// Reads a download to the end without keeping it, returning its length.
+pub async fn discard_download(mut body: ByteStream) -> Result<u64, BoxError> {
+    let mut received = 0;
+    while let Some(chunk) = body.try_next().await? {
+        received += chunk.len() as u64;
+    }
+    Ok(received)
+}
+
+struct ProgressBody {
+    data: Bytes,
+    bar: ProgressBar,
+}
+
+impl Body for ProgressBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, BoxError>>> {
+        if self.data.is_empty() {
+            return Poll::Ready(None);
+        }
+        let len = self.data.len().min(UPLOAD_CHUNK);
+        let chunk = self.data.split_to(len);
+        self.bar.inc(len as u64);
+        Poll::Ready(Some(Ok(Frame::data(chunk))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(self.data.len() as u64)
+    }
+}
+
+struct PayloadBody {
+    chunks: Chunks,
+    remaining: u64,
+    bar: ProgressBar,
+}
+
+impl Body for PayloadBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, BoxError>>> {
+        let Some(chunk) = self.chunks.next() else {
+            return Poll::Ready(None);
+        };
+        self.remaining -= chunk.len() as u64;
+        self.bar.inc(chunk.len() as u64);
+        Poll::Ready(Some(Ok(Frame::data(chunk))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == 0
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(self.remaining)
+    }
+}
+
+/// Writes log output to stderr with the bars cleared for the duration of the write, or to the
+/// dashboard while one is up.
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if dashboard::capture(buf) {
+            return Ok(buf.len());
+        }
+        multi().suspend(|| io::stderr().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
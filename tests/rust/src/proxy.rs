@@ -0,0 +1,225 @@
+//! Outbound proxies (`--proxy`), for servers only reachable through a corporate proxy or a
+//! tunnel.
+//!
+//! HTTP proxies are handed to the SDK's own connector. SOCKS5 proxies are not supported there,
+//! so SDK requests then go through the harness's own transport, which opens a tunnel through
+//! either kind of proxy with [`connect`].
+
+use std::fmt;
+use std::str::FromStr;
+
+use aws_smithy_http_client::proxy::ProxyConfig;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::guard::BoxError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyKind {
+    Http,
+    /// `remote_dns` is set for socks5h://, where the proxy resolves the target's name.
+    Socks5 { remote_dns: bool },
+}
+
+#[derive(Clone)]
+pub struct Proxy {
+    pub kind: ProxyKind,
+    /// `host:port` of the proxy itself.
+    pub addr: String,
+    credentials: Option<(String, String)>,
+}
+
+/// Leaves the password out of debug output.
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Proxy")
+            .field("kind", &self.kind)
+            .field("addr", &self.addr)
+            .field("user", &self.credentials.as_ref().map(|(user, _)| user))
+            .finish()
+    }
+}
+
+impl FromStr for Proxy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (scheme, rest) = s.split_once("://").ok_or_else(|| format!("proxy '{}' has no scheme", s))?;
+        let (kind, default_port) = match scheme.to_ascii_lowercase().as_str() {
+            "http" => (ProxyKind::Http, 80),
+            "socks5" => (ProxyKind::Socks5 { remote_dns: false }, 1080),
+            "socks5h" => (ProxyKind::Socks5 { remote_dns: true }, 1080),
+            other => {
+                return Err(format!("unsupported proxy scheme '{}', expected http, socks5 or socks5h", other))
+            }
+        };
+        let rest = rest.trim_end_matches('/');
+        let (credentials, host) = match rest.rsplit_once('@') {
+            Some((userinfo, host)) => {
+                let (user, password) = userinfo.split_once(':').unwrap_or((userinfo, ""));
+                (Some((user.to_string(), password.to_string())), host)
+            }
+            None => (None, rest),
+        };
+        if host.is_empty() {
+            return Err(format!("proxy '{}' has no host", s));
+        }
+        let addr = match host.rsplit_once(':') {
+            Some((_, port)) if port.parse::<u16>().is_ok() => host.to_string(),
+            _ => format!("{}:{}", host, default_port),
+        };
+        let proxy = Proxy { kind, addr, credentials };
+        if proxy.kind == ProxyKind::Http {
+            ProxyConfig::all(proxy.url()).map_err(|e| e.to_string())?;
+        }
+        Ok(proxy)
+    }
+}
+
+impl Proxy {
+    fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// The configuration for the SDK's connector; only meaningful for HTTP proxies.
+    pub fn smithy_config(&self) -> ProxyConfig {
+        let config = ProxyConfig::all(self.url()).expect("proxy URL was validated when parsed");
+        match &self.credentials {
+            Some((user, password)) => config.with_basic_auth(user, password),
+            None => config,
+        }
+    }
+}
+
+/// A TCP connection to `target` (`host:port`), tunnelled through `proxy` if there is one.
+pub async fn connect(proxy: Option<&Proxy>, target: &str) -> Result<TcpStream, BoxError> {
+    let Some(proxy) = proxy else {
+        return Ok(TcpStream::connect(target).await?);
+    };
+    let mut stream = TcpStream::connect(&proxy.addr)
+        .await
+        .map_err(|e| format!("cannot reach proxy {}: {}", proxy.addr, e))?;
+    match proxy.kind {
+        ProxyKind::Http => http_connect(&mut stream, proxy, target).await?,
+        ProxyKind::Socks5 { remote_dns } => socks5_connect(&mut stream, proxy, target, remote_dns).await?,
+    }
+    Ok(stream)
+}
+
+/// Opens a tunnel with `CONNECT`, as HTTP proxies do for HTTPS.
+async fn http_connect(stream: &mut TcpStream, proxy: &Proxy, target: &str) -> Result<(), BoxError> {
+    let mut request = format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n", target, target);
+    if let Some((user, password)) = &proxy.credentials {
+        let token = base64(format!("{}:{}", user, password).as_bytes());
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", token));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    // Read the response head byte by byte, so nothing past it is taken from the tunnel.
+    let mut reader = BufReader::with_capacity(1, stream);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line).await?;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line == "\r\n" {
+            break;
+        }
+    }
+    match status_line.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(format!("proxy {} refused CONNECT {}: {}", proxy.addr, target, status_line.trim()).into()),
+    }
+}
+
+/// The SOCKS5 handshake of RFC 1928, with username/password authentication from RFC 1929.
+async fn socks5_connect(
+    stream: &mut TcpStream,
+    proxy: &Proxy,
+    target: &str,
+    remote_dns: bool,
+) -> Result<(), BoxError> {
+    let method = if proxy.credentials.is_some() { 0x02 } else { 0x00 };
+    stream.write_all(&[0x05, 0x01, method]).await?;
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply != [0x05, method] {
+        return Err(format!("SOCKS proxy {} does not accept our authentication method", proxy.addr).into());
+    }
+    if let Some((user, password)) = &proxy.credentials {
+        let mut auth = vec![0x01, user.len() as u8];
+        auth.extend_from_slice(user.as_bytes());
+        auth.push(password.len() as u8);
+        auth.extend_from_slice(password.as_bytes());
+        stream.write_all(&auth).await?;
+        stream.read_exact(&mut reply).await?;
+        if reply[1] != 0x00 {
+            return Err(format!("SOCKS proxy {} rejected the credentials", proxy.addr).into());
+        }
+    }
+
+    let (host, port) = target.rsplit_once(':').ok_or_else(|| format!("target {} has no port", target))?;
+    let port: u16 = port.parse()?;
+    let mut request = vec![0x05, 0x01, 0x00];
+    // An IP literal, possibly a bracketed IPv6 one, is sent as an address even with socks5h.
+    let literal = host.trim_start_matches('[').trim_end_matches(']').parse::<std::net::IpAddr>().ok();
+    if remote_dns && literal.is_none() {
+        request.push(0x03);
+        request.push(host.len() as u8);
+        request.extend_from_slice(host.as_bytes());
+    } else {
+        let ip = match literal {
+            Some(ip) => ip,
+            None => tokio::net::lookup_host(target)
+                .await?
+                .next()
+                .ok_or_else(|| format!("cannot resolve {}", host))?
+                .ip(),
+        };
+        match ip {
+            std::net::IpAddr::V4(ip) => {
+                request.push(0x01);
+                request.extend_from_slice(&ip.octets());
+            }
+            std::net::IpAddr::V6(ip) => {
+                request.push(0x04);
+                request.extend_from_slice(&ip.octets());
+            }
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut head = [0; 4];
+    stream.read_exact(&mut head).await?;
+    if head[1] != 0x00 {
+        let reply = head[1];
+        return Err(format!("SOCKS proxy {} could not reach {} (reply {})", proxy.addr, target, reply).into());
+    }
+    // Skip the bound address the proxy reports.
+    let address_len = match head[3] {
+        0x01 => 4,
+        0x04 => 16,
+        _ => stream.read_u8().await? as usize,
+    };
+    let mut bound = vec![0; address_len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
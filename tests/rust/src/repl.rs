@@ -0,0 +1,188 @@
+//! `s3test repl`: ad-hoc S3 commands against the server, with the harness's connection,
+//! TLS, proxy and retry settings, for poking at the PHP server without writing a script.
+//!
+//! Lines are split like a shell would, so keys with spaces can be quoted. A failing command
+//! prints the error and the prompt comes back.
+
+use std::error::Error;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::guard::BoxError;
+
+const HELP: &str = "\
+commands:
+  ls                              list buckets
+  ls BUCKET[/PREFIX]              list objects
+  mb BUCKET                       create a bucket
+  rb BUCKET                       delete an empty bucket
+  put FILE BUCKET/KEY             upload a local file
+  get BUCKET/KEY [FILE]           download an object, to stdout if no file is given
+  head BUCKET/KEY                 show an object's metadata
+  rm BUCKET/KEY                   delete an object
+  presign get|put BUCKET/KEY [SECONDS]
+                                  print a presigned URL, valid for an hour by default
+  help                            show this list
+  quit                            leave (so does end of input)";
+
+pub async fn run(client: &Client) -> Result<(), BoxError> {
+    let interactive = io::stdin().is_terminal();
+    if interactive {
+        println!("type 'help' for the list of commands");
+    }
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        if interactive {
+            print!("s3> ");
+            io::stdout().flush()?;
+        }
+        let Some(line) = lines.next_line().await? else {
+            return Ok(());
+        };
+        let Some(words) = shlex::split(&line) else {
+            println!("error: unbalanced quotes");
+            continue;
+        };
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        match words.as_slice() {
+            [] => {}
+            ["quit" | "exit"] => return Ok(()),
+            ["help"] => println!("{}", HELP),
+            words => {
+                if let Err(e) = execute(client, words).await {
+                    println!("error: {}", describe(e.as_ref()));
+                }
+            }
+        }
+    }
+}
+
+async fn execute(client: &Client, words: &[&str]) -> Result<(), BoxError> {
+    match words {
+        ["ls"] => {
+            let resp = client.list_buckets().send().await?;
+            for bucket in resp.buckets() {
+                println!("{}", bucket.name().unwrap_or_default());
+            }
+        }
+        ["ls", path] => {
+            let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+            let mut pages = client
+                .list_objects_v2()
+                .bucket(bucket)
+                .prefix(prefix)
+                .into_paginator()
+                .send();
+            while let Some(page) = pages.next().await {
+                for object in page?.contents() {
+                    let modified = object.last_modified().map(|t| t.to_string()).unwrap_or_default();
+                    let size = object.size().unwrap_or(0);
+                    println!("{:>12}  {:<24}  {}", size, modified, object.key().unwrap_or_default());
+                }
+            }
+        }
+        ["mb", bucket] => {
+            client.create_bucket().bucket(*bucket).send().await?;
+        }
+        ["rb", bucket] => {
+            client.delete_bucket().bucket(*bucket).send().await?;
+        }
+        ["put", file, path] => {
+            let (bucket, key) = object_path(path)?;
+            let body = ByteStream::from_path(Path::new(file))
+                .await
+                .map_err(|e| format!("cannot read {}: {}", file, e))?;
+            let resp = client.put_object().bucket(bucket).key(key).body(body).send().await?;
+            println!("etag {}", resp.e_tag().unwrap_or_default());
+        }
+        ["get", path, rest @ ..] if rest.len() <= 1 => {
+            let (bucket, key) = object_path(path)?;
+            let mut resp = client.get_object().bucket(bucket).key(key).send().await?;
+            match rest.first() {
+                // Written as it arrives, so that objects larger than memory can be saved.
+                Some(file) => {
+                    let cannot_write = |e: io::Error| format!("cannot write {}: {}", file, e);
+                    let mut out = tokio::fs::File::create(file).await.map_err(cannot_write)?;
+                    let mut written = 0;
+                    while let Some(chunk) = resp.body.try_next().await? {
+                        out.write_all(&chunk).await.map_err(cannot_write)?;
+                        written += chunk.len();
+                    }
+                    out.flush().await.map_err(cannot_write)?;
+                    println!("{} bytes written to {}", written, file);
+                }
+                None => {
+                    let data = resp.body.collect().await?.into_bytes();
+                    match std::str::from_utf8(&data) {
+                        Ok(text) if text.ends_with('\n') => print!("{}", text),
+                        Ok(text) => println!("{}", text),
+                        Err(_) => {
+                            println!("{} bytes of binary data; give a file name to save them", data.len())
+                        }
+                    }
+                }
+            }
+        }
+        ["head", path] => {
+            let (bucket, key) = object_path(path)?;
+            let resp = client.head_object().bucket(bucket).key(key).send().await?;
+            println!("size          {}", resp.content_length().unwrap_or(0));
+            println!("content-type  {}", resp.content_type().unwrap_or_default());
+            println!("etag          {}", resp.e_tag().unwrap_or_default());
+            if let Some(modified) = resp.last_modified() {
+                println!("last-modified {}", modified);
+            }
+            for (name, value) in resp.metadata().into_iter().flatten() {
+                println!("x-amz-meta-{}: {}", name, value);
+            }
+        }
+        ["rm", path] => {
+            let (bucket, key) = object_path(path)?;
+            client.delete_object().bucket(bucket).key(key).send().await?;
+        }
+        ["presign", method, path, rest @ ..] if rest.len() <= 1 => {
+            let (bucket, key) = object_path(path)?;
+            let seconds = match rest.first() {
+                Some(seconds) => seconds.parse().map_err(|_| format!("invalid lifetime '{}'", seconds))?,
+                None => 3600,
+            };
+            let config = PresigningConfig::expires_in(Duration::from_secs(seconds))?;
+            let request = match *method {
+                "get" => client.get_object().bucket(bucket).key(key).presigned(config).await?,
+                "put" => client.put_object().bucket(bucket).key(key).presigned(config).await?,
+                other => return Err(format!("cannot presign '{}', only get and put", other).into()),
+            };
+            println!("{}", request.uri());
+        }
+        _ => {
+            let line = words.join(" ");
+            return Err(format!("unknown command or wrong arguments '{}', try 'help'", line).into());
+        }
+    }
+    Ok(())
+}
+
+/// The error and its sources, which is where SDK errors keep the server's error code.
+fn describe(error: &(dyn Error + 'static)) -> String {
+    let mut description = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        description = format!("{}: {}", description, cause);
+        source = cause.source();
+    }
+    description
+}
+
+/// Splits `BUCKET/KEY`.
+pub fn object_path(path: &str) -> Result<(&str, &str), BoxError> {
+    match path.split_once('/') {
+        Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => Ok((bucket, key)),
+        _ => Err(format!("expected BUCKET/KEY, got '{}'", path).into()),
+    }
+}
@@ -0,0 +1,62 @@
+//! SDK retry policy as configured from the command line.
+
+use std::time::Duration;
+
+use aws_sdk_s3::config::interceptors::BeforeTransmitInterceptorContextMut;
+use aws_sdk_s3::config::retry::RetryConfig;
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_smithy_runtime_api::box_error::BoxError;
+use aws_smithy_runtime_api::client::orchestrator::Metadata;
+
+use crate::cli::RetryArgs;
+
+pub fn retry_config(args: &RetryArgs) -> RetryConfig {
+    if args.no_retry {
+        return RetryConfig::disabled();
+    }
+    RetryConfig::standard()
+        .with_max_attempts(args.retry_attempts)
+        .with_initial_backoff(Duration::from_millis(args.retry_initial_backoff_ms))
+        .with_max_backoff(Duration::from_millis(args.retry_max_backoff_ms))
+}
+
+/// Disables retries for every operation not listed in `--retry-ops`.
+#[derive(Debug)]
+pub struct RetryableOperations {
+    operations: Vec<String>,
+}
+
+impl RetryableOperations {
+    /// Returns `None` when every operation may be retried.
+    pub fn from_args(args: &RetryArgs) -> Option<Self> {
+        if args.no_retry || args.retry_ops.is_empty() {
+            return None;
+        }
+        Some(RetryableOperations {
+            operations: args.retry_ops.clone(),
+        })
+    }
+}
+
+impl Intercept for RetryableOperations {
+    fn name(&self) -> &'static str {
+        "RetryableOperations"
+    }
+
+    fn modify_before_retry_loop(
+        &self,
+        _context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let operation = cfg.load::<Metadata>().map(Metadata::name).unwrap_or_default();
+        if !self
+            .operations
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(operation))
+        {
+            cfg.interceptor_state().store_put(RetryConfig::disabled());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,597 @@
+//! Executes scenarios one by one and reports a verdict for each.
+
+use std::any::Any;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use aws_sdk_s3::config::{Credentials, Region};
+use aws_sdk_s3::Client;
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::capture::{Exchange, HttpCapture};
+use crate::cassette::{Recorder, Tape, TapeClient};
+use crate::cli::{ConnectionArgs, RetryArgs, RunArgs, StsArgs};
+use crate::client::{build_client, credentials};
+use crate::diff;
+use crate::guard::BoxError;
+use crate::integrity::{Integrity, Mismatch};
+use crate::latency::{self, OpTimer, OpTiming};
+use crate::naming::RunId;
+use crate::payload::Payload;
+use crate::profile::Deviation;
+use crate::rawhttp::RawClient;
+use crate::scenarios::Selector;
+use crate::snapshot::Snapshots;
+use crate::state::StateFile;
+use crate::triage::Trace;
+use crate::version::{self, Version};
+use crate::xfail::{self, Xfail};
+
+/// Everything a scenario needs to talk to the server under test.
+#[derive(Clone)]
+pub struct TestContext {
+    pub client: Client,
+    pub raw: RawClient,
+    pub run_id: RunId,
+    /// Seed all payloads of the run are derived from.
+    pub seed: u64,
+    /// Sizes of the objects tests that cover a range of sizes create (`--object-sizes`).
+    pub object_sizes: Arc<[u64]>,
+    /// Concurrent readers of the consistency checks (`--consistency-readers`).
+    pub readers: usize,
+    /// UploadPart requests in flight at once in the multipart tests (`--part-workers`).
+    pub part_workers: usize,
+    /// Objects the scalability tests upload (`--scale-objects`).
+    pub scale_objects: usize,
+    /// Objects the mixed-size scalability test uploads (`--scale-mixed-objects`).
+    pub mixed_objects: usize,
+    /// Set with `--sts`, for the STS suite.
+    pub sts: Option<Arc<StsTarget>>,
+    /// A second user's credentials (`--other-access-key`), which must not reach the run's buckets.
+    pub other_user: Option<Credentials>,
+    /// More tenants (`--tenant`), who must keep their buckets to themselves like `other_user`.
+    pub tenants: Arc<[Credentials]>,
+    /// Regions requests are signed for to check that the server refuses them (`--signing-regions`).
+    pub signing_regions: Arc<[String]>,
+}
+
+/// Where the STS suite assumes which role, and for how long.
+#[derive(Debug)]
+pub struct StsTarget {
+    pub endpoint: String,
+    pub role_arn: String,
+    pub duration: u32,
+    /// The run's own credentials, which AssumeRole is signed with.
+    pub credentials: Credentials,
+}
+
+impl TestContext {
+    pub async fn connect(
+        connection: &ConnectionArgs,
+        retry: &RetryArgs,
+        run_id: RunId,
+        seed: u64,
+    ) -> Result<Self, BoxError> {
+        Ok(TestContext {
+            client: build_client(connection, retry).await?,
+            raw: RawClient::new(connection)?,
+            run_id,
+            seed,
+            object_sizes: Arc::from([]),
+            readers: 1,
+            part_workers: 1,
+            scale_objects: 0,
+            mixed_objects: 0,
+            sts: None,
+            other_user: None,
+            tenants: Arc::from([]),
+            signing_regions: Arc::from([]),
+        })
+    }
+
+    /// A copy of the context with the STS settings, if `--sts` was given; the STS endpoint
+    /// defaults to the S3 one.
+    pub fn with_sts(self, args: &StsArgs, connection: &ConnectionArgs) -> TestContext {
+        let sts = args.sts.then(|| StsTarget {
+            endpoint: args.sts_endpoint.clone().unwrap_or_else(|| connection.endpoint.clone()),
+            role_arn: args.sts_role_arn.clone(),
+            duration: args.sts_duration,
+            credentials: credentials(connection),
+        });
+        TestContext { sts: sts.map(Arc::new), ..self }
+    }
+
+    /// A client like the run's that signs with `credentials` instead.
+    pub fn client_as(&self, credentials: &Credentials) -> Client {
+        let config = self.client.config().to_builder().credentials_provider(credentials.clone());
+        Client::from_conf(config.build())
+    }
+
+    /// A client like the run's that signs for `region` instead.
+    pub fn client_in(&self, region: &str) -> Client {
+        let config = self.client.config().to_builder().region(Region::new(region.to_string()));
+        Client::from_conf(config.build())
+    }
+
+    /// The run's payload for `label`, typically an object key.
+    pub fn payload(&self, label: &str, len: usize) -> Payload {
+        Payload::new(self.seed, label, len)
+    }
+
+    /// A copy of the context whose clients record to or replay from `tape`.
+    fn with_tape(&self, tape: &Tape) -> TestContext {
+        let http_client = TapeClient::new(self.client.config().http_client(), tape.clone());
+        let config = self.client.config().to_builder().http_client(http_client);
+        TestContext {
+            client: Client::from_conf(config.build()),
+            raw: self.raw.with_tape(tape),
+            ..self.clone()
+        }
+    }
+
+    /// A copy of the context whose client also reports to `capture`.
+    fn with_capture(&self, capture: &HttpCapture) -> TestContext {
+        let config = self.client.config().to_builder().interceptor(capture.clone());
+        TestContext {
+            client: Client::from_conf(config.build()),
+            ..self.clone()
+        }
+    }
+
+    /// A copy of the context with the settings of `args` scenarios read.
+    fn with_run_args(&self, args: &RunArgs) -> TestContext {
+        TestContext {
+            object_sizes: args.object_sizes.as_slice().into(),
+            readers: args.consistency_readers as usize,
+            part_workers: args.part_workers as usize,
+            scale_objects: args.scale_objects,
+            mixed_objects: args.scale_mixed_objects,
+            other_user: match (&args.other_access_key, &args.other_secret_key) {
+                (Some(key), Some(secret)) => Some(Credentials::new(key, secret, None, None, "other-user")),
+                _ => None,
+            },
+            tenants: args
+                .tenants
+                .iter()
+                .map(|(key, secret)| Credentials::new(key, secret, None, None, "tenant"))
+                .collect(),
+            signing_regions: args.signing_regions.as_slice().into(),
+            ..self.clone()
+        }
+    }
+
+    /// A copy of the context whose client hashes what it uploads and downloads with `integrity`.
+    fn with_integrity(&self, integrity: &Integrity) -> TestContext {
+        let config = self.client.config().to_builder().interceptor(integrity.clone());
+        TestContext {
+            client: Client::from_conf(config.build()),
+            ..self.clone()
+        }
+    }
+
+    /// A copy of the context whose client times every operation with `timer`.
+    fn with_timer(&self, timer: &OpTimer) -> TestContext {
+        let config = self.client.config().to_builder().interceptor(timer.clone());
+        TestContext {
+            client: Client::from_conf(config.build()),
+            ..self.clone()
+        }
+    }
+}
+
+pub type TestFuture = Pin<Box<dyn Future<Output = Result<(), BoxError>> + Send>>;
+
+#[derive(Clone)]
+pub struct Scenario {
+    pub name: &'static str,
+    /// Labels for `--allow-fail` and `--require`, such as `core`.
+    pub tags: &'static [&'static str],
+    /// S3 APIs or features the scenario exercises, for the conformance matrix.
+    pub features: &'static [&'static str],
+    /// The S3 calls the scenario makes, in order, as shown by `--dry-run`.
+    pub calls: &'static [&'static str],
+    /// The first release of the PHP server that ships what the scenario covers, such as `1.2`;
+    /// against an older one it is skipped.
+    pub min_version: Option<&'static str>,
+    pub run: fn(TestContext) -> TestFuture,
+}
+
+#[derive(Debug)]
+pub enum Verdict {
+    Passed,
+    Failed(String),
+    TimedOut(Duration),
+    /// Not run, because the server lacks what the test needs.
+    Skipped(String),
+}
+
+impl Verdict {
+    /// Whether the test ran and did not pass.
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Verdict::Failed(_) | Verdict::TimedOut(_))
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Passed => f.write_str("passed"),
+            Verdict::Failed(reason) => write!(f, "FAILED: {}", reason),
+            Verdict::TimedOut(limit) => write!(f, "TIMED OUT after {}s", limit.as_secs()),
+            Verdict::Skipped(reason) => write!(f, "skipped: {}", reason),
+        }
+    }
+}
+
+pub struct TestResult {
+    pub name: &'static str,
+    pub tags: &'static [&'static str],
+    pub features: &'static [&'static str],
+    pub verdict: Verdict,
+    pub duration: Duration,
+    /// HTTP traffic of the test, kept only when it did not pass.
+    pub exchanges: Vec<Exchange>,
+    /// Log output of the test at debug level, kept only when it did not pass.
+    pub trace: String,
+    /// Outcome of the same test against the reference endpoint, if one was given.
+    pub reference_verdict: Option<Verdict>,
+    /// Behavioral differences from the reference endpoint.
+    pub divergences: Vec<String>,
+    /// Set when the test was skipped because it passed in the run being resumed.
+    pub resumed: bool,
+    /// Duration of every S3 operation the test made.
+    pub operations: Vec<OpTiming>,
+    /// Downloads whose content differed from what the test had uploaded.
+    pub integrity: Vec<Mismatch>,
+}
+
+/// The release of the server `ctx` reaches: `--server-version`, or else the one the server
+/// names, asked for only when one of `scenarios` or an entry of `gate`'s xfail file depends on it.
+pub async fn server_version(
+    ctx: &TestContext,
+    scenarios: &[Scenario],
+    args: &RunArgs,
+    gate: Option<&Gate<'_>>,
+) -> Option<Version> {
+    if let Some(version) = &args.server_version {
+        return Some(version.clone());
+    }
+    let versioned = gate.is_some_and(|gate| gate.xfails.iter().any(Xfail::is_versioned));
+    if !versioned && scenarios.iter().all(|s| s.min_version.is_none()) {
+        return None;
+    }
+    version::probe(ctx).await
+}
+
+/// Runs each scenario, and with a reference context runs it a second time against the
+/// reference and compares the HTTP traffic of both runs. With a state file, tests that passed
+/// before are skipped and every verdict is saved as soon as it is known. Scenarios that need
+/// a later release than `server` are skipped too.
+pub async fn run_all(
+    ctx: &TestContext,
+    reference: Option<&TestContext>,
+    scenarios: &[Scenario],
+    args: &RunArgs,
+    server: Option<&Version>,
+    mut cassette: Option<&mut Recorder>,
+    mut state: Option<&mut StateFile>,
+) -> Vec<TestResult> {
+    let timeout = Duration::from_secs(args.timeout);
+    let snapshots = Snapshots::new(args.snapshot_dir.clone(), args.snapshots, ctx.run_id);
+    // Snapshots and comparisons must see complete bodies.
+    let body_limit = if snapshots.enabled() || reference.is_some() {
+        usize::MAX
+    } else {
+        args.capture_body_limit
+    };
+    let ctx = &ctx.with_run_args(args);
+    let reference = reference.map(|r| r.with_run_args(args));
+    let reference = reference.as_ref();
+    let mut results = Vec::new();
+    for scenario in scenarios {
+        if let Some(reason) = version::unmet(scenario, server) {
+            info!(test = scenario.name, %reason, "skipping");
+            results.push(TestResult {
+                name: scenario.name,
+                tags: scenario.tags,
+                features: scenario.features,
+                verdict: Verdict::Skipped(reason),
+                duration: Duration::ZERO,
+                exchanges: Vec::new(),
+                trace: String::new(),
+                reference_verdict: None,
+                divergences: Vec::new(),
+                resumed: false,
+                operations: Vec::new(),
+                integrity: Vec::new(),
+            });
+            continue;
+        }
+        if let Some(duration) = state.as_deref().and_then(|s| s.passed(scenario.name)) {
+            info!(test = scenario.name, "skipping, passed in the run being resumed");
+            results.push(TestResult {
+                name: scenario.name,
+                tags: scenario.tags,
+                features: scenario.features,
+                verdict: Verdict::Passed,
+                duration,
+                exchanges: Vec::new(),
+                trace: String::new(),
+                reference_verdict: None,
+                divergences: Vec::new(),
+                resumed: true,
+                operations: Vec::new(),
+                integrity: Vec::new(),
+            });
+            continue;
+        }
+        let span = info_span!("test", id = scenario.name);
+        span.in_scope(|| info!("starting"));
+        let started = Instant::now();
+        let capture = HttpCapture::new(body_limit);
+        let timer = OpTimer::default();
+        let integrity = Integrity::default();
+        let trace = Trace::default();
+        let tape = cassette.as_deref().map(|c| c.tape(scenario.name));
+        let test_ctx = match &tape {
+            Some(tape) => ctx.with_tape(tape),
+            None => ctx.clone(),
+        };
+        let test_ctx = test_ctx.with_capture(&capture).with_timer(&timer).with_integrity(&integrity);
+        let mut verdict = execute(scenario, test_ctx, timeout, &span, &trace).await;
+        let mut exchanges = capture.take();
+        let operations = timer.take();
+        let mismatches = integrity.take();
+        for mismatch in &mismatches {
+            span.in_scope(|| warn!(%mismatch, "integrity mismatch"));
+        }
+        if let (Verdict::Passed, Some(first)) = (&verdict, mismatches.first()) {
+            verdict = Verdict::Failed(format!("integrity: {}", first));
+        }
+        if let (Some(cassette), Some(tape)) = (cassette.as_deref_mut(), &tape) {
+            cassette.store(scenario.name, tape);
+        }
+
+        let mut reference_verdict = None;
+        let mut divergences = Vec::new();
+        if let Some(reference) = reference {
+            let reference_capture = HttpCapture::new(body_limit);
+            let reference_span = info_span!("reference", id = scenario.name);
+            let outcome = execute(
+                scenario,
+                reference.with_capture(&reference_capture),
+                timeout,
+                &reference_span,
+                &Trace::default(),
+            )
+            .await;
+            let run_id = ctx.run_id.to_string();
+            divergences = diff::compare(&exchanges, &reference_capture.take(), &run_id);
+            for divergence in &divergences {
+                span.in_scope(|| warn!(%divergence, "differs from reference"));
+            }
+            reference_verdict = Some(outcome);
+        }
+
+        if let Verdict::Passed = verdict {
+            if let Err(mismatch) = snapshots.verify(scenario.name, &exchanges) {
+                verdict = Verdict::Failed(mismatch);
+            }
+        }
+        span.in_scope(|| match verdict {
+            Verdict::Passed => info!(%verdict, "finished"),
+            _ => warn!(%verdict, "finished"),
+        });
+        let mut trace = trace.take();
+        if let Verdict::Passed = verdict {
+            exchanges.clear();
+            trace.clear();
+        }
+        let duration = started.elapsed();
+        if let Some(state) = state.as_deref_mut() {
+            if let Err(e) = state.record(scenario.name, &verdict, duration) {
+                warn!(error = %e, "could not save the run's progress");
+            }
+        }
+        results.push(TestResult {
+            name: scenario.name,
+            tags: scenario.tags,
+            features: scenario.features,
+            verdict,
+            duration,
+            exchanges,
+            trace,
+            reference_verdict,
+            divergences,
+            resumed: false,
+            operations,
+            integrity: mismatches,
+        });
+    }
+    results
+}
+
+/// Runs one scenario in its own task so that a panic or a stalled request only fails that
+/// scenario. On timeout the scenario future is dropped, which lets its guards tear down.
+async fn execute(
+    scenario: &Scenario,
+    ctx: TestContext,
+    timeout: Duration,
+    span: &tracing::Span,
+    trace: &Trace,
+) -> Verdict {
+    let test = tokio::time::timeout(timeout, (scenario.run)(ctx));
+    let trace = trace.clone();
+    match tokio::spawn(async move { trace.record(test).await }.instrument(span.clone())).await {
+        Ok(Ok(Ok(()))) => Verdict::Passed,
+        Ok(Ok(Err(e))) => Verdict::Failed(format!("{:?}", e)),
+        Ok(Err(_)) => Verdict::TimedOut(timeout),
+        Err(e) if e.is_panic() => Verdict::Failed(panic_message(e.into_panic())),
+        Err(e) => Verdict::Failed(e.to_string()),
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload");
+    format!("panicked: {}", message)
+}
+
+/// Lists every scenario with the calls it would make, for `--dry-run`.
+pub fn print_plan(scenarios: &[Scenario]) {
+    for scenario in scenarios {
+        println!("{}", scenario.name);
+        for call in scenario.calls {
+            println!("    {}", call);
+        }
+    }
+    println!("\n{} scenario(s), nothing was sent", scenarios.len());
+}
+
+/// Prints one line per scenario with its tags, the labels `--allow-fail` and `--require`
+/// select on.
+pub fn print_list(scenarios: &[Scenario]) {
+    for scenario in scenarios {
+        println!("{:<40} {}", scenario.name, scenario.tags.join(", "));
+    }
+    println!("\n{} scenario(s)", scenarios.len());
+}
+
+/// Which failures fail the run: with `--require`, only those of the required tests, and never
+/// those of tests matching `--allow-fail`, a known deviation of the `--profile` or an entry of
+/// the `--xfail` file.
+pub struct Gate<'a> {
+    require: &'a [Selector],
+    allow_fail: &'a [Selector],
+    deviations: Vec<Deviation>,
+    xfails: Vec<Xfail>,
+    /// The release of the server, which decides the xfail entries for some releases only.
+    server: Option<Version>,
+}
+
+impl<'a> Gate<'a> {
+    /// Rejects selectors that match none of `scenarios`, which would most likely be a typo that
+    /// quietly changes what CI checks. A profile's deviations only apply to the tests being run.
+    pub fn new(args: &'a RunArgs, scenarios: &[Scenario]) -> Result<Self, BoxError> {
+        for selector in args.require.iter().chain(&args.allow_fail) {
+            if !scenarios.iter().any(|s| selector.matches(s.name, s.tags)) {
+                return Err(format!("{} matches none of the scenarios being run", selector).into());
+            }
+        }
+        let xfails = match &args.xfail {
+            Some(path) => xfail::load(path)?,
+            None => Vec::new(),
+        };
+        Ok(Gate {
+            require: &args.require,
+            allow_fail: &args.allow_fail,
+            deviations: args.profile.deviations(),
+            xfails,
+            server: args.server_version.clone(),
+        })
+    }
+
+    /// Applies the xfail entries for `version` of the server, once the run knows which it is.
+    pub fn set_server_version(&mut self, version: Option<Version>) {
+        self.server = version;
+    }
+
+    /// Whether a failure of `result` fails the run.
+    pub fn counts(&self, result: &TestResult) -> bool {
+        let matching = |selectors: &[Selector]| selectors.iter().any(|s| s.matches(result.name, result.tags));
+        (self.require.is_empty() || matching(self.require))
+            && !matching(self.allow_fail)
+            && self.deviation(result).is_none()
+    }
+
+    /// Why the profile or the xfail file expects `result` to fail, if they do.
+    pub fn deviation(&self, result: &TestResult) -> Option<&str> {
+        let xfails = self.xfails.iter().filter(|x| x.applies(self.server.as_ref()));
+        let mut deviations = self.deviations.iter().chain(xfails.map(|x| &x.deviation));
+        let deviation = deviations.find(|d| d.selector.matches(result.name, result.tags))?;
+        Some(&deviation.reason)
+    }
+}
+
+/// Prints the HTTP capture of every failed test, any differences from the reference and any
+/// integrity mismatches, then one line per scenario. Returns an error if any of them did not
+/// pass, unless `gate` tolerates its failure.
+pub fn summarize(results: &[TestResult], gate: &Gate) -> Result<(), BoxError> {
+    for result in results.iter().filter(|r| !r.exchanges.is_empty()) {
+        println!("\n=== HTTP exchanges of {} ({})", result.name, result.verdict);
+        for (i, exchange) in result.exchanges.iter().enumerate() {
+            println!("\n--- #{} {}", i + 1, exchange);
+        }
+    }
+
+    for result in results.iter().filter(|r| !r.divergences.is_empty()) {
+        println!("\n=== {} differs from the reference", result.name);
+        for divergence in &result.divergences {
+            println!("- {}", divergence);
+        }
+    }
+
+    for result in results.iter().filter(|r| !r.integrity.is_empty()) {
+        println!("\n=== integrity mismatches of {}", result.name);
+        for mismatch in &result.integrity {
+            println!("- {}", mismatch);
+        }
+    }
+
+    latency::print_breakdown(results);
+
+    println!();
+    for result in results {
+        let reference = match &result.reference_verdict {
+            Some(verdict) => format!("  (reference {}, {} difference(s))", verdict, result.divergences.len()),
+            None if result.resumed => "  (in an earlier run)".to_string(),
+            None => String::new(),
+        };
+        let passed = matches!(result.verdict, Verdict::Passed);
+        let failed = result.verdict.is_failure();
+        let tolerated = match gate.deviation(result) {
+            Some(reason) if failed => format!("  (expected to fail: {})", reason),
+            Some(_) if passed => "  (XPASS: expected to fail)".to_string(),
+            None if failed && !gate.counts(result) => "  (allowed to fail)".to_string(),
+            _ => String::new(),
+        };
+        println!(
+            "{:<40} {:>8.2}s  {}{}{}",
+            result.name,
+            result.duration.as_secs_f64(),
+            result.verdict,
+            reference,
+            tolerated
+        );
+    }
+
+    let failed: Vec<_> = results.iter().filter(|r| r.verdict.is_failure()).collect();
+    let gating = failed.iter().filter(|r| gate.counts(r)).count();
+    let passed = results.iter().filter(|r| matches!(r.verdict, Verdict::Passed)).count();
+    print!("\n{} passed, {} failed", passed, failed.len());
+    match results.len() - passed - failed.len() {
+        0 => {}
+        skipped => print!(", {} skipped", skipped),
+    }
+    match failed.len() - gating {
+        0 => println!(),
+        tolerated => println!(" ({} allowed to fail)", tolerated),
+    }
+    let xpassed = results
+        .iter()
+        .filter(|r| matches!(r.verdict, Verdict::Passed) && gate.deviation(r).is_some())
+        .count();
+    if xpassed > 0 {
+        println!("{} test(s) expected to fail passed; drop them from the xfail file or profile", xpassed);
+    }
+    if gating > 0 {
+        return Err(format!("{} test(s) failed", gating).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+//! HTTP caching of objects: conditional GETs on the ETag, which browsers and CDNs in front of
+//! the server rely on to revalidate what they hold.
+
+use http::Method;
+
+use crate::guard::{BoxError, BucketGuard};
+use crate::payload::Payload;
+use crate::rawhttp::{uri_encode, RawResponse};
+use crate::runner::TestContext;
+
+async fn put(ctx: &TestContext, path: &str, payload: &Payload) -> Result<(), BoxError> {
+    let resp = ctx.raw.request(Method::PUT, path).body(payload.bytes()).send().await?;
+    if resp.status != 200 {
+        return Err(format!("PUT {} answered {} {:?}", path, resp.status, resp.error_code()).into());
+    }
+    Ok(())
+}
+
+/// A GET of `path` answered 200 with `payload`; returns the ETag it came with.
+fn full_response(what: &str, resp: &RawResponse, payload: &Payload) -> Result<String, BoxError> {
+    if resp.status != 200 {
+        return Err(format!("{} answered {} {:?}", what, resp.status, resp.error_code()).into());
+    }
+    payload.verify(&resp.body).map_err(|e| format!("{}: {}", what, e))?;
+    let etag = resp.header("etag").ok_or_else(|| format!("{} returned no ETag", what))?;
+    Ok(etag.to_string())
+}
+
+/// A GET replayed with `If-None-Match` set to the ETag it returned is answered 304 with no
+/// body; once the object is overwritten the same request gets the new object and ETag.
+pub async fn if_none_match(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("caching")).await?;
+    let key = "cached.bin";
+    let path = format!("/{}/{}", bucket.name(), uri_encode(key, false));
+    let original = ctx.payload(key, 4096);
+    put(&ctx, &path, &original).await?;
+
+    let resp = ctx.raw.request(Method::GET, &path).send().await?;
+    let etag = full_response("GET", &resp, &original)?;
+
+    let resp = ctx.raw.request(Method::GET, &path).header("if-none-match", &etag).send().await?;
+    if resp.status != 304 {
+        let code = resp.error_code();
+        let message = format!("GET with If-None-Match: {} answered {} {:?}, not 304", etag, resp.status, code);
+        return Err(message.into());
+    }
+    if !resp.body.is_empty() {
+        return Err(format!("the 304 to If-None-Match came with a {} byte body", resp.body.len()).into());
+    }
+    if resp.header("etag").is_some_and(|e| e != etag) {
+        return Err(format!("the 304 to If-None-Match: {} has ETag {:?}", etag, resp.header("etag")).into());
+    }
+
+    let replacement = ctx.payload("cached.bin v2", 4096 + 1);
+    put(&ctx, &path, &replacement).await?;
+    let resp = ctx.raw.request(Method::GET, &path).header("if-none-match", &etag).send().await?;
+    let what = "GET with the old ETag in If-None-Match after an overwrite";
+    let new_etag = full_response(what, &resp, &replacement)?;
+    if new_etag == etag {
+        return Err(format!("the overwritten object kept its ETag {}", etag).into());
+    }
+
+    bucket.cleanup().await
+}
@@ -0,0 +1,225 @@
+//! Connection semantics, over connections the tests drive byte by byte: an HTTP/1.0 client
+//! that expects the connection to close after each response, an HTTP/1.1 client that asks for
+//! the same with `Connection: close`, and several requests pipelined on one persistent
+//! connection. Every response has to arrive whole and in order, and the connection has to be
+//! closed when, and only when, that was agreed on.
+//!
+//! Also a slow client, trickling an upload in the way slowloris does, which the server may wait
+//! out or give up on as long as no partial object becomes visible.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use aws_sdk_s3::primitives::ByteStream;
+use bytes::Bytes;
+use http::Method;
+use tracing::info;
+
+use crate::guard::{BoxError, BucketGuard};
+use crate::payload::Payload;
+use crate::rawhttp::uri_encode;
+use crate::runner::TestContext;
+use crate::wire::{Connection, WireResponse};
+
+/// How long the server gets to answer a request, or to close a connection it should close.
+const REPLY: Duration = Duration::from_secs(10);
+/// The slow upload sends [`TRICKLE`] bytes every [`TRICKLE_EVERY`], 8 bytes a second, and so
+/// takes 12 seconds for its [`SLOW_BODY`] bytes.
+const TRICKLE: usize = 4;
+const TRICKLE_EVERY: Duration = Duration::from_millis(500);
+const SLOW_BODY: usize = 96;
+
+/// Fails with `what` if `read` takes longer than [`REPLY`].
+async fn within<T>(what: &str, read: impl Future<Output = Result<T, BoxError>>) -> Result<T, BoxError> {
+    match tokio::time::timeout(REPLY, read).await {
+        Ok(result) => result.map_err(|e| format!("{}: {}", what, e).into()),
+        Err(_) => Err(format!("{}: nothing within {}s", what, REPLY.as_secs()).into()),
+    }
+}
+
+fn check_object(what: &str, resp: &WireResponse, payload: &Payload) -> Result<(), BoxError> {
+    if resp.response.status != 200 {
+        return Err(format!("{} answered {}", what, resp.response.status).into());
+    }
+    payload.verify(&resp.response.body).map_err(|e| format!("{}: {}", what, e))?;
+    Ok(())
+}
+
+async fn expect_close(conn: &mut Connection, what: &str) -> Result<(), BoxError> {
+    if !conn.closed_by_server(REPLY).await? {
+        return Err(format!("connection still open {}s after {}", REPLY.as_secs(), what).into());
+    }
+    Ok(())
+}
+
+fn is_chunked(resp: &WireResponse) -> bool {
+    resp.response.header("transfer-encoding").is_some_and(|v| v.to_ascii_lowercase().contains("chunked"))
+}
+
+fn path(bucket: &str, key: &str) -> String {
+    format!("/{}/{}", bucket, uri_encode(key, false))
+}
+
+/// The object's body as the SDK gets it, or None when there is no such object.
+async fn stored(ctx: &TestContext, bucket: &str, key: &str) -> Result<Option<Bytes>, BoxError> {
+    match ctx.client.get_object().bucket(bucket).key(key).send().await {
+        Ok(resp) => Ok(Some(resp.body.collect().await?.into_bytes())),
+        Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// PUT and GET over HTTP/1.0 without Keep-Alive: no chunked encoding, which HTTP/1.0 clients
+/// do not understand, and the connection closes after each response.
+pub async fn http_1_0(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("http10")).await?;
+    let key = "http-1.0.bin";
+    let payload = ctx.payload(key, 64 * 1024);
+
+    let put = ctx.raw.request(Method::PUT, &path(bucket.name(), key)).body(payload.bytes());
+    let mut conn = ctx.raw.connect().await?;
+    conn.send(&put.encode("HTTP/1.0")).await?;
+    let resp = within("HTTP/1.0 PUT", conn.read_response(&Method::PUT)).await?;
+    if resp.response.status != 200 {
+        return Err(format!("HTTP/1.0 PUT answered {}", resp.response.status).into());
+    }
+    expect_close(&mut conn, "the HTTP/1.0 PUT").await?;
+
+    let get = ctx.raw.request(Method::GET, &path(bucket.name(), key));
+    let mut conn = ctx.raw.connect().await?;
+    conn.send(&get.encode("HTTP/1.0")).await?;
+    let resp = within("HTTP/1.0 GET", conn.read_response(&Method::GET)).await?;
+    if is_chunked(&resp) {
+        return Err("the response to an HTTP/1.0 GET is chunked".into());
+    }
+    check_object("HTTP/1.0 GET", &resp, &payload)?;
+    // A close-delimited body has already seen the close.
+    if !resp.close_delimited {
+        expect_close(&mut conn, "the HTTP/1.0 GET").await?;
+    }
+    info!(version = %resp.version, "HTTP/1.0 round trip");
+
+    bucket.cleanup().await
+}
+
+/// An HTTP/1.1 GET with `Connection: close` gets its whole body and then the connection closes.
+pub async fn connection_close(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("connclose")).await?;
+    let key = "connection-close.bin";
+    let payload = ctx.payload(key, 256 * 1024);
+    let body = ByteStream::from(payload.bytes());
+    ctx.client.put_object().bucket(bucket.name()).key(key).body(body).send().await?;
+
+    let get = ctx.raw.request(Method::GET, &path(bucket.name(), key)).header("Connection", "close");
+    let mut conn = ctx.raw.connect().await?;
+    conn.send(&get.encode("HTTP/1.1")).await?;
+    let resp = within("GET with Connection: close", conn.read_response(&Method::GET)).await?;
+    check_object("GET with Connection: close", &resp, &payload)?;
+    if !resp.close_delimited {
+        expect_close(&mut conn, "a GET with Connection: close").await?;
+    }
+
+    bucket.cleanup().await
+}
+
+/// Four requests written at once on a persistent connection, a HEAD among them, answered in
+/// order with bodies that neither run short nor into the next response. A server may refuse
+/// to keep the connection open, but then it has to say so with `Connection: close` and close.
+pub async fn pipelined(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("pipeline")).await?;
+    let mut objects = Vec::new();
+    for (key, size) in [("empty.bin", 0), ("small.bin", 1024), ("large.bin", 256 * 1024)] {
+        let payload = ctx.payload(key, size);
+        let body = ByteStream::from(payload.bytes());
+        ctx.client.put_object().bucket(bucket.name()).key(key).body(body).send().await?;
+        objects.push((key, payload));
+    }
+
+    let plan = [
+        (Method::GET, &objects[2]),
+        (Method::HEAD, &objects[1]),
+        (Method::GET, &objects[0]),
+        (Method::GET, &objects[1]),
+    ];
+    let mut requests = Vec::new();
+    for (method, (key, _)) in &plan {
+        requests.extend(ctx.raw.request(method.clone(), &path(bucket.name(), key)).encode("HTTP/1.1"));
+    }
+    let mut conn = ctx.raw.connect().await?;
+    conn.send(&requests).await?;
+
+    for (i, (method, (key, payload))) in plan.iter().enumerate() {
+        let what = format!("response {} of {} ({} {})", i + 1, plan.len(), method, key);
+        let resp = within(&what, conn.read_response(method)).await?;
+        if *method == Method::HEAD {
+            let length = resp.response.header("content-length");
+            if resp.response.status != 200 || length != Some(&payload.len().to_string()) {
+                let status = resp.response.status;
+                return Err(format!("{}: {} with Content-Length {:?}", what, status, length).into());
+            }
+        } else {
+            check_object(&what, &resp, payload)?;
+        }
+        let closing = resp.response.header("connection").is_some_and(|v| v.eq_ignore_ascii_case("close"));
+        if closing && i + 1 < plan.len() {
+            expect_close(&mut conn, &format!("announcing Connection: close in {}", what)).await?;
+            info!(answered = i + 1, "server does not keep connections open; pipelining not exercised");
+            return bucket.cleanup().await;
+        }
+    }
+    info!(requests = plan.len(), "pipelined requests answered in order");
+
+    bucket.cleanup().await
+}
+
+/// A PUT whose body trickles in a few bytes at a time. The server may wait for all of it and
+/// store the object, or give up on the client with an error status or by closing the
+/// connection, but the object must not be visible halfway through, nor afterwards unless the
+/// server answered 200, and then it has to be complete.
+pub async fn slow_upload(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("slowput")).await?;
+    let key = "slow-upload.bin";
+    let payload = ctx.payload(key, SLOW_BODY);
+
+    let request = ctx.raw.request(Method::PUT, &path(bucket.name(), key)).body(payload.bytes());
+    let request = request.encode("HTTP/1.1");
+    let (head, body) = request.split_at(request.len() - SLOW_BODY);
+    let mut conn = ctx.raw.connect().await?;
+    conn.send(head).await?;
+    let started = Instant::now();
+    let mut sent = 0;
+    for chunk in body.chunks(TRICKLE) {
+        tokio::time::sleep(TRICKLE_EVERY).await;
+        if conn.send(chunk).await.is_err() {
+            // The server hung up on us.
+            break;
+        }
+        sent += chunk.len();
+        if sent == SLOW_BODY / 2 && stored(&ctx, bucket.name(), key).await?.is_some() {
+            return Err(format!("'{}' is visible with {} of its {} bytes sent", key, sent, SLOW_BODY).into());
+        }
+    }
+
+    // None when the server closed the connection without answering.
+    let status = match tokio::time::timeout(REPLY, conn.read_response(&Method::PUT)).await {
+        Ok(Ok(resp)) => Some(resp.response.status),
+        Ok(Err(_)) => None,
+        Err(_) => return Err(format!("slow PUT: no response within {}s", REPLY.as_secs()).into()),
+    };
+    let elapsed = started.elapsed().as_secs_f64();
+    match (status, stored(&ctx, bucket.name(), key).await?) {
+        (Some(200), Some(body)) => {
+            payload.verify(&body).map_err(|e| format!("'{}' after the slow PUT: {}", key, e))?;
+            info!(elapsed, "server waited for the whole slow upload");
+        }
+        (Some(200), None) => return Err("the slow PUT answered 200 but the object is missing".into()),
+        (status, Some(body)) => {
+            let outcome = status.map_or("closing the connection".to_string(), |s| format!("status {}", s));
+            let held = body.len();
+            return Err(format!("slow PUT ended with {} yet '{}' holds {} bytes", outcome, key, held).into());
+        }
+        (status, None) => info!(?status, sent, elapsed, "server gave up on the slow upload"),
+    }
+
+    bucket.cleanup().await
+}
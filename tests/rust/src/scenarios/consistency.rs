@@ -0,0 +1,122 @@
+//! Read-after-write consistency: S3 has been strongly consistent since December 2020, so every
+//! GET, HEAD and listing issued after a PutObject or DeleteObject returns has to see it. A
+//! server that caches metadata or writes through a queue shows stale objects here.
+//!
+//! Each check is made by `--consistency-readers` concurrent readers, over as many connections.
+
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use tokio::task::JoinSet;
+use tracing::{info, Instrument, Span};
+
+use crate::guard::{BoxError, BucketGuard};
+use crate::payload::Payload;
+use crate::progress;
+use crate::runner::TestContext;
+
+const ROUNDS: usize = 10;
+const OBJECT_SIZE: usize = 4096;
+
+/// What every read right after a write must see.
+#[derive(Clone)]
+enum Expected {
+    Object { payload: Payload, etag: String },
+    Absent,
+}
+
+/// Creates, overwrites and deletes keys, reading each one back with GET, HEAD and
+/// ListObjectsV2 as soon as every write returns.
+pub async fn read_after_write(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("consistency")).await?;
+
+    for round in 0..ROUNDS {
+        let key = format!("read-after-write-{:02}.bin", round);
+        for (version, write) in ["v1", "v2"].iter().zip(["PutObject", "PutObject overwrite"]) {
+            // Different lengths, so that a stale size shows as well as stale content.
+            let len = OBJECT_SIZE + round * 2 + version.len();
+            let payload = ctx.payload(&format!("{} {}", key, version), len);
+            let resp = client
+                .put_object()
+                .bucket(bucket.name())
+                .key(&key)
+                .body(ByteStream::from(payload.bytes()))
+                .send()
+                .await?;
+            let etag = resp.e_tag().ok_or_else(|| format!("{} of '{}' returned no ETag", write, key))?;
+            let expected = Expected::Object { payload, etag: etag.to_string() };
+            check(&ctx, bucket.name(), &key, &expected).await.map_err(|e| format!("after {}: {}", write, e))?;
+        }
+
+        client.delete_object().bucket(bucket.name()).key(&key).send().await?;
+        check(&ctx, bucket.name(), &key, &Expected::Absent)
+            .await
+            .map_err(|e| format!("after DeleteObject: {}", e))?;
+    }
+    info!(rounds = ROUNDS, readers = ctx.readers, "every read saw the last write");
+
+    bucket.cleanup().await
+}
+
+/// Reads `key` with every reader at once, failing with what the first stale reader saw.
+async fn check(ctx: &TestContext, bucket: &str, key: &str, expected: &Expected) -> Result<(), BoxError> {
+    let mut readers = JoinSet::new();
+    for _ in 0..ctx.readers {
+        let (client, bucket, key, expected) =
+            (ctx.client.clone(), bucket.to_string(), key.to_string(), expected.clone());
+        let reader = async move { read(&client, &bucket, &key, &expected).await };
+        readers.spawn(reader.instrument(Span::current()));
+    }
+    while let Some(reader) = readers.join_next().await {
+        reader??;
+    }
+    Ok(())
+}
+
+async fn read(client: &Client, bucket: &str, key: &str, expected: &Expected) -> Result<(), BoxError> {
+    let get = client.get_object().bucket(bucket).key(key).send().await;
+    let head = client.head_object().bucket(bucket).key(key).send().await;
+    let list = client.list_objects_v2().bucket(bucket).prefix(key).send().await?;
+    let listed = list.contents().iter().find(|o| o.key() == Some(key));
+
+    match expected {
+        Expected::Object { payload, etag } => {
+            let get = get.map_err(|e| format!("GetObject of '{}' failed: {}", key, e))?;
+            if get.e_tag() != Some(etag.as_str()) {
+                return Err(format!("GetObject of '{}' has ETag {:?}, not {}", key, get.e_tag(), etag).into());
+            }
+            let verified = progress::verify_download(get.body, payload).await;
+            verified.map_err(|e| format!("GetObject of '{}': {}", key, e))?;
+
+            let head = head.map_err(|e| format!("HeadObject of '{}' failed: {}", key, e))?;
+            if head.e_tag() != Some(etag.as_str()) || head.content_length() != Some(payload.len() as i64) {
+                let (found, length) = (head.e_tag(), head.content_length());
+                let message = format!("HeadObject of '{}' has ETag {:?}, length {:?}", key, found, length);
+                return Err(message.into());
+            }
+
+            let listed = listed.ok_or_else(|| format!("ListObjectsV2 does not list '{}'", key))?;
+            if listed.e_tag() != Some(etag.as_str()) || listed.size() != Some(payload.len() as i64) {
+                let (found, size) = (listed.e_tag(), listed.size());
+                let message = format!("ListObjectsV2 lists '{}' with ETag {:?}, size {:?}", key, found, size);
+                return Err(message.into());
+            }
+        }
+        Expected::Absent => {
+            match get {
+                Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => {}
+                Err(e) => return Err(e.into()),
+                Ok(_) => return Err(format!("GetObject still returns '{}'", key).into()),
+            }
+            match head {
+                Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => {}
+                Err(e) => return Err(e.into()),
+                Ok(_) => return Err(format!("HeadObject still finds '{}'", key).into()),
+            }
+            if listed.is_some() {
+                return Err(format!("ListObjectsV2 still lists '{}'", key).into());
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,57 @@
+//! Binary bodies and their media types.
+
+use aws_sdk_s3::primitives::ByteStream;
+use tracing::info;
+
+use crate::assert_bytes_eq_streamed;
+use crate::fixtures;
+use crate::guard::{BoxError, BucketGuard};
+use crate::runner::TestContext;
+
+/// Uploads a PNG and a JPEG with their media types and expects both the bytes and the
+/// Content-Type back unchanged from GetObject and HeadObject.
+pub async fn images(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("images")).await?;
+
+    let images = [
+        ("sample.png", "image/png", fixtures::png(64, 48, ctx.seed)),
+        ("sample.jpg", "image/jpeg", fixtures::jpeg(64, 48, ctx.seed)),
+    ];
+    for (key, content_type, data) in images {
+        client
+            .put_object()
+            .bucket(bucket.name())
+            .key(key)
+            .content_type(content_type)
+            .body(ByteStream::from(data.clone()))
+            .send()
+            .await?;
+        info!(key, content_type, bytes = data.len(), "uploaded image");
+
+        let resp = client.get_object().bucket(bucket.name()).key(key).send().await?;
+        let returned_type = resp.content_type().map(str::to_string);
+        assert_bytes_eq_streamed!(resp.body, data, "GetObject '{}'", key);
+        if returned_type.as_deref() != Some(content_type) {
+            return Err(format!(
+                "GetObject '{}': expected Content-Type {}, got {:?}",
+                key, content_type, returned_type
+            )
+            .into());
+        }
+
+        let head = client.head_object().bucket(bucket.name()).key(key).send().await?;
+        if head.content_type() != Some(content_type) {
+            return Err(format!(
+                "HeadObject '{}': expected Content-Type {}, got {:?}",
+                key,
+                content_type,
+                head.content_type()
+            )
+            .into());
+        }
+        info!(key, "verified image");
+    }
+
+    bucket.cleanup().await
+}
@@ -0,0 +1,67 @@
+//! CORS preflights: the unsigned OPTIONS requests browsers send before a cross-origin call.
+//! A bucket without a CORS configuration refuses them with an S3 error; a server with no
+//! OPTIONS handler tends to fall through to PHP's own error page instead.
+
+use aws_sdk_s3::primitives::ByteStream;
+use http::Method;
+
+use crate::guard::{BoxError, BucketGuard};
+use crate::rawhttp::{uri_encode, RawResponse};
+use crate::runner::TestContext;
+
+const ORIGIN: &str = "https://app.example.com";
+/// Prefix of the headers that let a browser make the cross-origin call.
+const GRANT: &str = "access-control-allow-";
+
+/// An S3 error answer with one of `statuses`, with no CORS grant and nothing that looks like a PHP error.
+fn expect_refused(what: &str, resp: &RawResponse, statuses: &[u16]) -> Result<(), BoxError> {
+    let body = String::from_utf8_lossy(&resp.body);
+    let content_type = resp.header("content-type").unwrap_or_default();
+    let php_error = body.contains("<b>Fatal error</b>") || body.contains("<br />");
+    if content_type.starts_with("text/html") || php_error {
+        return Err(format!("{}: answered with an HTML error page ({} {})", what, resp.status, body).into());
+    }
+    if !statuses.contains(&resp.status) {
+        let code = resp.error_code();
+        let message = format!("{}: expected {:?}, got {} {:?} ({})", what, statuses, resp.status, code, body);
+        return Err(message.into());
+    }
+    let granted = resp.headers.iter().find(|(n, _)| n.to_ascii_lowercase().starts_with(GRANT));
+    if let Some((name, value)) = granted {
+        return Err(format!("{}: a bucket without CORS answered {}: {}", what, name, value).into());
+    }
+    Ok(())
+}
+
+/// Preflights to a bucket and an object of a bucket without a CORS configuration are refused
+/// with 403; ones missing their Origin or Access-Control-Request-Method are malformed, and may
+/// be refused with 400 before the configuration is looked at.
+pub async fn preflight_without_cors(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("nocors")).await?;
+    let key = "cors/object.txt";
+    let body = ByteStream::from(ctx.payload(key, 64).bytes());
+    ctx.client.put_object().bucket(bucket.name()).key(key).body(body).send().await?;
+    let bucket_path = format!("/{}", bucket.name());
+    let object_path = format!("/{}/{}", bucket.name(), uri_encode(key, false));
+
+    for (path, method) in [(&object_path, "GET"), (&object_path, "PUT"), (&bucket_path, "GET")] {
+        let resp = ctx
+            .raw
+            .request(Method::OPTIONS, path)
+            .unsigned()
+            .header("origin", ORIGIN)
+            .header("access-control-request-method", method)
+            .send()
+            .await?;
+        expect_refused(&format!("preflight for {} {}", method, path), &resp, &[403])?;
+    }
+
+    let without_method = ctx.raw.request(Method::OPTIONS, &object_path).unsigned().header("origin", ORIGIN);
+    let resp = without_method.send().await?;
+    expect_refused("preflight without Access-Control-Request-Method", &resp, &[400, 403])?;
+    let without_origin = ctx.raw.request(Method::OPTIONS, &object_path).unsigned();
+    let resp = without_origin.header("access-control-request-method", "GET").send().await?;
+    expect_refused("preflight without Origin", &resp, &[400, 403])?;
+
+    bucket.cleanup().await
+}
@@ -0,0 +1,64 @@
+use tracing::{debug, info};
+
+use crate::guard::{BoxError, BucketGuard, ObjectGuard};
+use crate::progress;
+use crate::runner::TestContext;
+
+/// Uploads a generated payload of every size in `--object-sizes`, lists, downloads, verifies and
+/// deletes them again. Size-dependent bugs, in chunked reads or PHP memory limits, show up
+/// as the one size that fails.
+pub async fn round_trip(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = &ctx.run_id.bucket("crud");
+
+    // Create bucket
+    let bucket_guard = BucketGuard::create(client, bucket).await?;
+    info!(bucket = bucket_guard.name(), "created bucket");
+
+    // Upload one payload per size
+    let mut objects = Vec::new();
+    for &size in ctx.object_sizes.iter() {
+        let key = format!("payload-{}.bin", size);
+        let payload = ctx.payload(&key, size as usize);
+        let bar = progress::transfer(&format!("PUT {}", key), size);
+        let object = ObjectGuard::put(client, bucket, &key, progress::upload_payload(&payload, &bar)).await?;
+        bar.finish_and_clear();
+        info!(%key, bytes = payload.len(), "uploaded object");
+        objects.push((object, payload));
+    }
+
+    // List objects
+    let resp = client.list_objects_v2().bucket(bucket).send().await?;
+    let listed: Vec<&str> = resp.contents().iter().filter_map(|o| o.key()).collect();
+    for key in &listed {
+        debug!(key, "listed object");
+    }
+
+    // Download each object and compare it with its payload
+    for (object, payload) in &objects {
+        let key = object.key();
+        if !listed.contains(&key) {
+            return Err(format!("ListObjectsV2 is missing '{}'", key).into());
+        }
+        let resp = client.get_object().bucket(bucket).key(key).send().await?;
+        let bar = progress::transfer(&format!("GET {}", key), payload.len() as u64);
+        progress::download_verified(resp.body, &bar, payload)
+            .await
+            .map_err(|e| format!("GetObject '{}': {}", key, e))?;
+        bar.finish_and_clear();
+        info!(key, bytes = payload.len(), "downloaded and verified object");
+    }
+
+    // Delete all objects
+    for (object, _) in objects {
+        let key = object.key().to_string();
+        object.cleanup().await?;
+        info!(%key, "deleted object");
+    }
+
+    // Delete bucket
+    bucket_guard.cleanup().await?;
+    info!(%bucket, "deleted bucket");
+
+    Ok(())
+}
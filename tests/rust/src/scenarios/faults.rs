@@ -0,0 +1,259 @@
+//! Connections that break mid-transfer, through the fault-injecting proxy. Whatever the client
+//! saw, the server must end up consistent: an object is either all there or not there, and a
+//! failed overwrite leaves the previous version intact.
+
+use std::time::Duration;
+
+use aws_sdk_s3::config::retry::RetryConfig;
+use aws_sdk_s3::config::timeout::TimeoutConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use bytes::Bytes;
+use tracing::info;
+
+use crate::client::sdk_endpoint_url;
+use crate::faultproxy::{Fault, FaultProxy};
+use crate::guard::{BoxError, BucketGuard, MultipartGuard};
+use crate::payload::Payload;
+use crate::runner::TestContext;
+
+const OBJECT_SIZE: usize = 256 * 1024;
+/// Time the server gets to notice a dead connection before its state is checked.
+const SETTLE: Duration = Duration::from_millis(500);
+
+async fn start_proxy(ctx: &TestContext) -> Result<FaultProxy, BoxError> {
+    // The proxy cuts the stream at byte offsets, which means nothing inside a TLS session.
+    if ctx.raw.is_https() {
+        return Err("fault injection needs an http:// endpoint".into());
+    }
+    FaultProxy::start(&ctx.raw.host_port()).await
+}
+
+/// Overrides for a request sent through `proxy`: no retries, which would hide the fault, and
+/// optionally a deadline.
+fn through(
+    ctx: &TestContext,
+    proxy: &FaultProxy,
+    timeout: Option<Duration>,
+) -> aws_sdk_s3::config::Builder {
+    let mut config = aws_sdk_s3::config::Builder::default()
+        .endpoint_url(sdk_endpoint_url(&format!("{}{}", proxy.endpoint(), ctx.raw.base_path())))
+        .retry_config(RetryConfig::disabled());
+    if let Some(timeout) = timeout {
+        config = config.timeout_config(TimeoutConfig::builder().operation_timeout(timeout).build());
+    }
+    config
+}
+
+async fn put(client: &Client, bucket: &str, key: &str, payload: &Payload) -> Result<(), BoxError> {
+    let body = ByteStream::from(payload.bytes());
+    client.put_object().bucket(bucket).key(key).body(body).send().await?;
+    Ok(())
+}
+
+/// The object's body over the direct connection, or None when there is no such object.
+async fn fetch(client: &Client, bucket: &str, key: &str) -> Result<Option<Bytes>, BoxError> {
+    match client.get_object().bucket(bucket).key(key).send().await {
+        Ok(resp) => Ok(Some(resp.body.collect().await?.into_bytes())),
+        Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Whether ListObjectsV2 shows `key` in the bucket.
+async fn listed(client: &Client, bucket: &str, key: &str) -> Result<bool, BoxError> {
+    let resp = client.list_objects_v2().bucket(bucket).prefix(key).send().await?;
+    Ok(resp.contents().iter().any(|o| o.key() == Some(key)))
+}
+
+/// An object that must be either absent or complete.
+async fn expect_absent_or_complete(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    payload: &Payload,
+) -> Result<(), BoxError> {
+    if let Some(body) = fetch(client, bucket, key).await? {
+        payload.verify(&body).map_err(|e| format!("'{}' was left half-written: {}", key, e))?;
+    }
+    Ok(())
+}
+
+/// Uploads cut off after a quarter of the body: a new key must not appear, and an existing
+/// key must keep its previous content.
+pub async fn dropped_upload(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("dropput")).await?;
+    let proxy = start_proxy(&ctx).await?;
+    proxy.set(Fault::DropRequestAfter(OBJECT_SIZE / 4));
+
+    let new_key = "dropped-new.bin";
+    let payload = ctx.payload(new_key, OBJECT_SIZE);
+    let sent = client
+        .put_object()
+        .bucket(bucket.name())
+        .key(new_key)
+        .body(ByteStream::from(payload.bytes()))
+        .customize()
+        .config_override(through(&ctx, &proxy, None))
+        .send()
+        .await;
+    if sent.is_ok() {
+        return Err("PutObject succeeded although the proxy cut the upload off".into());
+    }
+    tokio::time::sleep(SETTLE).await;
+    if fetch(client, bucket.name(), new_key).await?.is_some() {
+        return Err(format!("the cut-off upload of '{}' became visible", new_key).into());
+    }
+    if listed(client, bucket.name(), new_key).await? {
+        return Err(format!("the cut-off upload of '{}' is listed", new_key).into());
+    }
+
+    let existing_key = "dropped-overwrite.bin";
+    let original = ctx.payload(existing_key, OBJECT_SIZE);
+    put(client, bucket.name(), existing_key, &original).await?;
+    let replacement = ctx.payload("dropped-overwrite.bin v2", OBJECT_SIZE);
+    let sent = client
+        .put_object()
+        .bucket(bucket.name())
+        .key(existing_key)
+        .body(ByteStream::from(replacement.bytes()))
+        .customize()
+        .config_override(through(&ctx, &proxy, None))
+        .send()
+        .await;
+    if sent.is_ok() {
+        return Err("PutObject succeeded although the proxy cut the upload off".into());
+    }
+    tokio::time::sleep(SETTLE).await;
+    match fetch(client, bucket.name(), existing_key).await? {
+        Some(body) => original
+            .verify(&body)
+            .map_err(|e| format!("a cut-off overwrite damaged '{}': {}", existing_key, e))?,
+        None => return Err(format!("a cut-off overwrite deleted '{}'", existing_key).into()),
+    }
+
+    bucket.cleanup().await
+}
+
+/// An UploadPart cut off after a quarter of the body: the part must not be listed for the
+/// upload, and nothing may appear under the upload's key.
+pub async fn dropped_part(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("droppart")).await?;
+    let key = "dropped-part.bin";
+    let upload = MultipartGuard::create(client, bucket.name(), key).await?;
+    let upload_id = upload.upload_id();
+
+    let proxy = start_proxy(&ctx).await?;
+    proxy.set(Fault::DropRequestAfter(OBJECT_SIZE / 4));
+    let part = ctx.payload("dropped-part.bin part 1", OBJECT_SIZE);
+    let sent = client
+        .upload_part()
+        .bucket(bucket.name())
+        .key(key)
+        .upload_id(upload_id)
+        .part_number(1)
+        .body(ByteStream::from(part.bytes()))
+        .customize()
+        .config_override(through(&ctx, &proxy, None))
+        .send()
+        .await;
+    if sent.is_ok() {
+        return Err("UploadPart succeeded although the proxy cut the upload off".into());
+    }
+    tokio::time::sleep(SETTLE).await;
+
+    let parts = client.list_parts().bucket(bucket.name()).key(key).upload_id(upload_id).send().await?;
+    if let Some(listed) = parts.parts().iter().find(|p| p.part_number() == Some(1)) {
+        return Err(format!("the cut-off part is listed with size {:?}", listed.size()).into());
+    }
+    if fetch(client, bucket.name(), key).await?.is_some() || listed(client, bucket.name(), key).await? {
+        return Err(format!("'{}' became visible after a cut-off UploadPart", key).into());
+    }
+
+    upload.cleanup().await?;
+    bucket.cleanup().await
+}
+
+/// A download truncated by the network must fail on the client and leave the object intact.
+pub async fn truncated_download(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("truncget")).await?;
+    let key = "truncated.bin";
+    let payload = ctx.payload(key, OBJECT_SIZE);
+    put(client, bucket.name(), key, &payload).await?;
+
+    let proxy = start_proxy(&ctx).await?;
+    proxy.set(Fault::TruncateResponseAfter(OBJECT_SIZE / 8));
+    let received: Result<Bytes, BoxError> = async {
+        let resp = client
+            .get_object()
+            .bucket(bucket.name())
+            .key(key)
+            .customize()
+            .config_override(through(&ctx, &proxy, None))
+            .send()
+            .await?;
+        Ok(resp.body.collect().await?.into_bytes())
+    }
+    .await;
+    if let Ok(body) = received {
+        return Err(format!(
+            "a GetObject truncated after {} bytes succeeded with {} bytes",
+            OBJECT_SIZE / 8,
+            body.len()
+        )
+        .into());
+    }
+
+    match fetch(client, bucket.name(), key).await? {
+        Some(body) => payload.verify(&body).map_err(|e| format!("'{}' changed: {}", key, e))?,
+        None => return Err(format!("'{}' disappeared after a truncated download", key).into()),
+    }
+    bucket.cleanup().await
+}
+
+/// Responses held back past the client's deadline: the client gives up, and an upload it
+/// abandoned is either stored complete or not at all.
+pub async fn delayed_response(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("slowresp")).await?;
+    let proxy = start_proxy(&ctx).await?;
+    proxy.set(Fault::DelayResponse(Duration::from_secs(2)));
+    let deadline = Some(Duration::from_secs(1));
+
+    let key = "delayed.bin";
+    let payload = ctx.payload(key, OBJECT_SIZE);
+    let sent = client
+        .put_object()
+        .bucket(bucket.name())
+        .key(key)
+        .body(ByteStream::from(payload.bytes()))
+        .customize()
+        .config_override(through(&ctx, &proxy, deadline))
+        .send()
+        .await;
+    info!(timed_out = sent.is_err(), "sent PutObject with a delayed response");
+    // Let the server finish whatever it was doing once the client walked away.
+    tokio::time::sleep(Duration::from_secs(2) + SETTLE).await;
+    expect_absent_or_complete(client, bucket.name(), key, &payload).await?;
+
+    put(client, bucket.name(), key, &payload).await?;
+    let received = client
+        .get_object()
+        .bucket(bucket.name())
+        .key(key)
+        .customize()
+        .config_override(through(&ctx, &proxy, deadline))
+        .send()
+        .await;
+    if received.is_ok() {
+        return Err("GetObject beat its deadline although the proxy held the response back".into());
+    }
+    match fetch(client, bucket.name(), key).await? {
+        Some(body) => payload.verify(&body).map_err(|e| format!("'{}' changed: {}", key, e))?,
+        None => return Err(format!("'{}' disappeared after a timed-out download", key).into()),
+    }
+    bucket.cleanup().await
+}
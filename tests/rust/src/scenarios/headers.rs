@@ -0,0 +1,326 @@
+//! Response headers of object requests. Clones often build the HEAD answer separately from
+//! the GET one and forget some of it: Content-Type, user metadata or the ETag differ between
+//! them, and clients that HEAD before downloading act on the wrong values.
+//!
+//! User metadata is limited to 2 KB per object, the names after `x-amz-meta-` and the values
+//! counted together, and a PUT with more is refused rather than stored cut short. Values that
+//! are not US-ASCII are kept: AWS decodes RFC 2047 encoded words on the way in and returns any
+//! value outside ASCII encoded as one (`=?UTF-8?B?...?=`).
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use http::Method;
+use tracing::{info, warn};
+
+use crate::expect_s3_error;
+use crate::guard::{BoxError, BucketGuard};
+use crate::rawhttp::{uri_encode, RawResponse};
+use crate::runner::TestContext;
+
+/// Headers that describe the exchange rather than the object, and may differ between two.
+const PER_RESPONSE: &[&str] =
+    &["date", "x-amz-request-id", "x-amz-id-2", "connection", "keep-alive", "server", "transfer-encoding"];
+
+struct Sample {
+    key: &'static str,
+    size: usize,
+    /// Sent with the PUT, for both answers to return.
+    headers: &'static [(&'static str, &'static str)],
+}
+
+const SAMPLE: &[Sample] = &[
+    Sample { key: "empty.txt", size: 0, headers: &[("content-type", "text/plain")] },
+    Sample { key: "one-byte.bin", size: 1, headers: &[] },
+    Sample {
+        key: "page.html",
+        size: 2048,
+        headers: &[("content-type", "text/html; charset=utf-8"), ("cache-control", "max-age=60")],
+    },
+    Sample {
+        key: "report.pdf",
+        size: 64 * 1024,
+        headers: &[
+            ("content-type", "application/pdf"),
+            ("content-disposition", "attachment; filename=\"report.pdf\""),
+            ("x-amz-meta-author", "s3test"),
+            ("x-amz-meta-revision", "3"),
+        ],
+    },
+    Sample { key: "dir/nested key.json", size: 300, headers: &[("content-type", "application/json")] },
+];
+
+/// Stored headers both answers must carry; the others sent are only compared between them,
+/// since not every server keeps them.
+fn required(name: &str) -> bool {
+    name == "content-type" || name == "content-length" || name.starts_with("x-amz-meta-")
+}
+
+/// The headers of `resp` that should be the same for HEAD and GET, by lowercase name.
+fn comparable(resp: &RawResponse) -> BTreeMap<String, Vec<&str>> {
+    let mut headers: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for (name, value) in &resp.headers {
+        let name = name.to_ascii_lowercase();
+        if !PER_RESPONSE.contains(&name.as_str()) {
+            headers.entry(name).or_default().push(value);
+        }
+    }
+    headers
+}
+
+/// HEAD and GET of a sample of objects return the same headers, with the Content-Type and
+/// metadata the objects were stored with. Every difference is reported, not just the first.
+pub async fn head_get_parity(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("parity")).await?;
+    let mut discrepancies = Vec::new();
+
+    for &Sample { key, size, headers: stored } in SAMPLE {
+        let path = format!("/{}/{}", bucket.name(), uri_encode(key, false));
+        let payload = ctx.payload(key, size);
+        let mut put = ctx.raw.request(Method::PUT, &path).body(payload.bytes());
+        for &(name, value) in stored {
+            put = put.header(name, value);
+        }
+        let resp = put.send().await?;
+        if resp.status != 200 {
+            return Err(format!("PUT {} answered {} {:?}", path, resp.status, resp.error_code()).into());
+        }
+
+        let head = ctx.raw.request(Method::HEAD, &path).send().await?;
+        let get = ctx.raw.request(Method::GET, &path).send().await?;
+        if head.status != 200 || get.status != 200 {
+            let message = format!("'{}': HEAD answered {}, GET {}", key, head.status, get.status);
+            return Err(message.into());
+        }
+        payload.verify(&get.body).map_err(|e| format!("GET '{}': {}", key, e))?;
+
+        let (head_headers, get_headers) = (comparable(&head), comparable(&get));
+        let names: BTreeSet<&String> = head_headers.keys().chain(get_headers.keys()).collect();
+        for name in names {
+            let difference = match (head_headers.get(name), get_headers.get(name)) {
+                (Some(h), Some(g)) if h != g => format!("HEAD {:?}, GET {:?}", h, g),
+                (Some(h), None) => format!("HEAD {:?}, missing from GET", h),
+                (None, Some(g)) => format!("GET {:?}, missing from HEAD", g),
+                _ => continue,
+            };
+            discrepancies.push(format!("'{}' {}: {}", key, name, difference));
+        }
+        let length = size.to_string();
+        let expected = stored.iter().copied().chain([("content-length", length.as_str())]);
+        for (name, value) in expected.filter(|&(name, _)| required(name)) {
+            for (method, headers) in [("HEAD", &head_headers), ("GET", &get_headers)] {
+                let found = headers.get(name);
+                if found.is_none_or(|values| values != &[value]) {
+                    let message = format!("{} has {:?}, stored {:?}", method, found, value);
+                    discrepancies.push(format!("'{}' {}: {}", key, name, message));
+                }
+            }
+        }
+    }
+
+    if !discrepancies.is_empty() {
+        let message = format!("HEAD and GET disagree: {}", discrepancies.join("; "));
+        return Err(message.into());
+    }
+    bucket.cleanup().await
+}
+
+/// The limit on an object's user metadata, in bytes of names and values.
+const METADATA_LIMIT: usize = 2048;
+/// Metadata entries each upload of [`metadata_limit`] spreads its bytes over.
+const METADATA_FIELDS: usize = 4;
+
+/// `x-amz-meta-*` headers of `total` bytes, names without the prefix and values counted.
+fn metadata_of(total: usize) -> Vec<(String, String)> {
+    let names: Vec<String> = (1..=METADATA_FIELDS).map(|i| format!("field-{}", i)).collect();
+    let mut left = total - names.iter().map(String::len).sum::<usize>();
+    let mut headers = Vec::new();
+    for (i, name) in names.into_iter().enumerate() {
+        let length = left / (METADATA_FIELDS - i);
+        left -= length;
+        headers.push((format!("x-amz-meta-{}", name), "m".repeat(length)));
+    }
+    headers
+}
+
+/// An object with user metadata just under the 2 KB limit is stored with all of it; one with
+/// a byte over is refused with MetadataTooLarge and not stored. The first stays under and the
+/// second goes over whether or not a server counts the `x-amz-meta-` prefixes.
+pub async fn metadata_limit(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("metalimit")).await?;
+    let prefixes = METADATA_FIELDS * "x-amz-meta-".len();
+
+    let key = "metadata-under-limit.txt";
+    let path = format!("/{}/{}", bucket.name(), uri_encode(key, false));
+    let metadata = metadata_of(METADATA_LIMIT - prefixes);
+    let mut put = ctx.raw.request(Method::PUT, &path).body("under the limit");
+    for (name, value) in &metadata {
+        put = put.header(name, value);
+    }
+    let resp = put.send().await?;
+    if resp.status != 200 {
+        let (status, code) = (resp.status, resp.error_code());
+        let bytes = METADATA_LIMIT - prefixes;
+        return Err(format!("PUT with {} bytes of metadata answered {} {:?}", bytes, status, code).into());
+    }
+    let head = ctx.raw.request(Method::HEAD, &path).send().await?;
+    for (name, value) in &metadata {
+        if head.header(name) != Some(value.as_str()) {
+            let length = head.header(name).map(str::len);
+            let message = format!("HEAD returns {} of {:?} bytes, stored {}", name, length, value.len());
+            return Err(message.into());
+        }
+    }
+
+    let key = "metadata-over-limit.txt";
+    let path = format!("/{}/{}", bucket.name(), uri_encode(key, false));
+    let mut put = ctx.raw.request(Method::PUT, &path).body("over the limit");
+    for (name, value) in metadata_of(METADATA_LIMIT + 1) {
+        put = put.header(&name, &value);
+    }
+    let resp = put.send().await?;
+    expect_s3_error!(resp, "MetadataTooLarge", 400, "PUT with {} bytes of metadata", METADATA_LIMIT + 1);
+    if ctx.raw.request(Method::HEAD, &path).send().await?.status != 404 {
+        return Err("the PUT with too much metadata was refused but stored".into());
+    }
+    bucket.cleanup().await
+}
+
+/// Metadata values outside US-ASCII, each with the text it stands for, sent as given.
+const NON_ASCII_VALUES: &[(&str, &str, &str)] = &[
+    ("utf8", "café crème", "café crème"),
+    ("emoji", "launch 🚀", "launch 🚀"),
+    ("rfc2047-b", "=?UTF-8?B?Y2Fmw6kgY3LDqG1l?=", "café crème"),
+    ("rfc2047-q", "=?UTF-8?Q?caf=C3=A9_cr=C3=A8me?=", "café crème"),
+];
+
+/// The text of a value made of RFC 2047 encoded words in UTF-8, or None if it is not one.
+fn decode_rfc2047(value: &str) -> Option<String> {
+    let mut text = Vec::new();
+    for word in value.split_whitespace() {
+        let inner = word.strip_prefix("=?")?.strip_suffix("?=")?;
+        let mut parts = inner.splitn(3, '?');
+        let (charset, encoding, encoded) = (parts.next()?, parts.next()?, parts.next()?);
+        if !charset.eq_ignore_ascii_case("utf-8") {
+            return None;
+        }
+        match encoding {
+            "B" | "b" => text.extend(aws_smithy_types::base64::decode(encoded).ok()?),
+            "Q" | "q" => {
+                let mut bytes = encoded.bytes();
+                while let Some(b) = bytes.next() {
+                    match b {
+                        b'_' => text.push(b' '),
+                        b'=' => {
+                            let hex = [bytes.next()?, bytes.next()?];
+                            text.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+                        }
+                        b => text.push(b),
+                    }
+                }
+            }
+            _ => return None,
+        }
+    }
+    String::from_utf8(text).ok()
+}
+
+/// Metadata values in UTF-8, raw or as RFC 2047 encoded words, are stored and returned either
+/// as sent or in the RFC 2047 form AWS answers with, standing for the same text. Every other
+/// outcome, a refused PUT or a server error included, is recorded and all of them reported at
+/// the end, so that one value the server mishandles does not hide the others.
+pub async fn non_ascii_metadata(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("metautf8")).await?;
+    let mut divergences = Vec::new();
+
+    for &(label, sent, text) in NON_ASCII_VALUES {
+        let key = format!("metadata-{}.txt", label);
+        let path = format!("/{}/{}", bucket.name(), uri_encode(&key, false));
+        let put = ctx.raw.request(Method::PUT, &path).header("x-amz-meta-note", sent);
+        let put = put.body(label.to_string());
+        let head = match put.send().await {
+            Ok(resp) if resp.status == 200 => ctx.raw.request(Method::HEAD, &path).send().await,
+            Ok(resp) => {
+                let (status, code) = (resp.status, resp.error_code());
+                divergences.push(format!("{}: PUT with {:?} answered {} {:?}", label, sent, status, code));
+                continue;
+            }
+            Err(e) => Err(e),
+        };
+        let returned = match &head {
+            Ok(resp) if resp.status == 200 => resp.header("x-amz-meta-note"),
+            Ok(resp) => {
+                divergences.push(format!("{}: HEAD answered {}", label, resp.status));
+                continue;
+            }
+            Err(e) => {
+                divergences.push(format!("{}: {}", label, e));
+                continue;
+            }
+        };
+        let form = match returned {
+            Some(value) if value == text => "as text",
+            Some(value) if decode_rfc2047(value).as_deref() == Some(text) => "RFC 2047 encoded",
+            other => {
+                let returned = format!("HEAD returns {:?}", other);
+                divergences.push(format!("{}: sent {:?} for {:?}, {}", label, sent, text, returned));
+                continue;
+            }
+        };
+        info!(label, sent, returned = returned.unwrap_or_default(), form, "non-ASCII metadata returned");
+    }
+
+    if !divergences.is_empty() {
+        for divergence in &divergences {
+            warn!(divergence, "non-ASCII metadata handled unlike AWS");
+        }
+        let message = format!("non-ASCII metadata handled unlike AWS: {}", divergences.join("; "));
+        return Err(message.into());
+    }
+    bucket.cleanup().await
+}
+
+/// Header names are case-insensitive. A PUT with its Content-Type and metadata names in odd
+/// casing, all of them signed, is accepted and stores them, the metadata under lowercase
+/// names; GETs with `RANGE` and `IF-NONE-MATCH` in capitals are answered as with any casing.
+pub async fn header_case(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("hdrcase")).await?;
+    let key = "header-case.txt";
+    let path = format!("/{}/{}", bucket.name(), uri_encode(key, false));
+    let payload = ctx.payload(key, 512);
+    let resp = ctx
+        .raw
+        .request(Method::PUT, &path)
+        .header("CONTENT-TYPE", "text/plain")
+        .header("x-AMZ-Meta-Foo", "bar")
+        .header("X-Amz-Meta-MixedCase", "Value Kept")
+        .body(payload.bytes())
+        .send()
+        .await?;
+    if resp.status != 200 {
+        let (status, code) = (resp.status, resp.error_code());
+        return Err(format!("PUT with headers in odd casing answered {} {:?}", status, code).into());
+    }
+
+    let head = ctx.client.head_object().bucket(bucket.name()).key(key).send().await?;
+    if head.content_type() != Some("text/plain") {
+        return Err(format!("CONTENT-TYPE was stored as Content-Type {:?}", head.content_type()).into());
+    }
+    let metadata = head.metadata().cloned().unwrap_or_default();
+    for (name, value) in [("foo", "bar"), ("mixedcase", "Value Kept")] {
+        if metadata.get(name).map(String::as_str) != Some(value) {
+            return Err(format!("expected metadata {}={:?}, got {:?}", name, value, metadata).into());
+        }
+    }
+
+    let resp = ctx.raw.request(Method::GET, &path).header("RANGE", "bytes=0-99").send().await?;
+    if resp.status != 206 || resp.body.len() != 100 {
+        let (status, bytes) = (resp.status, resp.body.len());
+        return Err(format!("GET with RANGE: bytes=0-99 answered {} with {} bytes", status, bytes).into());
+    }
+    let etag = head.e_tag().ok_or("HeadObject returned no ETag")?;
+    let resp = ctx.raw.request(Method::GET, &path).header("IF-NONE-MATCH", etag).send().await?;
+    if resp.status != 304 {
+        return Err(format!("GET with IF-NONE-MATCH of the current ETag answered {}", resp.status).into());
+    }
+    bucket.cleanup().await
+}
@@ -0,0 +1,57 @@
+//! HTTP/2, which a PHP server usually only speaks through the proxy or load balancer in front
+//! of it: the probe opens an HTTP/2 connection to the endpoint, h2 over TLS when it is https://
+//! and h2c with prior knowledge otherwise, and runs a few S3 operations over it. A server that
+//! cannot be talked to over HTTP/2 at all shows as not implemented in the conformance matrix;
+//! one that accepts the connection but gets the operations wrong, as broken.
+
+use http::Method;
+use tracing::info;
+
+use crate::guard::{BoxError, BucketGuard};
+use crate::matrix::Unsupported;
+use crate::rawhttp::{uri_encode, RawResponse};
+use crate::runner::TestContext;
+
+fn expect(what: &str, resp: &RawResponse, status: u16) -> Result<(), BoxError> {
+    if resp.status != status {
+        let code = resp.error_code().unwrap_or("no error code");
+        let message = format!("{} over HTTP/2 answered {} ({}), not {}", what, resp.status, code, status);
+        return Err(message.into());
+    }
+    Ok(())
+}
+
+/// HEAD bucket, PUT, GET, ListObjectsV2 and DELETE over one HTTP/2 connection.
+pub async fn probe(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("http2")).await?;
+    let key = "over-http2.bin";
+    let payload = ctx.payload(key, 64 * 1024);
+    let bucket_path = format!("/{}", bucket.name());
+    let object_path = format!("/{}/{}", bucket.name(), uri_encode(key, false));
+
+    // Until the server has answered one request, a failure means it does not speak HTTP/2.
+    let mut conn = ctx.raw.connect_h2().await.map_err(|e| Unsupported(format!("HTTP/2 connection: {}", e)))?;
+    let head = ctx.raw.request(Method::HEAD, &bucket_path).send_h2(&mut conn).await;
+    let resp = head.map_err(|e| Unsupported(format!("first request over {}: {}", conn.mode, e)))?;
+    expect("HEAD bucket", &resp, 200)?;
+
+    let resp = ctx.raw.request(Method::PUT, &object_path).body(payload.bytes()).send_h2(&mut conn).await?;
+    expect("PUT", &resp, 200)?;
+
+    let resp = ctx.raw.request(Method::GET, &object_path).send_h2(&mut conn).await?;
+    expect("GET", &resp, 200)?;
+    payload.verify(&resp.body).map_err(|e| format!("GET over HTTP/2: {}", e))?;
+
+    let list = ctx.raw.request(Method::GET, &bucket_path).query("list-type", "2");
+    let resp = list.send_h2(&mut conn).await?;
+    expect("ListObjectsV2", &resp, 200)?;
+    if !String::from_utf8_lossy(&resp.body).contains(&format!("<Key>{}</Key>", key)) {
+        return Err(format!("ListObjectsV2 over HTTP/2 does not list '{}'", key).into());
+    }
+
+    let resp = ctx.raw.request(Method::DELETE, &object_path).send_h2(&mut conn).await?;
+    expect("DELETE", &resp, 204)?;
+    info!(mode = conn.mode, "S3 operations over HTTP/2");
+
+    bucket.cleanup().await
+}
@@ -0,0 +1,190 @@
+//! Keys that look like file system paths. S3 keys are flat strings: `a//b`, `a/./b` and
+//! `a/../b` are three keys, none of them `a/b`. A server storing objects as files under the
+//! key's path collapses them into one, or, resolving `..`, writes and reads outside the
+//! bucket, in another bucket or anywhere on the disk.
+//!
+//! The key is also percent-decoded from the path exactly once: `%2F` in the path is a `/` in
+//! the key, `%252F` a literal `%2F`, and `+` stays a plus, where PHP's `urldecode` would turn
+//! it into a space.
+
+use std::collections::BTreeSet;
+
+use aws_sdk_s3::primitives::ByteStream;
+use http::Method;
+use tracing::info;
+
+use crate::expect_s3_error;
+use crate::guard::{BoxError, BucketGuard};
+use crate::rawhttp::uri_encode;
+use crate::runner::TestContext;
+
+/// Keys in groups that a server resolving them as paths maps to the same file, `a/b.txt`,
+/// `dir/file.txt` and `up.txt`, and keys that climb out of the bucket.
+const PATH_KEYS: &[&str] = &[
+    "a/b.txt",
+    "a//b.txt",
+    "a///b.txt",
+    "/a/b.txt",
+    "a/b.txt/",
+    "dir/file.txt",
+    "dir/./file.txt",
+    "./dir/file.txt",
+    "dir/.",
+    "up.txt",
+    "dir/../up.txt",
+    "a/b/../../up.txt",
+    "..",
+    "../up.txt",
+    "../../../../../../../../tmp/s3test-traversal.txt",
+    ".../up.txt",
+];
+
+/// Uploads every key of [`PATH_KEYS`] with its own content, reads each back as it was written
+/// and lists all of them under their literal names. `..` in a key must not reach another
+/// bucket, neither to write there nor to read from it, also when sent percent-encoded.
+pub async fn path_segments(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("pathkeys")).await?;
+    let sibling = BucketGuard::create(client, &ctx.run_id.bucket("pathside")).await?;
+
+    for key in PATH_KEYS {
+        let body = ByteStream::from(ctx.payload(key, 64).bytes());
+        client.put_object().bucket(bucket.name()).key(*key).body(body).send().await?;
+    }
+    // Where a key resolving `..` would land: the sibling bucket's directory, next to the bucket's.
+    let escaping = format!("../{}/escaped.txt", sibling.name());
+    let body = ByteStream::from(ctx.payload(&escaping, 64).bytes());
+    client.put_object().bucket(bucket.name()).key(&escaping).body(body).send().await?;
+    info!(keys = PATH_KEYS.len() + 1, "uploaded keys with path segments");
+
+    for key in PATH_KEYS.iter().copied().chain([escaping.as_str()]) {
+        let resp = client.get_object().bucket(bucket.name()).key(key).send().await;
+        let resp = resp.map_err(|e| format!("GetObject '{}': {}", key, e))?;
+        let body = resp.body.collect().await?.into_bytes();
+        ctx.payload(key, 64).verify(&body).map_err(|e| format!("GetObject '{}': {}", key, e))?;
+    }
+
+    let mut listed = BTreeSet::new();
+    let mut token = None;
+    loop {
+        let resp = client
+            .list_objects_v2()
+            .bucket(bucket.name())
+            .set_continuation_token(token)
+            .send()
+            .await?;
+        listed.extend(resp.contents().iter().filter_map(|o| o.key()).map(str::to_string));
+        token = resp.next_continuation_token().map(str::to_string);
+        if token.is_none() {
+            break;
+        }
+    }
+    let expected: BTreeSet<String> =
+        PATH_KEYS.iter().map(|k| k.to_string()).chain([escaping.clone()]).collect();
+    if listed != expected {
+        let missing: Vec<_> = expected.difference(&listed).collect();
+        let extra: Vec<_> = listed.difference(&expected).collect();
+        let message = format!("ListObjectsV2 lists other keys: missing {:?}, extra {:?}", missing, extra);
+        return Err(message.into());
+    }
+
+    let resp = client.list_objects_v2().bucket(sibling.name()).send().await?;
+    if let Some(key) = resp.contents().first().and_then(|o| o.key()) {
+        let (from, to) = (bucket.name(), sibling.name());
+        let message = format!("writing '{}' to {} created '{}' in {}", escaping, from, key, to);
+        return Err(message.into());
+    }
+
+    // Sent raw, with `..` as is and percent-encoded, since a server may decode before resolving.
+    let secret = "secret.txt";
+    let body = ByteStream::from(ctx.payload(secret, 64).bytes());
+    client.put_object().bucket(sibling.name()).key(secret).body(body).send().await?;
+    let targets = [
+        format!("/{}/../{}/{}", bucket.name(), sibling.name(), secret),
+        format!("/{}/%2E%2E/{}/{}", bucket.name(), sibling.name(), secret),
+        format!("/{}/{}", bucket.name(), uri_encode(&format!("../{}/{}", sibling.name(), secret), true)),
+    ];
+    for target in targets {
+        let resp = ctx.raw.request(Method::GET, &target).send().await?;
+        expect_s3_error!(resp, "NoSuchKey", 404, "GET {}", target);
+    }
+
+    sibling.cleanup().await?;
+    bucket.cleanup().await
+}
+
+/// Keys that a server decoding the path wrongly, or twice, confuses with one another.
+const ENCODED_KEYS: &[&str] = &[
+    "a+b.txt",
+    "a b.txt",
+    "a%20b.txt",
+    "dir/file.txt",
+    "dir%2Ffile.txt",
+    "100%.txt",
+    "with space.txt",
+];
+
+/// Raw paths below the bucket, each with the key it names, or None for one that must not exist.
+const ENCODED_PATHS: &[(&str, Option<&str>)] = &[
+    ("a+b.txt", Some("a+b.txt")),
+    ("a%2Bb.txt", Some("a+b.txt")),
+    ("a%20b.txt", Some("a b.txt")),
+    ("a%2520b.txt", Some("a%20b.txt")),
+    ("dir%2Ffile.txt", Some("dir/file.txt")),
+    ("dir%252Ffile.txt", Some("dir%2Ffile.txt")),
+    ("100%25.txt", Some("100%.txt")),
+    ("with+space.txt", None),
+];
+
+/// Keys with `+`, spaces, `%2F` and `%` are kept apart and listed as written, and raw GETs of
+/// [`ENCODED_PATHS`] find the object their path decodes to once: `+` as a plus, `%2F` as a
+/// slash, `%25` as a percent sign. Prefixes in the query string decode the same way.
+pub async fn url_encoding(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("enckeys")).await?;
+    for key in ENCODED_KEYS {
+        let body = ByteStream::from(ctx.payload(key, 64).bytes());
+        client.put_object().bucket(bucket.name()).key(*key).body(body).send().await?;
+    }
+
+    let resp = client.list_objects_v2().bucket(bucket.name()).send().await?;
+    let listed: BTreeSet<&str> = resp.contents().iter().filter_map(|o| o.key()).collect();
+    let expected: BTreeSet<&str> = ENCODED_KEYS.iter().copied().collect();
+    if listed != expected {
+        let missing: Vec<_> = expected.difference(&listed).collect();
+        let extra: Vec<_> = listed.difference(&expected).collect();
+        let message = format!("ListObjectsV2 lists other keys: missing {:?}, extra {:?}", missing, extra);
+        return Err(message.into());
+    }
+    for (prefix, only) in [("a+", "a+b.txt"), ("a ", "a b.txt"), ("dir%", "dir%2Ffile.txt")] {
+        let resp = client.list_objects_v2().bucket(bucket.name()).prefix(prefix).send().await?;
+        let keys: Vec<&str> = resp.contents().iter().filter_map(|o| o.key()).collect();
+        if keys != [only] {
+            let message = format!("prefix '{}' lists {:?}, expected ['{}']", prefix, keys, only);
+            return Err(message.into());
+        }
+    }
+
+    for (path, key) in ENCODED_PATHS {
+        let target = format!("/{}/{}", bucket.name(), path);
+        let resp = ctx.raw.request(Method::GET, &target).send().await?;
+        match key {
+            Some(key) => {
+                if resp.status != 200 {
+                    let (status, code) = (resp.status, resp.error_code());
+                    let message = format!("GET {} (key '{}') answered {} {:?}", target, key, status, code);
+                    return Err(message.into());
+                }
+                let verified = ctx.payload(key, 64).verify(&resp.body);
+                verified.map_err(|e| format!("GET {}, expected key '{}': {}", target, key, e))?;
+            }
+            None if resp.status != 404 => {
+                let (status, code) = (resp.status, resp.error_code());
+                return Err(format!("GET {}: expected 404, got {} {:?}", target, status, code).into());
+            }
+            None => {}
+        }
+    }
+
+    bucket.cleanup().await
+}
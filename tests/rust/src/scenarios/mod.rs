@@ -0,0 +1,919 @@
+//! Registry of every scenario the runner knows about: the built-in ones, and those a crate
+//! using the harness as a library adds with [`register`] for checks of its own deployment.
+//!
+//! ```no_run
+//! use s3_rust_client::guard::BoxError;
+//! use s3_rust_client::runner::{Scenario, TestContext};
+//!
+//! async fn bucket_quota(ctx: TestContext) -> Result<(), BoxError> {
+//!     ctx.client.list_buckets().send().await?;
+//!     Ok(())
+//! }
+//!
+//! s3_rust_client::scenarios::register(Scenario {
+//!     name: "acme::bucket_quota",
+//!     tags: &["acme"],
+//!     features: &["ListBuckets"],
+//!     calls: &["ListBuckets"],
+//!     min_version: Some("1.2"),
+//!     run: |ctx| Box::pin(bucket_quota(ctx)),
+//! })
+//! .unwrap();
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::guard::BoxError;
+use crate::runner::Scenario;
+use crate::version;
+
+mod auth;
+mod caching;
+mod connection;
+mod consistency;
+mod content;
+mod cors;
+mod crud;
+mod faults;
+mod headers;
+mod http2;
+mod keys;
+mod multipart;
+mod presigned;
+mod properties;
+mod races;
+mod robustness;
+mod sts;
+mod stress;
+
+/// Scenarios added with [`register`], which run after the built-in ones.
+static REGISTERED: Mutex<Vec<Scenario>> = Mutex::new(Vec::new());
+
+/// Adds a scenario to the built-in ones. From then on it is selected, run and reported like
+/// them, by the runner and by [`crate::S3ConformanceSuite`]; its name has to be unique, and
+/// should start with a suite of its own such as `acme::`. A `min_version` has to be a version
+/// number.
+pub fn register(scenario: Scenario) -> Result<(), BoxError> {
+    version::check(&scenario)?;
+    if all().iter().any(|s| s.name == scenario.name) {
+        return Err(format!("there already is a scenario named {}", scenario.name).into());
+    }
+    REGISTERED.lock().unwrap().push(scenario);
+    Ok(())
+}
+
+/// Every scenario: the built-in ones, then those registered.
+pub fn all() -> Vec<Scenario> {
+    let mut all = built_in();
+    all.extend(REGISTERED.lock().unwrap().iter().cloned());
+    all
+}
+
+fn built_in() -> Vec<Scenario> {
+    vec![
+        Scenario {
+            name: "crud::round_trip",
+            tags: &["core"],
+            features: &[
+                "CreateBucket",
+                "PutObject",
+                "ListObjectsV2",
+                "GetObject",
+                "DeleteObject",
+                "DeleteBucket",
+            ],
+            calls: &[
+                "CreateBucket",
+                "PutObject per --object-sizes (default 0 B, 1 B, 1 KiB, 1 MiB, 8 MiB, 100 MiB)",
+                "ListObjectsV2",
+                "GetObject per size",
+                "DeleteObject per size",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(crud::round_trip(ctx)),
+        },
+        Scenario {
+            name: "content::images",
+            tags: &["core"],
+            features: &["Binary content", "Content-Type"],
+            calls: &[
+                "CreateBucket",
+                "PutObject image/png",
+                "GetObject",
+                "HeadObject",
+                "PutObject image/jpeg",
+                "GetObject",
+                "HeadObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(content::images(ctx)),
+        },
+        Scenario {
+            name: "keys::path_segments",
+            tags: &["core"],
+            features: &["PutObject", "GetObject", "ListObjectsV2", "Key names"],
+            calls: &[
+                "CreateBucket x2",
+                "PutObject of keys with //, ./ and ../ segments, x17",
+                "GetObject x17",
+                "ListObjectsV2",
+                "ListObjectsV2 of the other bucket",
+                "PutObject to the other bucket",
+                "raw GET /bucket/../other/key, plain and percent-encoded, x3",
+                "DeleteBucket x2",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(keys::path_segments(ctx)),
+        },
+        Scenario {
+            name: "keys::url_encoding",
+            tags: &["core"],
+            features: &["PutObject", "GetObject", "ListObjectsV2", "Key names"],
+            calls: &[
+                "CreateBucket",
+                "PutObject of keys with +, spaces, %2F and %, x7",
+                "ListObjectsV2",
+                "ListObjectsV2 with prefixes a+, 'a ' and dir%",
+                "raw GET /bucket/key with +, %2B, %20, %2F, %25 and double encoding, x8",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(keys::url_encoding(ctx)),
+        },
+        Scenario {
+            name: "caching::if_none_match",
+            tags: &["core"],
+            features: &["ETag", "Conditional GET"],
+            calls: &[
+                "CreateBucket",
+                "raw PUT /bucket/key",
+                "raw GET /bucket/key",
+                "raw GET /bucket/key with If-None-Match: its ETag",
+                "raw PUT /bucket/key overwrite",
+                "raw GET /bucket/key with If-None-Match: the old ETag",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(caching::if_none_match(ctx)),
+        },
+        Scenario {
+            name: "headers::head_get_parity",
+            tags: &["core"],
+            features: &["HeadObject", "GetObject", "Content-Type", "User metadata"],
+            calls: &[
+                "CreateBucket",
+                "raw PUT /bucket/key x5, with Content-Type, Cache-Control, Content-Disposition, metadata",
+                "raw HEAD /bucket/key and GET /bucket/key per object",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(headers::head_get_parity(ctx)),
+        },
+        Scenario {
+            name: "headers::metadata_limit",
+            tags: &["core"],
+            features: &["User metadata"],
+            calls: &[
+                "CreateBucket",
+                "raw PUT /bucket/key with metadata just under 2 KB",
+                "raw HEAD /bucket/key",
+                "raw PUT /bucket/key with metadata of 2 KB and a byte",
+                "raw HEAD /bucket/key",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(headers::metadata_limit(ctx)),
+        },
+        Scenario {
+            name: "headers::non_ascii_metadata",
+            tags: &["core"],
+            features: &["User metadata"],
+            calls: &[
+                "CreateBucket",
+                "raw PUT /bucket/key with x-amz-meta-* in UTF-8 and RFC 2047 encoded words, x4",
+                "raw HEAD /bucket/key x4",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(headers::non_ascii_metadata(ctx)),
+        },
+        Scenario {
+            name: "headers::header_case",
+            tags: &["http"],
+            features: &["User metadata", "Content-Type", "Range requests", "Conditional GET"],
+            calls: &[
+                "CreateBucket",
+                "raw PUT /bucket/key with CONTENT-TYPE and x-AMZ-Meta-* headers",
+                "HeadObject",
+                "raw GET /bucket/key with RANGE",
+                "raw GET /bucket/key with IF-NONE-MATCH",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(headers::header_case(ctx)),
+        },
+        Scenario {
+            name: "cors::preflight_without_cors",
+            tags: &["http"],
+            features: &["CORS"],
+            calls: &[
+                "CreateBucket",
+                "PutObject",
+                "raw unsigned OPTIONS /bucket/key and /bucket with Origin, Access-Control-Request-Method",
+                "raw unsigned OPTIONS /bucket/key without Access-Control-Request-Method",
+                "raw unsigned OPTIONS /bucket/key without Origin",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(cors::preflight_without_cors(ctx)),
+        },
+        Scenario {
+            name: "robustness::missing_host",
+            tags: &["http"],
+            features: &["Host header validation"],
+            calls: &["CreateBucket", "raw GET /bucket?list-type=2 without Host", "DeleteBucket"],
+            min_version: None,
+            run: |ctx| Box::pin(robustness::missing_host(ctx)),
+        },
+        Scenario {
+            name: "robustness::duplicate_headers",
+            tags: &["http"],
+            features: &["Repeated headers", "User metadata"],
+            calls: &[
+                "CreateBucket",
+                "raw PUT /bucket/key with x-amz-meta-dup sent twice",
+                "HeadObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(robustness::duplicate_headers(ctx)),
+        },
+        Scenario {
+            name: "robustness::short_content_length",
+            tags: &["http"],
+            features: &["Content-Length validation"],
+            calls: &[
+                "CreateBucket",
+                "raw PUT /bucket/key with a Content-Length below the body size",
+                "GetObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(robustness::short_content_length(ctx)),
+        },
+        Scenario {
+            name: "robustness::long_content_length",
+            tags: &["http"],
+            features: &["Content-Length validation"],
+            calls: &[
+                "CreateBucket",
+                "raw PUT /bucket/key with a Content-Length above the body size, then the body ends",
+                "HeadObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(robustness::long_content_length(ctx)),
+        },
+        Scenario {
+            name: "robustness::chunked_upload",
+            tags: &["http"],
+            features: &["Chunked uploads", "Content-Length validation"],
+            calls: &[
+                "CreateBucket",
+                "raw PUT /bucket/key with Transfer-Encoding: chunked and UNSIGNED-PAYLOAD",
+                "GetObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(robustness::chunked_upload(ctx)),
+        },
+        Scenario {
+            name: "robustness::unsigned_request",
+            tags: &["http"],
+            features: &["Authentication"],
+            calls: &["CreateBucket", "raw unsigned GET /bucket", "DeleteBucket"],
+            min_version: None,
+            run: |ctx| Box::pin(robustness::unsigned_request(ctx)),
+        },
+        Scenario {
+            name: "auth::session_token",
+            tags: &["http"],
+            features: &["Authentication", "Temporary credentials"],
+            calls: &[
+                "CreateBucket",
+                "raw PUT /bucket/key with x-amz-security-token",
+                "raw GET /bucket/key with x-amz-security-token",
+                "raw GET /bucket/key with the token changed after signing",
+                "raw GET /bucket/key with the token not signed",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(auth::session_token(ctx)),
+        },
+        Scenario {
+            name: "auth::signing_modes",
+            tags: &["http"],
+            features: &["Authentication", "Presigned URLs"],
+            calls: &[
+                "CreateBucket",
+                "raw PutObject, HeadObject, GetObject x3, CopyObject, DeleteObject signed in a header",
+                "raw ListObjectsV2, HeadBucket, GetBucketLocation, ListBuckets signed in a header",
+                "the same signed in the query string",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(auth::signing_modes(ctx)),
+        },
+        Scenario {
+            name: "auth::host_signing",
+            tags: &["http"],
+            features: &["Authentication", "Host header validation"],
+            calls: &[
+                "CreateBucket",
+                "PutObject",
+                "raw GET /bucket/key signed over a Host with and without a port, x4",
+                "raw GET /bucket/key with the Host changed after signing, x4",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(auth::host_signing(ctx)),
+        },
+        Scenario {
+            name: "auth::query_canonicalization",
+            tags: &["http"],
+            features: &["Authentication"],
+            calls: &[
+                "CreateBucket",
+                "PutObject",
+                "raw GET /bucket/key with repeated, rewritten, reserved and =-less parameters, x4",
+                "raw GET /bucket?location, ?location=, an empty prefix and unsorted parameters, x4",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(auth::query_canonicalization(ctx)),
+        },
+        Scenario {
+            name: "auth::sigv4_cases",
+            tags: &["http"],
+            features: &["Authentication"],
+            calls: &[
+                "CreateBucket",
+                "raw PUT /bucket/key with UNSIGNED-PAYLOAD, a bare ?, empty and spaced headers, x6",
+                "raw PUT /bucket/key with unsigned or changed headers and wrong payload hashes, x5",
+                "GetObject x11",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(auth::sigv4_cases(ctx)),
+        },
+        Scenario {
+            name: "auth::date_precedence",
+            tags: &["http"],
+            features: &["Authentication"],
+            calls: &[
+                "CreateBucket",
+                "PutObject",
+                "raw GET /bucket/key dated by x-amz-date, Date, or both with different times, x5",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(auth::date_precedence(ctx)),
+        },
+        Scenario {
+            name: "auth::malformed_authorization",
+            tags: &["http"],
+            features: &["Authentication"],
+            calls: &[
+                "CreateBucket",
+                "PutObject",
+                "raw GET /bucket/key with a malformed credential scope, x5",
+                "raw GET /bucket/key with fields missing, a truncated signature, an unknown algorithm, x4",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(auth::malformed_authorization(ctx)),
+        },
+        Scenario {
+            name: "auth::wrong_credentials",
+            tags: &["core"],
+            features: &["Authentication", "Access control"],
+            calls: &[
+                "CreateBucket",
+                "PutObject",
+                "ListBuckets, ListObjectsV2, Head/Get/Put/DeleteObject with an unknown access key",
+                "the same with a wrong secret, and as the --other-access-key user if given",
+                "GetObject",
+                "HeadObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(auth::wrong_credentials(ctx)),
+        },
+        Scenario {
+            name: "auth::signing_region",
+            tags: &["core"],
+            features: &["Authentication", "Regions"],
+            calls: &[
+                "CreateBucket",
+                "PutObject",
+                "ListBuckets, ListObjectsV2, Head/Get/Put/DeleteObject signed for another region",
+                "DeleteBucket and CreateBucket signed for another region",
+                "CreateBucket constrained to another region",
+                "CreateBucket and GetBucketLocation constrained to the run's region, outside us-east-1",
+                "GetObject",
+                "HeadObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(auth::signing_region(ctx)),
+        },
+        Scenario {
+            name: "auth::tenant_isolation",
+            tags: &["tenants"],
+            features: &["Authentication", "Access control"],
+            calls: &[
+                "CreateBucket, PutObject, ListBuckets per tenant",
+                "ListBuckets, ListObjectsV2, Head/Get/Put/DeleteObject, DeleteBucket as every other tenant",
+                "ListObjectsV2, GetObject per tenant",
+                "DeleteBucket per tenant",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(auth::tenant_isolation(ctx)),
+        },
+        Scenario {
+            name: "presigned::signed_headers",
+            tags: &["http"],
+            features: &["Presigned URLs", "Authentication", "Content-Type", "User metadata"],
+            calls: &[
+                "CreateBucket",
+                "presigned PUT with its signed Content-Type or x-amz-meta-* changed or missing, x4",
+                "HeadObject",
+                "presigned PUT as signed",
+                "HeadObject",
+                "GetObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(presigned::signed_headers(ctx)),
+        },
+        Scenario {
+            name: "presigned::post_policy",
+            tags: &["http"],
+            features: &["Presigned URLs", "Authentication"],
+            calls: &[
+                "CreateBucket",
+                "raw POST /bucket form upload within the policy",
+                "GetObject",
+                "raw POST /bucket form upload outside the key prefix or size range, or re-signed, x5",
+                "HeadObject x5",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(presigned::post_policy(ctx)),
+        },
+        Scenario {
+            name: "presigned::empty_unsigned",
+            tags: &["http"],
+            features: &["Presigned URLs", "Authentication", "Empty objects"],
+            calls: &[
+                "CreateBucket",
+                "raw PUT /bucket/key of an empty body, hashed, UNSIGNED-PAYLOAD and presigned, x3",
+                "presigned PUT of an empty body",
+                "HeadObject x4",
+                "presigned GET x4",
+                "raw PUT /bucket/key with x-amz-content-sha256 changed after signing",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(presigned::empty_unsigned(ctx)),
+        },
+        Scenario {
+            name: "sts::assume_role",
+            tags: &["sts"],
+            features: &["STS AssumeRole"],
+            calls: &[
+                "CreateBucket",
+                "AssumeRole",
+                "PutObject, GetObject, ListObjectsV2 with the temporary credentials",
+                "GetObject with the temporary keys but no session token",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(sts::assume_role(ctx)),
+        },
+        Scenario {
+            name: "sts::expiry",
+            tags: &["sts"],
+            features: &["STS AssumeRole", "Credential expiry"],
+            calls: &[
+                "CreateBucket",
+                "AssumeRole for --sts-duration seconds",
+                "PutObject with the temporary credentials",
+                "GetObject with them once they have expired",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(sts::expiry(ctx)),
+        },
+        Scenario {
+            name: "connection::http_1_0",
+            tags: &["http"],
+            features: &["HTTP/1.0"],
+            calls: &[
+                "CreateBucket",
+                "raw HTTP/1.0 PUT /bucket/key",
+                "raw HTTP/1.0 GET /bucket/key",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(connection::http_1_0(ctx)),
+        },
+        Scenario {
+            name: "connection::connection_close",
+            tags: &["http"],
+            features: &["Connection: close"],
+            calls: &[
+                "CreateBucket",
+                "PutObject",
+                "raw GET /bucket/key with Connection: close",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(connection::connection_close(ctx)),
+        },
+        Scenario {
+            name: "connection::pipelined",
+            tags: &["http"],
+            features: &["Keep-alive", "Pipelining"],
+            calls: &[
+                "CreateBucket",
+                "PutObject x3",
+                "raw GET, HEAD, GET, GET pipelined on one connection",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(connection::pipelined(ctx)),
+        },
+        Scenario {
+            name: "connection::slow_upload",
+            tags: &["http", "slow"],
+            features: &["Slow clients"],
+            calls: &[
+                "CreateBucket",
+                "raw PUT /bucket/key trickling 96 B at 8 B/s",
+                "GetObject halfway through",
+                "GetObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(connection::slow_upload(ctx)),
+        },
+        Scenario {
+            name: "http2::probe",
+            tags: &["http", "probe"],
+            features: &["HTTP/2"],
+            calls: &[
+                "CreateBucket",
+                "raw HEAD /bucket over HTTP/2 (h2 or h2c)",
+                "raw PUT, GET /bucket/key, GET /bucket?list-type=2, DELETE /bucket/key on that connection",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(http2::probe(ctx)),
+        },
+        Scenario {
+            name: "faults::dropped_upload",
+            tags: &["network", "fault-injection"],
+            features: &["Interrupted uploads"],
+            calls: &[
+                "CreateBucket",
+                "PutObject new key, cut off after 64 KiB by the proxy",
+                "GetObject",
+                "ListObjectsV2",
+                "PutObject",
+                "PutObject overwrite, cut off after 64 KiB by the proxy",
+                "GetObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(faults::dropped_upload(ctx)),
+        },
+        Scenario {
+            name: "faults::dropped_part",
+            tags: &["network", "fault-injection"],
+            features: &["Interrupted uploads", "Multipart uploads"],
+            calls: &[
+                "CreateBucket",
+                "CreateMultipartUpload",
+                "UploadPart 1, cut off after 64 KiB by the proxy",
+                "ListParts",
+                "GetObject",
+                "ListObjectsV2",
+                "AbortMultipartUpload",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(faults::dropped_part(ctx)),
+        },
+        Scenario {
+            name: "faults::truncated_download",
+            tags: &["network", "fault-injection"],
+            features: &["Interrupted downloads"],
+            calls: &[
+                "CreateBucket",
+                "PutObject",
+                "GetObject, truncated after 32 KiB by the proxy",
+                "GetObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(faults::truncated_download(ctx)),
+        },
+        Scenario {
+            name: "faults::delayed_response",
+            tags: &["network", "fault-injection"],
+            features: &["Client timeouts"],
+            calls: &[
+                "CreateBucket",
+                "PutObject, response delayed 2s by the proxy, 1s deadline",
+                "GetObject",
+                "PutObject",
+                "GetObject, response delayed 2s by the proxy, 1s deadline",
+                "GetObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(faults::delayed_response(ctx)),
+        },
+        Scenario {
+            name: "properties::round_trip_bytes",
+            tags: &["core", "generative"],
+            features: &["PutObject", "GetObject", "Binary content"],
+            calls: &["CreateBucket", "PutObject + GetObject per generated body", "DeleteBucket"],
+            min_version: None,
+            run: |ctx| Box::pin(properties::round_trip_bytes(ctx)),
+        },
+        Scenario {
+            name: "properties::listing_model",
+            tags: &["core", "generative"],
+            features: &["PutObject", "DeleteObject", "ListObjectsV2"],
+            calls: &[
+                "CreateBucket",
+                "PutObject / DeleteObject per generated operation",
+                "ListObjectsV2 per generated sequence",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(properties::listing_model(ctx)),
+        },
+        Scenario {
+            name: "consistency::read_after_write",
+            tags: &["core", "concurrency"],
+            features: &["Read-after-write consistency", "PutObject", "DeleteObject"],
+            calls: &[
+                "CreateBucket",
+                "PutObject, then GetObject, HeadObject, ListObjectsV2 per reader, x10",
+                "PutObject overwrite, then GetObject, HeadObject, ListObjectsV2 per reader, x10",
+                "DeleteObject, then GetObject, HeadObject, ListObjectsV2 per reader, x10",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(consistency::read_after_write(ctx)),
+        },
+        Scenario {
+            name: "races::delete_during_download",
+            tags: &["core", "concurrency"],
+            features: &["GetObject", "DeleteObject", "Concurrent requests"],
+            calls: &[
+                "CreateBucket",
+                "PutObject 32 MiB",
+                "GetObject, paused after its first chunk",
+                "DeleteObject while the download is paused",
+                "GetObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(races::delete_during_download(ctx)),
+        },
+        Scenario {
+            name: "multipart::concurrent_parts",
+            tags: &["core", "concurrency"],
+            features: &["Multipart uploads", "Concurrent requests"],
+            calls: &[
+                "CreateBucket",
+                "CreateMultipartUpload",
+                "UploadPart x8 of 5 MiB, --part-workers at a time",
+                "CompleteMultipartUpload",
+                "GetObject",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(multipart::concurrent_parts(ctx)),
+        },
+        Scenario {
+            name: "stress::many_buckets",
+            tags: &["stress"],
+            features: &["CreateBucket", "ListBuckets", "DeleteBucket", "Concurrent requests"],
+            calls: &[
+                "CreateBucket x200, 32 at a time",
+                "ListBuckets",
+                "PutObject x600 spread over the buckets, 32 at a time",
+                "ListObjectsV2 per bucket, 32 at a time",
+                "DeleteObject x600, 32 at a time",
+                "DeleteBucket x200, 32 at a time",
+                "ListBuckets",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(stress::many_buckets(ctx)),
+        },
+        Scenario {
+            name: "stress::copy_fan_out",
+            tags: &["stress"],
+            features: &["CopyObject", "Concurrent requests"],
+            calls: &[
+                "CreateBucket",
+                "PutObject 256 KiB",
+                "CopyObject to 300 keys, 32 at a time",
+                "GetObject per copy and of the source, 32 at a time",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(stress::copy_fan_out(ctx)),
+        },
+        Scenario {
+            name: "stress::deep_listing",
+            tags: &["stress", "perf"],
+            features: &["ListObjectsV2", "Delimiters"],
+            calls: &[
+                "CreateBucket",
+                "PutObject x1944 in a hierarchy 5 directories deep, 32 at a time",
+                "ListObjectsV2 with delimiter / x5 per level, timed",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(stress::deep_listing(ctx)),
+        },
+        Scenario {
+            name: "stress::large_listing",
+            tags: &["stress", "scale"],
+            features: &["ListObjectsV2", "Pagination", "Large buckets"],
+            calls: &[
+                "CreateBucket",
+                "PutObject x100000 (--scale-objects), 32 at a time",
+                "ListObjectsV2 pages of 1000 keys until the end, timed",
+                "DeleteObject per object, 32 at a time",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(stress::large_listing(ctx)),
+        },
+        Scenario {
+            name: "stress::mixed_sizes",
+            tags: &["stress", "scale"],
+            features: &["HeadObject", "GetObject", "Large buckets"],
+            calls: &[
+                "CreateBucket",
+                "PutObject x20000 (--scale-mixed-objects) of 0 B to 1 MiB in 4 stages, 32 at a time",
+                "HeadObject and GetObject of 200 random keys after each stage, timed",
+                "DeleteObject per object, 32 at a time",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(stress::mixed_sizes(ctx)),
+        },
+        Scenario {
+            name: "stress::ranged_download",
+            tags: &["stress", "perf"],
+            features: &["GetObject", "Range requests", "Concurrent requests"],
+            calls: &[
+                "CreateBucket",
+                "PutObject 32 MiB",
+                "GetObject, timed",
+                "GetObject with Range per 4 MiB, 1, 4 and 8 at a time, timed",
+                "DeleteBucket",
+            ],
+            min_version: None,
+            run: |ctx| Box::pin(stress::ranged_download(ctx)),
+        },
+    ]
+}
+
+/// Tags of the scenarios that only run when asked for, and the flag that asks for them.
+const OPT_IN: &[(&str, &str)] = &[("sts", "--sts"), ("scale", "--scale"), ("tenants", "--tenant")];
+
+/// The opt-in tag of `scenario` that is not in `opted_in`, with the flag that asks for it.
+pub fn held_back_by(scenario: &Scenario, opted_in: &[&str]) -> Option<&'static (&'static str, &'static str)> {
+    OPT_IN.iter().find(|(tag, _)| scenario.tags.contains(tag) && !opted_in.contains(tag))
+}
+
+/// The scenarios whose name contains one of `only`, or all of them; those with an opt-in tag
+/// only when it is in `opted_in`.
+pub fn select(only: &[String], opted_in: &[&str]) -> Result<Vec<Scenario>, BoxError> {
+    let wanted = |s: &Scenario| only.is_empty() || only.iter().any(|text| s.name.contains(text.as_str()));
+    let held_back_by = |s: &Scenario| held_back_by(s, opted_in);
+    let (selected, held_back): (Vec<_>, Vec<_>) =
+        all().into_iter().filter(wanted).partition(|s| held_back_by(s).is_none());
+    if let (true, Some((tag, flag))) = (selected.is_empty(), held_back.first().and_then(held_back_by)) {
+        return Err(format!("only scenarios tagged {} match, and those only run with {}", tag, flag).into());
+    }
+    if selected.is_empty() {
+        return Err(format!("no scenario name contains any of {:?}", only).into());
+    }
+    Ok(selected)
+}
+
+/// Puts `scenarios` in an order that only depends on `seed`, so that a test relying on what
+/// an earlier one left behind, in the harness or on the server, fails in some run and the run
+/// can be repeated with the same seed.
+pub fn shuffle(scenarios: &mut [Scenario], seed: u64) {
+    scenarios.shuffle(&mut StdRng::seed_from_u64(seed));
+}
+
+/// Picks out scenarios by tag, by suite (the part of the name before `::`) or by name, written
+/// `tag=experimental`, `suite=crud` or `test=crud::round_trip`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    Tag(String),
+    Suite(String),
+    Test(String),
+}
+
+impl Selector {
+    pub fn matches(&self, name: &str, tags: &[&str]) -> bool {
+        match self {
+            Selector::Tag(tag) => tags.contains(&tag.as_str()),
+            Selector::Suite(suite) => name.split("::").next() == Some(suite.as_str()),
+            Selector::Test(test) => name == test,
+        }
+    }
+}
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (kind, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' should look like tag=NAME, suite=NAME or test=NAME", s))?;
+        let value = value.to_string();
+        match kind {
+            "tag" => Ok(Selector::Tag(value)),
+            "suite" => Ok(Selector::Suite(value)),
+            "test" => Ok(Selector::Test(value)),
+            other => Err(format!("unknown selector '{}', expected tag, suite or test", other)),
+        }
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Selector::Tag(tag) => write!(f, "tag={}", tag),
+            Selector::Suite(suite) => write!(f, "suite={}", suite),
+            Selector::Test(test) => write!(f, "test={}", test),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selector_parses_and_displays() {
+        for s in ["tag=slow", "suite=crud", "test=crud::put_get"] {
+            assert_eq!(s.parse::<Selector>().unwrap().to_string(), s);
+        }
+        assert!(matches!("test=a=b".parse(), Ok(Selector::Test(t)) if t == "a=b"));
+    }
+
+    #[test]
+    fn selector_refuses() {
+        assert!("crud".parse::<Selector>().is_err());
+        assert!("name=crud".parse::<Selector>().is_err());
+    }
+
+    #[test]
+    fn selector_matches() {
+        let tag = Selector::Tag("slow".to_string());
+        assert!(tag.matches("stress::burst", &["slow", "load"]));
+        assert!(!tag.matches("stress::burst", &["load"]));
+
+        let suite = Selector::Suite("crud".to_string());
+        assert!(suite.matches("crud::put_get", &[]));
+        assert!(!suite.matches("crudx::put_get", &[]));
+        assert!(!suite.matches("multipart::crud", &[]));
+
+        let test = Selector::Test("crud::put_get".to_string());
+        assert!(test.matches("crud::put_get", &[]));
+        assert!(!test.matches("crud::put_get_range", &[]));
+    }
+}
@@ -0,0 +1,159 @@
+//! Multipart uploads, with their parts sent concurrently to one upload ID the way SDK transfer
+//! managers send large objects. A server that keeps an upload's part list in a file it rewrites
+//! on every UploadPart loses parts when two of them finish together.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use aws_sdk_s3::Client;
+use bytes::{Bytes, BytesMut};
+use md5::{Digest, Md5};
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinSet;
+use tracing::{info, warn, Instrument, Span};
+
+use crate::bench::millis;
+use crate::guard::{BoxError, BucketGuard, MultipartGuard};
+use crate::payload::Payload;
+use crate::progress;
+use crate::runner::TestContext;
+
+/// Size of every part but the last, the smallest S3 allows, and how many parts there are; the
+/// last one is shorter.
+const PART_SIZE: usize = 5 * 1024 * 1024;
+const PARTS: usize = 8;
+const OBJECT_SIZE: usize = PART_SIZE * PARTS - 4321;
+
+/// Uploads `payload` as a multipart upload of `part_size` parts, `workers` of them in flight at
+/// once, and returns the ETag of the completed object. Parts are generated into a channel that
+/// holds one per worker, so only a few are in memory whatever the object's size. Every part's
+/// ETag must be its MD5. The upload is aborted if any part fails.
+pub async fn upload_concurrently(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    payload: &Payload,
+    part_size: usize,
+    workers: usize,
+) -> Result<String, BoxError> {
+    let upload = MultipartGuard::create(client, bucket, key).await?;
+    let result = upload_parts(client, bucket, key, upload.upload_id(), payload, part_size, workers).await;
+    match result {
+        Ok(_) => upload.completed(),
+        Err(_) => {
+            let upload_id = upload.upload_id().to_string();
+            if let Err(e) = upload.cleanup().await {
+                warn!(upload_id, error = %e, "could not abort the multipart upload");
+            }
+        }
+    }
+    result
+}
+
+async fn upload_parts(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    payload: &Payload,
+    part_size: usize,
+    workers: usize,
+) -> Result<String, BoxError> {
+    let (parts, queue) = mpsc::channel::<(i32, Bytes)>(workers);
+    let queue = Arc::new(Mutex::new(queue));
+    let mut tasks = JoinSet::new();
+    for _ in 0..workers {
+        let queue = queue.clone();
+        let (client, bucket, key, upload_id) =
+            (client.clone(), bucket.to_string(), key.to_string(), upload_id.to_string());
+        tasks.spawn(
+            async move {
+                let mut completed = Vec::new();
+                loop {
+                    let Some((number, part)) = queue.lock().await.recv().await else {
+                        return Ok::<_, BoxError>(completed);
+                    };
+                    let md5 = hex::encode(Md5::digest(&part));
+                    let resp = client
+                        .upload_part()
+                        .bucket(&bucket)
+                        .key(&key)
+                        .upload_id(&upload_id)
+                        .part_number(number)
+                        .body(ByteStream::from(part))
+                        .send()
+                        .await?;
+                    let etag = resp.e_tag().unwrap_or_default();
+                    if etag.trim_matches('"') != md5 {
+                        return Err(format!("part {} has ETag {}, its MD5 is {}", number, etag, md5).into());
+                    }
+                    completed.push(CompletedPart::builder().part_number(number).e_tag(etag).build());
+                }
+            }
+            .instrument(Span::current()),
+        );
+    }
+    drop(queue);
+
+    let mut number = 0;
+    let mut pending = BytesMut::new();
+    let mut chunks = payload.chunks().peekable();
+    'generate: while let Some(chunk) = chunks.next() {
+        pending.extend_from_slice(&chunk);
+        while pending.len() >= part_size || (chunks.peek().is_none() && !pending.is_empty()) {
+            let part = pending.split_to(part_size.min(pending.len())).freeze();
+            number += 1;
+            // Fails only once every worker has stopped, on an error collected below.
+            if parts.send((number, part)).await.is_err() {
+                break 'generate;
+            }
+        }
+    }
+    drop(parts);
+
+    let mut completed = Vec::new();
+    let mut errors = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined? {
+            Ok(parts) => completed.extend(parts),
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(format!("{} UploadPart(s) failed: {}", errors.len(), errors.join("; ")).into());
+    }
+    completed.sort_by_key(|p| p.part_number());
+    let resp = client
+        .complete_multipart_upload()
+        .bucket(bucket)
+        .key(key)
+        .upload_id(upload_id)
+        .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(completed)).build())
+        .send()
+        .await?;
+    Ok(resp.e_tag().unwrap_or_default().to_string())
+}
+
+/// An object uploaded as [`PARTS`] parts, `--part-workers` of them at a time to the same
+/// upload ID, completes with a multipart ETag and downloads as what was sent.
+pub async fn concurrent_parts(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("mpconc")).await?;
+    let key = "concurrent-parts.bin";
+    let payload = ctx.payload(key, OBJECT_SIZE);
+
+    let started = Instant::now();
+    let etag = upload_concurrently(client, bucket.name(), key, &payload, PART_SIZE, ctx.part_workers).await?;
+    let took = started.elapsed();
+    info!(parts = PARTS, workers = ctx.part_workers, ms = millis(took), %etag, "uploaded the parts");
+    if !etag.trim_matches('"').ends_with(&format!("-{}", PARTS)) {
+        return Err(format!("the completed upload has ETag {}, not one ending in -{}", etag, PARTS).into());
+    }
+
+    let resp = client.get_object().bucket(bucket.name()).key(key).send().await?;
+    progress::verify_download(resp.body, &payload).await.map_err(|e| format!("GetObject: {}", e))?;
+
+    bucket.cleanup().await
+}
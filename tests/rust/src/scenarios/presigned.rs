@@ -0,0 +1,286 @@
+//! Presigned requests: URLs carrying their signature in the query string, handed to a browser
+//! or another program that sends them later without credentials. Headers the URL was signed
+//! with bind the request to them; a server that checks only the query string lets whoever
+//! holds the URL upload anything with any Content-Type and metadata.
+
+use std::time::Duration;
+
+use aws_sdk_s3::presigning::{PresignedRequest, PresigningConfig};
+use http::Method;
+
+use crate::guard::{BoxError, BucketGuard};
+use crate::rawhttp::{uri_encode, RawResponse};
+use crate::runner::TestContext;
+
+const CONTENT_TYPE: &str = "text/csv";
+/// User metadata the URL is signed with, sent as `x-amz-meta-uploaded-by`.
+const META_KEY: &str = "uploaded-by";
+const META_VALUE: &str = "presigned-test";
+
+/// Sends `presigned` as a raw PUT of `body` with the headers it was signed with, but for
+/// `change`, a header sent with another value or, with None, left out.
+async fn send(
+    ctx: &TestContext,
+    presigned: &PresignedRequest,
+    body: &[u8],
+    change: Option<(&str, Option<&str>)>,
+) -> Result<RawResponse, BoxError> {
+    let uri: http::Uri = presigned.uri().parse()?;
+    let target = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let target = target.strip_prefix(ctx.raw.base_path()).unwrap_or(target);
+    let mut request = ctx.raw.request(Method::PUT, target).unsigned().body(body.to_vec());
+    for (name, value) in presigned.headers() {
+        let value = match change {
+            Some((changed, value)) if changed.eq_ignore_ascii_case(name) => value,
+            _ => Some(value),
+        };
+        if let Some(value) = value {
+            request = request.header(name, value);
+        }
+    }
+    request.send().await
+}
+
+/// A presigned PUT signed over Content-Type and an `x-amz-meta-*` header is refused when
+/// either is sent with another value or left out, and none of the refused uploads is stored;
+/// sent as signed, it stores the object with that Content-Type and metadata.
+pub async fn signed_headers(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("presign")).await?;
+    let key = "presigned/report.csv";
+    let payload = ctx.payload(key, 2048);
+    let config = PresigningConfig::expires_in(Duration::from_secs(900))?;
+    let presigned = client
+        .put_object()
+        .bucket(bucket.name())
+        .key(key)
+        .content_type(CONTENT_TYPE)
+        .metadata(META_KEY, META_VALUE)
+        .presigned(config)
+        .await?;
+
+    let meta_header = format!("x-amz-meta-{}", META_KEY);
+    let query = presigned.uri().split_once('?').map(|(_, q)| q).unwrap_or_default();
+    let signed = query.split('&').find_map(|p| p.strip_prefix("X-Amz-SignedHeaders="));
+    let signed = signed.unwrap_or_default().to_ascii_lowercase().replace("%3b", ";");
+    for name in ["content-type", meta_header.as_str()] {
+        if !signed.split(';').any(|h| h == name) {
+            let message = format!("the presigned URL does not sign {} (SignedHeaders={})", name, signed);
+            return Err(message.into());
+        }
+    }
+
+    let body = payload.bytes();
+    let tampered = [
+        ("another Content-Type", "content-type", Some("text/html")),
+        ("another metadata value", meta_header.as_str(), Some("someone-else")),
+        ("no Content-Type", "content-type", None),
+        ("no metadata header", meta_header.as_str(), None),
+    ];
+    for (what, name, value) in tampered {
+        let resp = send(&ctx, &presigned, &body, Some((name, value))).await?;
+        if resp.status != 403 {
+            let (status, code) = (resp.status, resp.error_code());
+            let message = format!("presigned PUT with {}: expected 403, got {} {:?}", what, status, code);
+            return Err(message.into());
+        }
+    }
+    let head = client.head_object().bucket(bucket.name()).key(key).send().await;
+    if head.is_ok() {
+        return Err("a presigned PUT that was refused stored the object anyway".into());
+    }
+
+    let resp = send(&ctx, &presigned, &body, None).await?;
+    if resp.status != 200 {
+        let code = resp.error_code();
+        return Err(format!("presigned PUT as signed answered {} {:?}", resp.status, code).into());
+    }
+    let head = client.head_object().bucket(bucket.name()).key(key).send().await?;
+    if head.content_type() != Some(CONTENT_TYPE) {
+        let stored = head.content_type();
+        return Err(format!("the object has Content-Type {:?}, signed {}", stored, CONTENT_TYPE).into());
+    }
+    let stored = head.metadata().and_then(|m| m.get(META_KEY));
+    if stored.map(String::as_str) != Some(META_VALUE) {
+        return Err(format!("the object has {} {:?}, signed {}", meta_header, stored, META_VALUE).into());
+    }
+    let resp = client.get_object().bucket(bucket.name()).key(key).send().await?;
+    payload.verify(&resp.body.collect().await?.into_bytes()).map_err(|e| format!("GetObject: {}", e))?;
+
+    bucket.cleanup().await
+}
+
+/// Prefix every key uploaded with the POST policy must start with, and the sizes it allows.
+const POST_PREFIX: &str = "uploads/";
+const POST_MIN_SIZE: usize = 1;
+const POST_MAX_SIZE: usize = 1024;
+/// Separates the parts of the form; the payloads are random, so it never occurs in them.
+const BOUNDARY: &str = "s3test-form-8d1c5e0a4b7f";
+
+/// Sends a browser-based upload of `body` as `key` to `bucket`: a `multipart/form-data` POST
+/// of `fields`, the key, and the file last, the way an HTML form sends it.
+async fn post_form(
+    ctx: &TestContext,
+    bucket: &str,
+    fields: &[(String, String)],
+    key: &str,
+    body: &[u8],
+) -> Result<RawResponse, BoxError> {
+    let mut form = Vec::new();
+    let key_field = ("key".to_string(), key.to_string());
+    for (name, value) in fields.iter().chain([&key_field]) {
+        let disposition = format!("Content-Disposition: form-data; name=\"{}\"", name);
+        form.extend_from_slice(format!("--{}\r\n{}\r\n\r\n{}\r\n", BOUNDARY, disposition, value).as_bytes());
+    }
+    let file = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"upload.bin\"\r\n\
+         Content-Type: application/octet-stream\r\n\r\n",
+        BOUNDARY
+    );
+    form.extend_from_slice(file.as_bytes());
+    form.extend_from_slice(body);
+    form.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+    let content_type = format!("multipart/form-data; boundary={}", BOUNDARY);
+    let request = ctx.raw.request(Method::POST, &format!("/{}", bucket)).unsigned();
+    request.header("content-type", &content_type).body(form).send().await
+}
+
+/// A browser-based upload signed with a POST policy is stored when its key and size meet the
+/// policy's conditions, and refused without storing anything when the key is outside the
+/// allowed prefix, the file is too large or too small, or the policy or its signature was
+/// changed. The SDK does not make POST policies, so the harness signs them itself.
+pub async fn post_policy(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("postpolicy")).await?;
+    let conditions = vec![
+        serde_json::json!(["starts-with", "$key", POST_PREFIX]),
+        serde_json::json!(["content-length-range", POST_MIN_SIZE, POST_MAX_SIZE]),
+    ];
+    let fields = ctx.raw.post_policy(bucket.name(), conditions, Duration::from_secs(900));
+
+    let key = format!("{}accepted.bin", POST_PREFIX);
+    let payload = ctx.payload(&key, POST_MAX_SIZE / 2);
+    let resp = post_form(&ctx, bucket.name(), &fields, &key, &payload.bytes()).await?;
+    if !(200..300).contains(&resp.status) {
+        let code = resp.error_code();
+        return Err(format!("POST within the policy answered {} {:?}", resp.status, code).into());
+    }
+    let resp = client.get_object().bucket(bucket.name()).key(&key).send().await?;
+    payload.verify(&resp.body.collect().await?.into_bytes()).map_err(|e| format!("GetObject: {}", e))?;
+
+    // Signed with the same keys, but allowing any key and size.
+    let looser = vec![serde_json::json!(["starts-with", "$key", ""])];
+    let looser = ctx.raw.post_policy(bucket.name(), looser, Duration::from_secs(900));
+    let swap = |name: &str, value: &str| {
+        let mut fields = fields.clone();
+        fields.iter_mut().filter(|(n, _)| n == name).for_each(|(_, v)| *v = value.to_string());
+        fields
+    };
+    let looser_policy = looser.iter().find(|(n, _)| n == "policy").map(|(_, v)| v.as_str());
+    let swapped_policy = swap("policy", looser_policy.unwrap_or_default());
+    let signature = fields.iter().find(|(n, _)| n == "x-amz-signature").map(|(_, v)| v.as_str());
+    let wrong_signature = signature.unwrap_or_default().chars().rev().collect::<String>();
+    let wrong_signature = swap("x-amz-signature", &wrong_signature);
+
+    let too_large = POST_MAX_SIZE + 1;
+    let refused = [
+        ("a key outside the prefix", &fields, "elsewhere/refused.bin".to_string(), 16, 403),
+        ("a file over the size range", &fields, format!("{}too-large.bin", POST_PREFIX), too_large, 400),
+        ("an empty file", &fields, format!("{}too-small.bin", POST_PREFIX), 0, 400),
+        ("another policy", &swapped_policy, format!("{}swapped.bin", POST_PREFIX), 16, 403),
+        ("a wrong signature", &wrong_signature, format!("{}unsigned.bin", POST_PREFIX), 16, 403),
+    ];
+    for (what, fields, key, size, expected) in refused {
+        let body = ctx.payload(&key, size).bytes();
+        let resp = post_form(&ctx, bucket.name(), fields, &key, &body).await?;
+        if resp.status != expected {
+            let (status, code) = (resp.status, resp.error_code());
+            let message = format!("POST with {}: expected {}, got {} {:?}", what, expected, status, code);
+            return Err(message.into());
+        }
+        if client.head_object().bucket(bucket.name()).key(&key).send().await.is_ok() {
+            return Err(format!("a POST with {} was refused but stored '{}' anyway", what, key).into());
+        }
+    }
+
+    bucket.cleanup().await
+}
+
+/// ETag of an object with no content, the MD5 of nothing.
+const EMPTY_ETAG: &str = "d41d8cd98f00b204e9800998ecf8427e";
+
+/// Checks that the empty upload `what` answered with `resp` stored an empty object at `key`,
+/// and that a presigned GET reads it back as empty.
+async fn expect_empty(
+    ctx: &TestContext,
+    bucket: &str,
+    key: &str,
+    what: &str,
+    resp: RawResponse,
+) -> Result<(), BoxError> {
+    if resp.status != 200 {
+        return Err(format!("empty body in {} answered {} {:?}", what, resp.status, resp.error_code()).into());
+    }
+    let head = ctx.client.head_object().bucket(bucket).key(key).send().await?;
+    let (length, etag) = (head.content_length(), head.e_tag().unwrap_or_default().trim_matches('"'));
+    if length != Some(0) || etag != EMPTY_ETAG {
+        let message = format!("empty body in {} stored Content-Length {:?}, ETag {}", what, length, etag);
+        return Err(message.into());
+    }
+    let path = format!("/{}/{}", bucket, uri_encode(key, false));
+    let resp = ctx.raw.request(Method::GET, &path).presigned(Duration::from_secs(900)).send().await?;
+    if resp.status != 200 || !resp.body.is_empty() {
+        let (status, bytes) = (resp.status, resp.body.len());
+        let message = format!("presigned GET after {} answered {}, {} bytes", what, status, bytes);
+        return Err(message.into());
+    }
+    Ok(())
+}
+
+/// Empty uploads in every way of signing them: the hash of nothing and `UNSIGNED-PAYLOAD` in
+/// a header-signed PUT, a PUT signed in the query string by the harness and by the SDK. Each
+/// stores an empty object, which a presigned GET reads back as empty. A header-signed PUT whose
+/// `x-amz-content-sha256` is changed after signing is refused, so the header is signed.
+pub async fn empty_unsigned(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("emptyput")).await?;
+    let put = |key: &str| {
+        let path = format!("/{}/{}", bucket.name(), uri_encode(key, false));
+        ctx.raw.request(Method::PUT, &path)
+    };
+    let expires_in = Duration::from_secs(900);
+
+    let uploads = [
+        ("empty/hashed.bin", "a PUT signed over the hash of nothing", put("empty/hashed.bin")),
+        (
+            "empty/unsigned-payload.bin",
+            "a PUT signed with UNSIGNED-PAYLOAD",
+            put("empty/unsigned-payload.bin").unsigned_payload(),
+        ),
+        (
+            "empty/query-signed.bin",
+            "a PUT signed in the query string",
+            put("empty/query-signed.bin").presigned(expires_in),
+        ),
+    ];
+    for (key, what, request) in uploads {
+        expect_empty(&ctx, bucket.name(), key, what, request.send().await?).await?;
+    }
+    let key = "empty/sdk-presigned.bin";
+    let config = PresigningConfig::expires_in(expires_in)?;
+    let presigned = client.put_object().bucket(bucket.name()).key(key).presigned(config).await?;
+    let resp = send(&ctx, &presigned, b"", None).await?;
+    expect_empty(&ctx, bucket.name(), key, "a PUT presigned by the SDK", resp).await?;
+
+    let sha256_of_nothing = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+    let request = put("empty/hash-changed.bin").unsigned_payload();
+    let resp = request.after_signing("x-amz-content-sha256", sha256_of_nothing).send().await?;
+    if resp.status != 403 {
+        let (status, code) = (resp.status, resp.error_code());
+        let what = "PUT with x-amz-content-sha256 changed after signing";
+        let message = format!("{}: expected 403, got {} {:?}", what, status, code);
+        return Err(message.into());
+    }
+
+    bucket.cleanup().await
+}
@@ -0,0 +1,159 @@
+//! Property-based checks: proptest generates the inputs, the server is the system under test.
+//!
+//! The generator is seeded from the run's `--seed`, so a failure replays with the same seed,
+//! and proptest shrinks a failing input to a minimal one before it is reported.
+
+use std::cell::Cell;
+use std::collections::BTreeSet;
+
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::test_runner::{Config, RngAlgorithm, TestCaseError, TestError, TestRng, TestRunner};
+use tokio::runtime::Handle;
+use tracing::info;
+
+use crate::guard::{BoxError, BucketGuard};
+use crate::runner::TestContext;
+
+const CASES: u32 = 32;
+/// Keys the listing model draws from; few enough that puts and deletes keep colliding.
+const MODEL_KEYS: &[&str] = &["a", "b", "a/b", "a/c", "b/a/a", "c d", "é", "z~1"];
+
+#[derive(Debug, Clone)]
+enum ModelOp {
+    Put(&'static str),
+    Delete(&'static str),
+}
+
+/// Any byte sequence uploaded with PutObject comes back unchanged from GetObject.
+pub async fn round_trip_bytes(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("prop-bytes")).await?;
+
+    let strategy = vec(any::<u8>(), 0..=64 * 1024);
+    check(&ctx, strategy, |data: Vec<u8>| {
+        block_on(async {
+            let key = "blob";
+            client
+                .put_object()
+                .bucket(bucket.name())
+                .key(key)
+                .body(ByteStream::from(data.clone()))
+                .send()
+                .await
+                .map_err(fail)?;
+            let resp = client.get_object().bucket(bucket.name()).key(key).send().await.map_err(fail)?;
+            let body = resp.body.collect().await.map_err(fail)?.into_bytes();
+            prop_assert_eq!(body.len(), data.len(), "body length");
+            prop_assert!(body == data, "body differs from the {} bytes uploaded", data.len());
+            Ok(())
+        })
+    })?;
+
+    bucket.cleanup().await?;
+    Ok(())
+}
+
+/// After any sequence of puts and deletes, ListObjectsV2 returns exactly the keys a set
+/// model of the bucket holds.
+pub async fn listing_model(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("prop-list")).await?;
+
+    let key = proptest::sample::select(MODEL_KEYS);
+    let op = prop_oneof![key.clone().prop_map(ModelOp::Put), key.prop_map(ModelOp::Delete)];
+    let case = Cell::new(0);
+    check(&ctx, vec(op, 1..20), |ops: Vec<ModelOp>| {
+        // Every case, including each shrinking step, starts from an empty prefix.
+        case.set(case.get() + 1);
+        let prefix = format!("case-{}/", case.get());
+        block_on(async {
+            let mut model = BTreeSet::new();
+            for op in &ops {
+                match *op {
+                    ModelOp::Put(key) => {
+                        let key = format!("{}{}", prefix, key);
+                        let put = client.put_object().bucket(bucket.name()).key(&key);
+                        put.body(ByteStream::from_static(b"model")).send().await.map_err(fail)?;
+                        model.insert(key);
+                    }
+                    ModelOp::Delete(key) => {
+                        let key = format!("{}{}", prefix, key);
+                        client.delete_object().bucket(bucket.name()).key(&key).send().await.map_err(fail)?;
+                        model.remove(&key);
+                    }
+                }
+            }
+            let listed = list_keys(client, bucket.name(), &prefix).await.map_err(fail)?;
+            prop_assert_eq!(listed, model.into_iter().collect::<Vec<_>>(), "listing after {:?}", ops);
+            Ok(())
+        })
+    })?;
+
+    bucket.cleanup().await?;
+    Ok(())
+}
+
+/// Runs `test` over inputs from `strategy`, shrinking and reporting the minimal failing one.
+fn check<S: Strategy>(
+    ctx: &TestContext,
+    strategy: S,
+    test: impl Fn(S::Value) -> Result<(), TestCaseError>,
+) -> Result<(), BoxError> {
+    let mut seed = [0; 32];
+    for chunk in seed.chunks_mut(8) {
+        chunk.copy_from_slice(&ctx.seed.to_le_bytes());
+    }
+    let config =
+        Config { cases: CASES, failure_persistence: None, max_shrink_iters: 256, ..Config::default() };
+    let mut runner = TestRunner::new_with_rng(config, TestRng::from_seed(RngAlgorithm::ChaCha, &seed));
+    match runner.run(&strategy, test) {
+        Ok(()) => {
+            info!(cases = CASES, "property held");
+            Ok(())
+        }
+        Err(TestError::Fail(reason, value)) => {
+            Err(format!("property failed, minimal input {}: {}", describe(&value), reason).into())
+        }
+        Err(TestError::Abort(reason)) => Err(format!("property check aborted: {}", reason).into()),
+    }
+}
+
+/// Failing inputs can be large byte vectors; keep the report readable.
+fn describe(value: &impl std::fmt::Debug) -> String {
+    let text = format!("{:?}", value);
+    match text.char_indices().nth(200) {
+        Some((end, _)) => format!("{}... ({} characters)", &text[..end], text.len()),
+        None => text,
+    }
+}
+
+/// Waits for `future` from inside a proptest closure, which is synchronous.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::task::block_in_place(|| Handle::current().block_on(future))
+}
+
+fn fail(e: impl std::fmt::Debug) -> TestCaseError {
+    TestCaseError::fail(format!("{:?}", e))
+}
+
+async fn list_keys(client: &Client, bucket: &str, prefix: &str) -> Result<Vec<String>, BoxError> {
+    let mut keys = Vec::new();
+    let mut token = None;
+    loop {
+        let resp = client
+            .list_objects_v2()
+            .bucket(bucket)
+            .prefix(prefix)
+            .set_continuation_token(token)
+            .send()
+            .await?;
+        keys.extend(resp.contents().iter().filter_map(|o| o.key().map(str::to_string)));
+        match resp.next_continuation_token() {
+            Some(next) => token = Some(next.to_string()),
+            None => return Ok(keys),
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Requests racing each other on the same key. Whatever order the server puts them in, neither
+//! may see a mixture: a download answered 200 has to carry the object as it was when it started.
+
+use std::pin::pin;
+use std::time::Duration;
+
+use aws_sdk_s3::primitives::ByteStream;
+use tracing::info;
+
+use crate::expect_s3_error;
+use crate::guard::{BoxError, BucketGuard};
+use crate::runner::TestContext;
+
+/// Large enough that most of it is still on the server when the deletion goes in.
+const LARGE_OBJECT: usize = 32 * 1024 * 1024;
+/// How long the DELETE gets before the download resumes, for a server that holds it back until
+/// the object is no longer being read.
+const DELETE_WAIT: Duration = Duration::from_secs(10);
+
+/// A GET of a large object paused after its first chunk while the key is deleted: the download
+/// either finishes with the full original content or fails, and never ends short with its 200.
+pub async fn delete_during_download(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("delrace")).await?;
+    let key = "deleted-while-downloading.bin";
+    let payload = ctx.payload(key, LARGE_OBJECT);
+    let body = ByteStream::from(payload.bytes());
+    client.put_object().bucket(bucket.name()).key(key).body(body).send().await?;
+
+    let mut download = client.get_object().bucket(bucket.name()).key(key).send().await?.body;
+    let mut verifier = payload.verifier();
+    let first = download.try_next().await?.ok_or("GetObject of a 32 MiB object returned no body")?;
+    verifier.update(&first)?;
+
+    let mut delete = pin!(client.delete_object().bucket(bucket.name()).key(key).send());
+    let deleted_first = match tokio::time::timeout(DELETE_WAIT, &mut delete).await {
+        Ok(result) => {
+            result?;
+            true
+        }
+        Err(_) => false,
+    };
+    info!(received = first.len(), deleted_first, "deleted the key in the middle of its download");
+
+    let mut received = first.len();
+    loop {
+        match download.try_next().await {
+            Ok(Some(chunk)) => {
+                received += chunk.len();
+                verifier.update(&chunk).map_err(|e| format!("download racing DeleteObject: {}", e))?;
+            }
+            // A short body that ends without an error is the one outcome that is never fine.
+            Ok(None) => {
+                verifier.finish().map_err(|e| format!("download answered 200 but its {}", e))?;
+                break;
+            }
+            Err(e) => {
+                info!(received, error = %e, "download failed after the deletion");
+                break;
+            }
+        }
+    }
+    if !deleted_first {
+        delete.await?;
+    }
+
+    let resp = client.get_object().bucket(bucket.name()).key(key).send().await;
+    expect_s3_error!(resp, "NoSuchKey", 404, "GetObject '{}' after DeleteObject", key);
+    bucket.cleanup().await
+}
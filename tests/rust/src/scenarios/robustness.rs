@@ -0,0 +1,190 @@
+//! Malformed and unusual requests the SDK would never send. The server may reject them, but
+//! it must answer with a 4xx rather than crash, and must never store mangled data.
+
+use std::time::Duration;
+
+use http::Method;
+use tracing::{info, warn};
+
+use crate::expect_s3_error;
+use crate::guard::{BoxError, BucketGuard};
+use crate::rawhttp::{uri_encode, RawResponse};
+use crate::runner::TestContext;
+
+fn no_server_error(what: &str, resp: &RawResponse) -> Result<(), BoxError> {
+    if resp.status >= 500 {
+        return Err(format!(
+            "{}: server answered {} ({})",
+            what,
+            resp.status,
+            String::from_utf8_lossy(&resp.body)
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// A request without a Host header is invalid HTTP/1.1 and must be rejected with 400.
+pub async fn missing_host(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("nohost")).await?;
+    let resp = ctx
+        .raw
+        .request(Method::GET, &format!("/{}", bucket.name()))
+        .query("list-type", "2")
+        .without_header("host")
+        .send()
+        .await?;
+    no_server_error("GET without Host", &resp)?;
+    if resp.status != 400 {
+        return Err(format!("expected 400 for a request without Host, got {}", resp.status).into());
+    }
+    bucket.cleanup().await
+}
+
+/// Repeated headers must be combined into one comma-separated value, as AWS does.
+pub async fn duplicate_headers(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("duphdr")).await?;
+    let key = "duplicate-meta.txt";
+    let resp = ctx
+        .raw
+        .request(Method::PUT, &format!("/{}/{}", bucket.name(), uri_encode(key, false)))
+        .header("x-amz-meta-dup", "first")
+        .header("x-amz-meta-dup", "second")
+        .body("duplicate headers")
+        .send()
+        .await?;
+    no_server_error("PUT with duplicate headers", &resp)?;
+    if resp.status != 200 || resp.header("etag").is_none() {
+        return Err(format!(
+            "PUT with duplicate headers answered {} with ETag {:?}",
+            resp.status,
+            resp.header("etag")
+        )
+        .into());
+    }
+
+    let head = ctx.client.head_object().bucket(bucket.name()).key(key).send().await?;
+    let value = head.metadata().and_then(|m| m.get("dup")).map(String::as_str);
+    if value != Some("first,second") {
+        return Err(format!("expected metadata dup=\"first,second\", got {:?}", value).into());
+    }
+    bucket.cleanup().await
+}
+
+/// A Content-Length shorter than the body cuts the body off; the signed payload hash no longer
+/// matches, so the upload must be refused with a 4xx and nothing stored, not even the bytes
+/// that were declared.
+pub async fn short_content_length(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("shortcl")).await?;
+    let key = "short-content-length.txt";
+    let resp = ctx
+        .raw
+        .request(Method::PUT, &format!("/{}/{}", bucket.name(), uri_encode(key, false)))
+        .body("0123456789")
+        .content_length(4)
+        .send()
+        .await?;
+    no_server_error("PUT with short Content-Length", &resp)?;
+    if resp.status < 400 {
+        return Err(format!("PUT of 10 bytes declared as 4 answered {}, expected a 4xx", resp.status).into());
+    }
+    info!(status = resp.status, code = ?resp.error_code(), "short Content-Length refused");
+
+    if let Ok(object) = ctx.client.get_object().bucket(bucket.name()).key(key).send().await {
+        let data = object.body.collect().await?.into_bytes();
+        return Err(format!("the refused upload was stored anyway, as {:?}", data).into());
+    }
+    bucket.cleanup().await
+}
+
+/// A Content-Length longer than the body, with the client's side of the connection closed
+/// after the short body: the server must answer with a 4xx, `IncompleteBody` as S3 does,
+/// rather than wait forever or store the partial upload, padded or not.
+pub async fn long_content_length(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("longcl")).await?;
+    let key = "long-content-length.txt";
+    let request = ctx
+        .raw
+        .request(Method::PUT, &format!("/{}/{}", bucket.name(), uri_encode(key, false)))
+        .body("0123456789")
+        .content_length(100);
+    let mut conn = ctx.raw.connect().await?;
+    conn.send(&request.encode("HTTP/1.1")).await?;
+    conn.finish_sending().await?;
+    let reply = tokio::time::timeout(Duration::from_secs(10), conn.read_response(&Method::PUT)).await;
+    let resp = match reply {
+        Ok(Ok(resp)) => resp.response,
+        Ok(Err(e)) => return Err(format!("PUT with long Content-Length got no answer: {}", e).into()),
+        Err(_) => return Err("PUT with long Content-Length: no answer within 10s of the body ending".into()),
+    };
+    no_server_error("PUT with long Content-Length", &resp)?;
+    if resp.status < 400 {
+        let message = format!("PUT of 10 bytes declared as 100 answered {}, expected a 4xx", resp.status);
+        return Err(message.into());
+    }
+    if resp.error_code() != Some("IncompleteBody") {
+        let (status, code) = (resp.status, resp.error_code());
+        warn!(status, ?code, "long Content-Length refused, but not with IncompleteBody");
+    }
+
+    if ctx.client.head_object().bucket(bucket.name()).key(key).send().await.is_ok() {
+        return Err("a truncated upload became visible".into());
+    }
+    bucket.cleanup().await
+}
+
+/// A PUT with `Transfer-Encoding: chunked`, no Content-Length and an unsigned payload, sent
+/// in 8 KiB chunks: either the object is stored as the body without the chunk framing, or
+/// the upload is refused as S3 refuses it, 411 MissingContentLength or 501 NotImplemented,
+/// and nothing is stored.
+pub async fn chunked_upload(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("chunked")).await?;
+    let key = "chunked-upload.bin";
+    let payload = ctx.payload(key, 100 * 1024 + 17);
+    let request = ctx
+        .raw
+        .request(Method::PUT, &format!("/{}/{}", bucket.name(), uri_encode(key, false)))
+        .body(payload.bytes());
+    let mut conn = ctx.raw.connect().await?;
+    conn.send(&request.encode_chunked(8 * 1024)).await?;
+    let reply = tokio::time::timeout(Duration::from_secs(30), conn.read_response(&Method::PUT)).await;
+    let resp = reply.map_err(|_| "chunked PUT: no answer within 30s")??.response;
+
+    let stored = ctx.client.get_object().bucket(bucket.name()).key(key).send().await;
+    match resp.status {
+        200 => {
+            let stored = stored.map_err(|e| format!("GetObject after the chunked PUT: {}", e))?;
+            let data = stored.body.collect().await?.into_bytes();
+            payload.verify(&data).map_err(|e| format!("the chunked upload was stored wrongly: {}", e))?;
+            info!(bytes = data.len(), "chunked upload stored");
+        }
+        411 | 501 => {
+            let code = resp.error_code();
+            if stored.is_ok() {
+                let message = format!("chunked PUT refused with {} {:?} but stored", resp.status, code);
+                return Err(message.into());
+            }
+            info!(status = resp.status, ?code, "chunked upload refused");
+        }
+        status => {
+            let code = resp.error_code();
+            let message = format!("chunked PUT answered {} {:?}, expected 200, 411 or 501", status, code);
+            return Err(message.into());
+        }
+    }
+    bucket.cleanup().await
+}
+
+/// Unauthenticated requests must be refused with AccessDenied.
+pub async fn unsigned_request(ctx: TestContext) -> Result<(), BoxError> {
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("anon")).await?;
+    let resp = ctx
+        .raw
+        .request(Method::GET, &format!("/{}", bucket.name()))
+        .unsigned()
+        .send()
+        .await?;
+    no_server_error("anonymous GET", &resp)?;
+    expect_s3_error!(resp, "AccessDenied", 403, "anonymous GET");
+    bucket.cleanup().await
+}
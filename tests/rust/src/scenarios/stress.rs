@@ -0,0 +1,587 @@
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{info, Instrument, Span};
+
+use crate::bench::{millis, percentile};
+use crate::guard::{BoxError, BucketGuard};
+use crate::histogram::Histogram;
+use crate::progress;
+use crate::runner::TestContext;
+
+const BUCKETS: usize = 200;
+const OBJECTS_PER_BUCKET: usize = 3;
+const OBJECT_SIZE: usize = 1024;
+const COPIES: usize = 300;
+const COPY_SIZE: usize = 256 * 1024;
+/// Shape of the deep listing's hierarchy: directories per directory, levels of them, and
+/// objects in each directory of the last level.
+const FAN_OUT: usize = 3;
+const LEVELS: usize = 5;
+const LEAVES: usize = 8;
+const LISTINGS_PER_LEVEL: usize = 5;
+/// Longest a single delimiter listing of the hierarchy may take, whatever its depth.
+const LIST_BUDGET: Duration = Duration::from_secs(2);
+/// Keys per page of the large listing, and the longest one page of it may take.
+const PAGE_SIZE: i32 = 1000;
+const PAGE_BUDGET: Duration = Duration::from_secs(5);
+/// The object the ranged download fetches, odd-sized so that its last range is a short one,
+/// the size of each range, and how many of them are in flight at once, in turn.
+const RANGED_SIZE: usize = 32 * 1024 * 1024 + 12345;
+const RANGE_SIZE: usize = 4 * 1024 * 1024;
+const RANGE_CONCURRENCY: &[usize] = &[1, 4, 8];
+/// Requests in flight at once in every phase.
+/// Sizes of the mixed-size bucket, as the share of objects in percent with the smallest and
+/// largest size: mostly small documents and thumbnails, some images, a few larger files.
+const SIZE_CLASSES: &[(u32, usize, usize)] =
+    &[(80, 0, 4 << 10), (18, 4 << 10, 64 << 10), (2, 64 << 10, 1 << 20)];
+/// Fractions of the mixed-size bucket after which its latency is measured.
+const FILL_STAGES: &[usize] = &[10, 25, 50, 100];
+/// Random keys read with HEAD and GET at every stage.
+const LOOKUPS: usize = 200;
+const CONCURRENCY: usize = 32;
+
+/// Creates a few hundred buckets at once, spreads objects across them, then lists and deletes
+/// everything, again concurrently. The other scenarios use one bucket at a time, so this is
+/// the one that finds races in how the server stores bucket metadata, such as two creations
+/// overwriting each other's entry in a shared index.
+pub async fn many_buckets(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let names: Vec<String> = (0..BUCKETS).map(|i| ctx.run_id.bucket(&format!("stress-{:03}", i))).collect();
+    let objects = BUCKETS * OBJECTS_PER_BUCKET;
+    let bucket_of = |i: usize| names[i % BUCKETS].clone();
+
+    // Create the buckets
+    let guards = concurrently(
+        "CreateBucket",
+        names.iter().map(|name| {
+            let (client, name) = (client.clone(), name.clone());
+            async move { BucketGuard::create(&client, &name).await }
+        }),
+    )
+    .await?;
+    info!(buckets = guards.len(), "created buckets");
+
+    let resp = client.list_buckets().send().await?;
+    let listed: BTreeSet<&str> = resp.buckets().iter().filter_map(|b| b.name()).collect();
+    let missing = names.iter().filter(|name| !listed.contains(name.as_str())).count();
+    if missing > 0 {
+        let message = format!("ListBuckets is missing {} of the {} buckets just created", missing, BUCKETS);
+        return Err(message.into());
+    }
+
+    // Spread the objects across the buckets
+    concurrently(
+        "PutObject",
+        (0..objects).map(|i| {
+            let (client, bucket) = (client.clone(), bucket_of(i));
+            let body = ByteStream::from(ctx.payload(&key(i), OBJECT_SIZE).bytes());
+            async move {
+                client.put_object().bucket(bucket).key(key(i)).body(body).send().await?;
+                Ok(())
+            }
+        }),
+    )
+    .await?;
+    info!(objects, "uploaded objects");
+
+    // List every bucket and check it holds its objects and nothing else
+    concurrently(
+        "ListObjectsV2",
+        names.iter().enumerate().map(|(b, name)| {
+            let (client, name) = (client.clone(), name.clone());
+            let expected: BTreeSet<String> = (0..objects).filter(|i| i % BUCKETS == b).map(key).collect();
+            async move {
+                let resp = client.list_objects_v2().bucket(&name).send().await?;
+                let listed: BTreeSet<String> =
+                    resp.contents().iter().filter_map(|o| o.key()).map(String::from).collect();
+                if listed != expected {
+                    return Err(format!("'{}' lists {:?}, expected {:?}", name, listed, expected).into());
+                }
+                if let Some(object) = resp.contents().iter().find(|o| o.size() != Some(OBJECT_SIZE as i64)) {
+                    let key = object.key().unwrap_or_default();
+                    return Err(format!("'{}/{}' is listed with size {:?}", name, key, object.size()).into());
+                }
+                Ok(())
+            }
+        }),
+    )
+    .await?;
+    info!(buckets = BUCKETS, "listed buckets");
+
+    // Delete the objects, then the buckets
+    concurrently(
+        "DeleteObject",
+        (0..objects).map(|i| {
+            let (client, bucket) = (client.clone(), bucket_of(i));
+            async move {
+                client.delete_object().bucket(bucket).key(key(i)).send().await?;
+                Ok(())
+            }
+        }),
+    )
+    .await?;
+    info!(objects, "deleted objects");
+
+    concurrently("DeleteBucket", guards.into_iter().map(BucketGuard::cleanup)).await?;
+    info!(buckets = BUCKETS, "deleted buckets");
+
+    let resp = client.list_buckets().send().await?;
+    let left = resp.buckets().iter().filter_map(|b| b.name()).filter(|name| names.iter().any(|n| n == name));
+    let left = left.count();
+    if left > 0 {
+        return Err(format!("ListBuckets still shows {} of the {} deleted buckets", left, BUCKETS).into());
+    }
+
+    Ok(())
+}
+
+/// Copies one object to [`COPIES`] keys concurrently and reads every copy back. Servers that
+/// lock the source while copying it serialise here, or fail copies that time out on the lock;
+/// ones that stream the copy through a shared buffer hand out mixed-up bodies.
+pub async fn copy_fan_out(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("fanout")).await?;
+    let source = "source.bin";
+    let payload = ctx.payload(source, COPY_SIZE);
+    let resp = client
+        .put_object()
+        .bucket(bucket.name())
+        .key(source)
+        .body(ByteStream::from(payload.bytes()))
+        .send()
+        .await?;
+    let etag = resp.e_tag().ok_or("PutObject of the copy source returned no ETag")?.to_string();
+    let name = bucket.name();
+    let copy_source = format!("{}/{}", name, source);
+    let copy_key = |i: usize| format!("copies/copy-{:03}.bin", i);
+
+    concurrently(
+        "CopyObject",
+        (0..COPIES).map(|i| {
+            let (client, bucket, copy_source) = (client.clone(), name.to_string(), copy_source.clone());
+            async move {
+                let copy = client.copy_object().bucket(bucket).key(copy_key(i)).copy_source(copy_source);
+                copy.send().await?;
+                Ok(())
+            }
+        }),
+    )
+    .await?;
+    info!(copies = COPIES, "copied the source object");
+
+    // Every copy, and the source, hold the original bytes
+    concurrently(
+        "GetObject",
+        (0..COPIES).map(copy_key).chain([source.to_string()]).map(|key| {
+            let (client, bucket) = (client.clone(), name.to_string());
+            let (payload, etag) = (payload.clone(), etag.clone());
+            async move {
+                let resp = client.get_object().bucket(bucket).key(&key).send().await?;
+                // A single-part copy keeps the MD5 ETag of its source.
+                if resp.e_tag() != Some(etag.as_str()) {
+                    return Err(format!("'{}' has ETag {:?}, the source {}", key, resp.e_tag(), etag).into());
+                }
+                let verified = progress::verify_download(resp.body, &payload).await;
+                verified.map_err(|e| format!("'{}': {}", key, e))?;
+                Ok(())
+            }
+        }),
+    )
+    .await?;
+    info!(copies = COPIES, "verified every copy");
+
+    bucket.cleanup().await
+}
+
+/// Builds a hierarchy [`LEVELS`] directories deep, [`FAN_OUT`] wide at every level, with
+/// [`LEAVES`] objects at the bottom of each branch (close to 2000 in all), then lists prefixes
+/// of every depth with a `/` delimiter and reports the latency per level. A listing should
+/// only cost the entries it returns; a filesystem-backed server that walks every directory
+/// below the prefix instead is slowest at the top and goes over [`LIST_BUDGET`].
+pub async fn deep_listing(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("deep")).await?;
+    let name = bucket.name();
+
+    let branches = FAN_OUT.pow(LEVELS as u32);
+    let leaves: Vec<String> = (0..branches * LEAVES)
+        .map(|n| {
+            let branch = n / LEAVES;
+            let path: Vec<usize> =
+                (0..LEVELS).map(|level| branch / FAN_OUT.pow(level as u32) % FAN_OUT).collect();
+            format!("{}leaf-{}.bin", directory(&path), n % LEAVES)
+        })
+        .collect();
+    concurrently(
+        "PutObject",
+        leaves.iter().map(|key| {
+            let (client, bucket, key) = (client.clone(), name.to_string(), key.clone());
+            let body = ByteStream::from(ctx.payload(&key, 16).bytes());
+            async move {
+                client.put_object().bucket(bucket).key(key).body(body).send().await?;
+                Ok(())
+            }
+        }),
+    )
+    .await?;
+    info!(objects = leaves.len(), levels = LEVELS, "uploaded the hierarchy");
+
+    for depth in 0..=LEVELS {
+        let mut latencies = Vec::new();
+        for sample in 0..LISTINGS_PER_LEVEL {
+            let path: Vec<usize> = (0..depth).map(|level| (sample + level) % FAN_OUT).collect();
+            latencies.push(list_level(client, name, &directory(&path), depth).await?);
+        }
+        latencies.sort();
+        let (median, slowest) = (percentile(&latencies, 50.0), percentile(&latencies, 100.0));
+        info!(depth, median_ms = millis(median), max_ms = millis(slowest), "listed a level of the hierarchy");
+    }
+
+    bucket.cleanup().await
+}
+
+/// Lists `prefix`, `depth` levels down the deep listing's hierarchy, with a `/` delimiter,
+/// checks what it holds and returns how long that took.
+async fn list_level(client: &Client, bucket: &str, prefix: &str, depth: usize) -> Result<Duration, BoxError> {
+    let started = Instant::now();
+    let resp = client.list_objects_v2().bucket(bucket).prefix(prefix).delimiter("/").send().await?;
+    let took = started.elapsed();
+
+    let (expected, objects): (BTreeSet<String>, usize) = if depth < LEVELS {
+        ((0..FAN_OUT).map(|i| format!("{}level{}-{}/", prefix, depth + 1, i)).collect(), 0)
+    } else {
+        (BTreeSet::new(), LEAVES)
+    };
+    let listed: BTreeSet<String> =
+        resp.common_prefixes().iter().filter_map(|p| p.prefix()).map(String::from).collect();
+    if listed != expected {
+        let message = format!("listing '{}' returned the prefixes {:?}, not {:?}", prefix, listed, expected);
+        return Err(message.into());
+    }
+    let listed = resp.contents().len();
+    if listed != objects {
+        return Err(format!("listing '{}' returned {} objects, not {}", prefix, listed, objects).into());
+    }
+    if took > LIST_BUDGET {
+        let message = format!("listing '{}' took {:.0} ms, over {:?}", prefix, millis(took), LIST_BUDGET);
+        return Err(message.into());
+    }
+    Ok(took)
+}
+
+/// Fills a bucket with `--scale-objects` tiny objects, 100,000 by default, and pages through
+/// it [`PAGE_SIZE`] keys at a time: every key comes back once, in order, and no page takes
+/// longer than [`PAGE_BUDGET`]. Large buckets are where servers that list by reading a whole
+/// directory, or sort in PHP on every request, fall over.
+pub async fn large_listing(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("large")).await?;
+    let name = bucket.name();
+    let count = ctx.scale_objects;
+    // Zero-padded, so that the order they were made in is the order they list in.
+    let keys: Vec<String> = (0..count).map(|i| format!("objects/{:07}", i)).collect();
+
+    concurrently(
+        "PutObject",
+        keys.iter().map(|key| {
+            let (client, bucket, key) = (client.clone(), name.to_string(), key.clone());
+            let body = ByteStream::from(ctx.payload(&key, 8).bytes());
+            async move {
+                client.put_object().bucket(bucket).key(key).body(body).send().await?;
+                Ok(())
+            }
+        }),
+    )
+    .await?;
+    info!(objects = count, "uploaded the objects");
+
+    let mut listed = Vec::with_capacity(count);
+    let mut latencies = Vec::new();
+    let mut token = None;
+    loop {
+        let started = Instant::now();
+        let resp = client
+            .list_objects_v2()
+            .bucket(name)
+            .max_keys(PAGE_SIZE)
+            .set_continuation_token(token)
+            .send()
+            .await?;
+        let took = started.elapsed();
+        let page = latencies.len() + 1;
+        latencies.push(took);
+        if took > PAGE_BUDGET {
+            let message = format!("page {} took {:.0} ms, over {:?}", page, millis(took), PAGE_BUDGET);
+            return Err(message.into());
+        }
+        let keys_on_page = resp.contents().len();
+        if keys_on_page > PAGE_SIZE as usize {
+            let message = format!("page {} lists {} keys, over max-keys {}", page, keys_on_page, PAGE_SIZE);
+            return Err(message.into());
+        }
+        listed.extend(resp.contents().iter().filter_map(|o| o.key()).map(String::from));
+
+        token = resp.next_continuation_token().map(String::from);
+        match (resp.is_truncated().unwrap_or(false), &token) {
+            (false, _) => break,
+            (true, Some(_)) => {}
+            (true, None) => {
+                return Err(format!("page {} is truncated but has no continuation token", page).into())
+            }
+        }
+    }
+
+    if let Some(i) = (0..listed.len().min(count)).find(|&i| listed[i] != keys[i]) {
+        let message = format!("key {} of the listing is '{}', expected '{}'", i, listed[i], keys[i]);
+        return Err(message.into());
+    }
+    if listed.len() != count {
+        return Err(format!("the listing has {} keys, the bucket {}", listed.len(), count).into());
+    }
+    latencies.sort();
+    let (median, slowest) = (percentile(&latencies, 50.0), percentile(&latencies, 100.0));
+    info!(pages = latencies.len(), median_ms = millis(median), max_ms = millis(slowest), "listed the bucket");
+
+    concurrently(
+        "DeleteObject",
+        keys.into_iter().map(|key| {
+            let (client, bucket) = (client.clone(), name.to_string());
+            async move {
+                client.delete_object().bucket(bucket).key(key).send().await?;
+                Ok(())
+            }
+        }),
+    )
+    .await?;
+    bucket.cleanup().await
+}
+
+/// Fills a bucket with `--scale-mixed-objects` objects, 20,000 by default, of the sizes of
+/// [`SIZE_CLASSES`], and after every stage of [`FILL_STAGES`] reads [`LOOKUPS`] random keys of
+/// those uploaded so far with HEAD and GET, one at a time. HEAD must report each object's size
+/// and GET return its bytes. The latencies are logged per stage, to show how lookups in the
+/// server's storage layout slow down as a bucket grows.
+pub async fn mixed_sizes(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("mixed")).await?;
+    let name = bucket.name();
+    let count = ctx.mixed_objects;
+    let mut rng = StdRng::seed_from_u64(ctx.seed);
+    let objects: Vec<(String, usize)> =
+        (0..count).map(|i| (format!("mixed/{:07}", i), mixed_size(&mut rng))).collect();
+    let total: usize = objects.iter().map(|(_, size)| size).sum();
+    info!(objects = count, mib = total as f64 / (1024.0 * 1024.0), "uploading mixed-size objects");
+
+    let mut uploaded = 0;
+    for &percent in FILL_STAGES {
+        let upto = count * percent / 100;
+        concurrently(
+            "PutObject",
+            objects[uploaded..upto].iter().map(|(key, size)| {
+                let (client, bucket, key) = (client.clone(), name.to_string(), key.clone());
+                let body = ByteStream::from(ctx.payload(&key, *size).bytes());
+                async move {
+                    client.put_object().bucket(bucket).key(key).body(body).send().await?;
+                    Ok(())
+                }
+            }),
+        )
+        .await?;
+        uploaded = upto;
+        if uploaded == 0 {
+            continue;
+        }
+
+        let (mut heads, mut gets) = (Histogram::default(), Histogram::default());
+        for _ in 0..LOOKUPS {
+            let (key, size) = &objects[rng.random_range(0..uploaded)];
+            let started = Instant::now();
+            let head = client.head_object().bucket(name).key(key).send().await?;
+            heads.record(started.elapsed());
+            if head.content_length() != Some(*size as i64) {
+                let reported = head.content_length();
+                let message = format!("HeadObject {} reports {:?} bytes, not {}", key, reported, size);
+                return Err(message.into());
+            }
+            let started = Instant::now();
+            let resp = client.get_object().bucket(name).key(key).send().await?;
+            let data = resp.body.collect().await?.into_bytes();
+            gets.record(started.elapsed());
+            ctx.payload(key, *size).verify(&data).map_err(|e| format!("GetObject {}: {}", key, e))?;
+        }
+        info!(
+            objects = uploaded,
+            head_p50_ms = millis(heads.percentile(50.0)),
+            head_p99_ms = millis(heads.percentile(99.0)),
+            get_p50_ms = millis(gets.percentile(50.0)),
+            get_p99_ms = millis(gets.percentile(99.0)),
+            get_max_ms = millis(gets.max()),
+            "looked up random keys"
+        );
+    }
+
+    concurrently(
+        "DeleteObject",
+        objects.into_iter().map(|(key, _)| {
+            let (client, bucket) = (client.clone(), name.to_string());
+            async move {
+                client.delete_object().bucket(bucket).key(key).send().await?;
+                Ok(())
+            }
+        }),
+    )
+    .await?;
+    bucket.cleanup().await
+}
+
+/// A size drawn from [`SIZE_CLASSES`].
+fn mixed_size(rng: &mut StdRng) -> usize {
+    let mut share = rng.random_range(0..100);
+    for &(percent, smallest, largest) in SIZE_CLASSES {
+        if share < percent {
+            return rng.random_range(smallest..=largest);
+        }
+        share -= percent;
+    }
+    0
+}
+
+/// Downloads one large object with a single GET, then as [`RANGE_SIZE`] Range requests at
+/// several levels of concurrency, reassembling the ranges into the object. Every range must
+/// come back with the bytes and the Content-Range it asked for, and the reassembly must be the
+/// object. The throughput of each download is logged, to show whether parallel reads of one
+/// object help the server or contend on it.
+pub async fn ranged_download(ctx: TestContext) -> Result<(), BoxError> {
+    let client = &ctx.client;
+    let bucket = BucketGuard::create(client, &ctx.run_id.bucket("ranged")).await?;
+    let key = "ranged.bin";
+    let payload = ctx.payload(key, RANGED_SIZE);
+    let body = ByteStream::from(payload.bytes());
+    client.put_object().bucket(bucket.name()).key(key).body(body).send().await?;
+
+    let started = Instant::now();
+    let resp = client.get_object().bucket(bucket.name()).key(key).send().await?;
+    progress::verify_download(resp.body, &payload).await.map_err(|e| format!("GetObject: {}", e))?;
+    let single = started.elapsed();
+    let mib = RANGED_SIZE as f64 / (1024.0 * 1024.0);
+    info!(ms = millis(single), mib_per_s = mib / single.as_secs_f64(), "downloaded with one GET");
+
+    for &in_flight in RANGE_CONCURRENCY {
+        let started = Instant::now();
+        let object = download_ranges(client, bucket.name(), key, in_flight).await?;
+        let took = started.elapsed();
+        payload.verify(&object).map_err(|e| format!("ranges {} at a time, reassembled: {}", in_flight, e))?;
+        info!(
+            in_flight,
+            ranges = RANGED_SIZE.div_ceil(RANGE_SIZE),
+            ms = millis(took),
+            mib_per_s = mib / took.as_secs_f64(),
+            speedup = single.as_secs_f64() / took.as_secs_f64(),
+            "downloaded in ranges"
+        );
+    }
+
+    bucket.cleanup().await
+}
+
+/// Fetches the ranged download's object as [`RANGE_SIZE`] ranges, `in_flight` at a time, and
+/// puts them together.
+async fn download_ranges(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    in_flight: usize,
+) -> Result<Vec<u8>, BoxError> {
+    let permits = Arc::new(Semaphore::new(in_flight));
+    let mut tasks = JoinSet::new();
+    for start in (0..RANGED_SIZE).step_by(RANGE_SIZE) {
+        let end = (start + RANGE_SIZE).min(RANGED_SIZE) - 1;
+        let permit = permits.clone().acquire_owned().await?;
+        let (client, bucket, key) = (client.clone(), bucket.to_string(), key.to_string());
+        tasks.spawn(
+            async move {
+                let range = format!("bytes={}-{}", start, end);
+                let resp = client.get_object().bucket(bucket).key(key).range(&range).send().await?;
+                let expected = format!("bytes {}-{}/{}", start, end, RANGED_SIZE);
+                if resp.content_range() != Some(expected.as_str()) {
+                    let message = format!("{} answered Content-Range {:?}", range, resp.content_range());
+                    return Err(message.into());
+                }
+                let data = resp.body.collect().await?.into_bytes();
+                drop(permit);
+                if data.len() != end - start + 1 {
+                    return Err(format!("{} returned {} bytes", range, data.len()).into());
+                }
+                Ok::<_, BoxError>((start, data))
+            }
+            .instrument(Span::current()),
+        );
+    }
+
+    let mut object = vec![0; RANGED_SIZE];
+    while let Some(joined) = tasks.join_next().await {
+        let (start, data) = joined??;
+        object[start..start + data.len()].copy_from_slice(&data);
+    }
+    Ok(object)
+}
+
+/// The directory prefix of `path`, the index of the directory taken at every level.
+fn directory(path: &[usize]) -> String {
+    path.iter().enumerate().map(|(level, i)| format!("level{}-{}/", level + 1, i)).collect()
+}
+
+fn key(i: usize) -> String {
+    format!("object-{:04}.bin", i)
+}
+
+/// Runs `requests` with at most [`CONCURRENCY`] in flight and returns their results in order.
+/// Every request runs to its end even if some fail, so that the error can say how many did.
+async fn concurrently<T, Fut>(
+    operation: &str,
+    requests: impl Iterator<Item = Fut>,
+) -> Result<Vec<T>, BoxError>
+where
+    T: Send + 'static,
+    Fut: Future<Output = Result<T, BoxError>> + Send + 'static,
+{
+    let in_flight = Arc::new(Semaphore::new(CONCURRENCY));
+    let mut tasks = JoinSet::new();
+    let mut count = 0;
+    for (i, request) in requests.enumerate() {
+        let permit = in_flight.clone().acquire_owned().await?;
+        tasks.spawn(
+            async move {
+                let result = request.await;
+                drop(permit);
+                (i, result)
+            }
+            .instrument(Span::current()),
+        );
+        count += 1;
+    }
+
+    let mut results: Vec<Option<T>> = (0..count).map(|_| None).collect();
+    let mut errors = Vec::new();
+    while let Some(task) = tasks.join_next().await {
+        match task? {
+            (i, Ok(value)) => results[i] = Some(value),
+            (i, Err(e)) => errors.push((i, e)),
+        }
+    }
+    if let Some((i, first)) = errors.iter().min_by_key(|(i, _)| *i) {
+        let failed = errors.len();
+        let message = format!("{} of {} {} calls failed, #{} with: {}", failed, count, operation, i, first);
+        return Err(message.into());
+    }
+    Ok(results.into_iter().flatten().collect())
+}
@@ -0,0 +1,150 @@
+//! STS: AssumeRole on the server, S3 calls with the temporary credentials it hands out, and
+//! their expiry. Only run with `--sts`, since few S3 servers implement STS; one that answers
+//! AssumeRole as a request it does not know shows as not implemented in the conformance matrix.
+
+use std::time::{Duration, SystemTime};
+
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::config::{BehaviorVersion, Credentials};
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use aws_sdk_sts::error::ProvideErrorMetadata;
+use aws_sdk_sts::types::Credentials as TemporaryCredentials;
+use tracing::info;
+
+use crate::client::sdk_endpoint_url;
+use crate::guard::{BoxError, BucketGuard};
+use crate::matrix::Unsupported;
+use crate::progress;
+use crate::runner::{StsTarget, TestContext};
+
+/// Clock difference tolerated between the harness and the server.
+const SKEW: Duration = Duration::from_secs(60);
+/// How long after the stated expiry the server gets to start refusing the credentials.
+const GRACE: Duration = Duration::from_secs(5);
+
+fn target(ctx: &TestContext) -> Result<&StsTarget, BoxError> {
+    ctx.sts.as_deref().ok_or_else(|| "the STS suite needs --sts".into())
+}
+
+/// Assumes the role with the run's own credentials, over the S3 client's HTTP client, and
+/// checks that the credentials last as long as asked for.
+async fn assume(ctx: &TestContext, session: &str) -> Result<TemporaryCredentials, BoxError> {
+    let target = target(ctx)?;
+    let s3 = ctx.client.config();
+    let mut config = aws_sdk_sts::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .endpoint_url(sdk_endpoint_url(&target.endpoint))
+        .region(s3.region().cloned())
+        .credentials_provider(target.credentials.clone());
+    config.set_http_client(s3.http_client());
+    config.set_retry_config(s3.retry_config().cloned());
+    let sts = aws_sdk_sts::Client::from_conf(config.build());
+
+    let asked = SystemTime::now();
+    let resp = sts
+        .assume_role()
+        .role_arn(&target.role_arn)
+        .role_session_name(format!("s3test-{}-{}", session, ctx.run_id))
+        .duration_seconds(target.duration as i32)
+        .send()
+        .await;
+    let resp = match resp {
+        Ok(resp) => resp,
+        // The answer of a server that does not know the action: an error it gives for any
+        // request it has no handler for, or something that is not an STS response at all.
+        Err(SdkError::ResponseError(e)) => {
+            return Err(Unsupported(format!("AssumeRole answered with no STS response: {:?}", e)).into())
+        }
+        Err(SdkError::ServiceError(e)) if matches!(e.raw().status().as_u16(), 404 | 405 | 501) => {
+            let status = e.raw().status().as_u16();
+            let code = e.err().code().map(|code| format!(" {}", code)).unwrap_or_default();
+            return Err(Unsupported(format!("AssumeRole answered {}{}", status, code)).into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let credentials = resp.credentials().ok_or("AssumeRole returned no credentials")?.clone();
+    let expiration = SystemTime::try_from(*credentials.expiration())?;
+    let requested = Duration::from_secs(target.duration as u64);
+    if expiration <= asked {
+        return Err(format!("AssumeRole returned credentials that expired at {:?}", expiration).into());
+    }
+    if expiration > asked + requested + SKEW {
+        let asked_for = target.duration;
+        return Err(format!("AssumeRole gave credentials outlasting the {}s asked for", asked_for).into());
+    }
+    Ok(credentials)
+}
+
+/// An S3 client signing with the temporary credentials, with or without their session token.
+/// The SDK is not told when they expire, so that it keeps sending them and the server has to
+/// refuse them.
+fn client_with(ctx: &TestContext, credentials: &TemporaryCredentials, with_token: bool) -> Client {
+    let token = with_token.then(|| credentials.session_token().to_string());
+    let credentials =
+        Credentials::new(credentials.access_key_id(), credentials.secret_access_key(), token, None, "sts");
+    ctx.client_as(&credentials)
+}
+
+/// The HTTP status of a failed S3 call, None when it never got one; fails when the call worked.
+fn refused<T, E>(what: &str, result: Result<T, SdkError<E, HttpResponse>>) -> Result<Option<u16>, BoxError> {
+    match result {
+        Ok(_) => Err(format!("{} succeeded", what).into()),
+        Err(e) => Ok(e.raw_response().map(|r| r.status().as_u16())),
+    }
+}
+
+/// Temporary credentials from AssumeRole work for S3 calls, and not without their session
+/// token.
+pub async fn assume_role(ctx: TestContext) -> Result<(), BoxError> {
+    let credentials = assume(&ctx, "role").await?;
+    let client = client_with(&ctx, &credentials, true);
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("sts-role")).await?;
+    let key = "temporary-credentials.bin";
+    let payload = ctx.payload(key, 4096);
+
+    let body = ByteStream::from(payload.bytes());
+    client.put_object().bucket(bucket.name()).key(key).body(body).send().await?;
+    let resp = client.get_object().bucket(bucket.name()).key(key).send().await?;
+    progress::verify_download(resp.body, &payload).await?;
+    let resp = client.list_objects_v2().bucket(bucket.name()).send().await?;
+    if !resp.contents().iter().any(|o| o.key() == Some(key)) {
+        return Err(format!("ListObjectsV2 with temporary credentials does not list '{}'", key).into());
+    }
+
+    let tokenless = client_with(&ctx, &credentials, false);
+    let result = tokenless.get_object().bucket(bucket.name()).key(key).send().await;
+    let status = refused("GetObject with temporary keys but no session token", result)?;
+    info!(?status, "temporary keys refused without their session token");
+
+    bucket.cleanup().await
+}
+
+/// Temporary credentials stop working once they expire. The test takes as long as the
+/// credentials last (`--sts-duration`).
+pub async fn expiry(ctx: TestContext) -> Result<(), BoxError> {
+    let credentials = assume(&ctx, "expiry").await?;
+    let client = client_with(&ctx, &credentials, true);
+    let bucket = BucketGuard::create(&ctx.client, &ctx.run_id.bucket("sts-expiry")).await?;
+    let key = "expiring-credentials.bin";
+    let payload = ctx.payload(key, 1024);
+    let body = ByteStream::from(payload.bytes());
+    client.put_object().bucket(bucket.name()).key(key).body(body).send().await?;
+
+    let expiration = SystemTime::try_from(*credentials.expiration())?;
+    let wait = expiration.duration_since(SystemTime::now()).unwrap_or_default() + GRACE;
+    info!(wait_secs = wait.as_secs(), "waiting for the temporary credentials to expire");
+    tokio::time::sleep(wait).await;
+
+    let result = client.get_object().bucket(bucket.name()).key(key).send().await;
+    let status = refused("GetObject with expired temporary credentials", result)?;
+    if !matches!(status, Some(400 | 403)) {
+        let message = format!("GetObject with expired credentials answered {:?}, not 400 or 403", status);
+        return Err(message.into());
+    }
+
+    // The bucket was made with the run's own credentials, which clean it up.
+    bucket.cleanup().await
+}
@@ -0,0 +1,229 @@
+//! Snapshot checks for the XML documents the server returns.
+//!
+//! Response bodies are parsed and re-rendered in a canonical, indented form with volatile
+//! values (timestamps, request and upload IDs, the run ID) redacted, then compared against the
+//! `.snap` files stored per test. A missing or differing snapshot is written next to the stored
+//! one as `.snap.new`, so it can be reviewed and renamed, or accepted with `--snapshots update`.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use similar::TextDiff;
+
+use crate::capture::{CapturedBody, Exchange};
+use crate::naming::RunId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SnapshotMode {
+    /// Do not look at snapshots
+    Off,
+    /// Fail tests whose responses differ from the stored snapshots
+    Check,
+    /// Overwrite the stored snapshots with the current responses
+    Update,
+}
+
+/// Operations whose successful responses are snapshotted. Error bodies are snapshotted for
+/// every operation.
+const SNAPSHOT_OPERATIONS: &[&str] = &["ListObjectsV2", "CompleteMultipartUpload"];
+
+pub struct Snapshots {
+    dir: PathBuf,
+    mode: SnapshotMode,
+    run_id: String,
+}
+
+impl Snapshots {
+    pub fn new(dir: PathBuf, mode: SnapshotMode, run_id: RunId) -> Self {
+        Snapshots {
+            dir,
+            mode,
+            run_id: run_id.to_string(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.mode != SnapshotMode::Off
+    }
+
+    /// Compares the snapshot-worthy responses of a test against the stored snapshots. Returns a
+    /// description of every mismatch.
+    pub fn verify(&self, test: &str, exchanges: &[Exchange]) -> Result<(), String> {
+        if !self.enabled() {
+            return Ok(());
+        }
+        let dir = self.dir.join(test.replace("::", "__"));
+        let mut problems = Vec::new();
+        let eligible = exchanges.iter().filter_map(|e| snapshot_body(e).map(|body| (e, body)));
+
+        for (i, (exchange, body)) in eligible.enumerate() {
+            let response = exchange.response.as_ref().unwrap();
+            let actual = match canonicalize(body, &self.run_id) {
+                Ok(xml) => format!(
+                    "# {} {} -> {}\n{}",
+                    exchange.method, exchange.operation, response.status, xml
+                ),
+                Err(e) => {
+                    problems.push(format!("{}: response is not well-formed XML: {}", exchange.operation, e));
+                    continue;
+                }
+            };
+
+            let path = dir.join(format!("{:02}-{}.snap", i + 1, exchange.operation));
+            let new_path = path.with_extension("snap.new");
+            let stored = fs::read_to_string(&path).ok();
+            if self.mode == SnapshotMode::Update {
+                if stored.as_deref() != Some(actual.as_str()) {
+                    write_file(&path, &actual).map_err(|e| e.to_string())?;
+                }
+                let _ = fs::remove_file(&new_path);
+                continue;
+            }
+
+            match stored {
+                Some(stored) if stored == actual => {
+                    let _ = fs::remove_file(&new_path);
+                }
+                Some(stored) => {
+                    write_file(&new_path, &actual).map_err(|e| e.to_string())?;
+                    let diff = TextDiff::from_lines(&stored, &actual)
+                        .unified_diff()
+                        .header(&path.display().to_string(), "actual")
+                        .to_string();
+                    problems.push(format!("snapshot {} differs:\n{}", path.display(), diff));
+                }
+                None => {
+                    write_file(&new_path, &actual).map_err(|e| e.to_string())?;
+                    problems.push(format!("new snapshot written to {}", new_path.display()));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("\n"))
+        }
+    }
+}
+
+fn snapshot_body(exchange: &Exchange) -> Option<&str> {
+    let response = exchange.response.as_ref()?;
+    let CapturedBody::Bytes { data, .. } = &response.body else {
+        return None;
+    };
+    let body = std::str::from_utf8(data).ok()?.trim();
+    let wanted = response.status >= 400 || SNAPSHOT_OPERATIONS.contains(&exchange.operation.as_str());
+    (wanted && body.starts_with('<')).then_some(body)
+}
+
+fn write_file(path: &std::path::Path, contents: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)
+}
+
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<Element>,
+}
+
+/// Parses `xml` and renders it with one element per line, sorted attributes and volatile
+/// values replaced by placeholders.
+pub fn canonicalize(xml: &str, run_id: &str) -> Result<String, quick_xml::Error> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut stack = vec![Element::default()];
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) => stack.push(element(&start)?),
+            Event::Empty(start) => {
+                let element = element(&start)?;
+                stack.last_mut().unwrap().children.push(element);
+            }
+            Event::End(_) => {
+                let element = stack.pop().unwrap();
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Err(quick_xml::Error::Syntax(quick_xml::errors::SyntaxError::UnclosedTag)),
+                }
+            }
+            Event::Text(text) => stack.last_mut().unwrap().text.push_str(&text.unescape()?),
+            Event::CData(data) => {
+                stack.last_mut().unwrap().text.push_str(&String::from_utf8_lossy(&data));
+            }
+            Event::Eof if stack.len() == 1 => break,
+            Event::Eof => {
+                return Err(quick_xml::Error::Syntax(quick_xml::errors::SyntaxError::UnclosedTag))
+            }
+            Event::Decl(_) | Event::Comment(_) | Event::PI(_) | Event::DocType(_) => {}
+        }
+    }
+
+    let mut out = String::new();
+    for root in &stack[0].children {
+        render(root, 0, run_id, &mut out);
+    }
+    Ok(out)
+}
+
+fn element(start: &BytesStart<'_>) -> Result<Element, quick_xml::Error> {
+    let mut attributes = Vec::new();
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        attributes.push((
+            String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+            attribute.unescape_value()?.into_owned(),
+        ));
+    }
+    attributes.sort();
+    Ok(Element {
+        name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+        attributes,
+        ..Element::default()
+    })
+}
+
+fn render(element: &Element, depth: usize, run_id: &str, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    let mut open = element.name.clone();
+    for (key, value) in &element.attributes {
+        write!(open, " {}=\"{}\"", key, value).unwrap();
+    }
+
+    if element.children.is_empty() {
+        let text = redact(&element.name, &element.text, run_id);
+        if text.is_empty() {
+            writeln!(out, "{}<{}/>", indent, open).unwrap();
+        } else {
+            writeln!(out, "{}<{}>{}</{}>", indent, open, text, element.name).unwrap();
+        }
+        return;
+    }
+
+    writeln!(out, "{}<{}>", indent, open).unwrap();
+    for child in &element.children {
+        render(child, depth + 1, run_id, out);
+    }
+    writeln!(out, "{}</{}>", indent, element.name).unwrap();
+}
+
+fn redact(name: &str, text: &str, run_id: &str) -> String {
+    match name {
+        "LastModified" | "CreationDate" | "Initiated" => "[timestamp]".to_string(),
+        "RequestId" | "HostId" => "[request-id]".to_string(),
+        "UploadId" | "NextUploadIdMarker" | "UploadIdMarker" => "[upload-id]".to_string(),
+        // Object bodies depend on --seed, and with them the ETags.
+        "ETag" => "[etag]".to_string(),
+        _ => text.replace(run_id, "[run-id]"),
+    }
+}
@@ -0,0 +1,295 @@
+//! `s3test soak`: the CRUD workload kept up for hours.
+//!
+//! Workers loop over PUT, GET, LIST and DELETE of objects of varying size, verifying every
+//! download. A set of reference objects is written once at the start and read back at every
+//! report, so data that rots in storage is caught even though the workload never touches it
+//! again. The error rate and latency of each interval are printed as they come in, and the
+//! trend over the whole run at the end: a PHP process that leaks memory or a filling disk
+//! shows up as latency that keeps climbing.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio::time::{self, Instant};
+use tracing::{info, warn};
+
+use crate::bench::{millis, percentile, Op};
+use crate::cli::SoakArgs;
+use crate::dashboard;
+use crate::guard::{BoxError, BucketGuard};
+use crate::metrics::{self, Metrics};
+use crate::naming::RunId;
+use crate::payload::Payload;
+use crate::progress;
+
+/// Object sizes the workers cycle through.
+const SIZES: [usize; 4] = [0, 1024, 64 * 1024, 1024 * 1024];
+const REFERENCE_SIZE: usize = 64 * 1024;
+
+struct Sample {
+    op: Op,
+    latency: Duration,
+    ok: bool,
+}
+
+/// One report interval.
+struct Interval {
+    elapsed: Duration,
+    requests: usize,
+    errors: usize,
+    p50: Duration,
+    p99: Duration,
+    corrupted: usize,
+}
+
+pub async fn run(client: &Client, args: &SoakArgs) -> Result<(), BoxError> {
+    if args.interval == 0 || args.concurrency == 0 {
+        return Err("--interval and --concurrency must be greater than zero".into());
+    }
+    let run_id = RunId::generate();
+    let seed = run_id.timestamp();
+    let bucket = BucketGuard::create(client, &run_id.bucket("soak")).await?;
+
+    let reference: Vec<(String, Payload)> = (0..args.reference_objects)
+        .map(|i| {
+            let key = format!("reference/{}", i);
+            let payload = Payload::new(seed, &key, REFERENCE_SIZE);
+            (key, payload)
+        })
+        .collect();
+    for (key, payload) in &reference {
+        let body = ByteStream::from(payload.bytes());
+        client.put_object().bucket(bucket.name()).key(key).body(body).send().await?;
+    }
+    let metrics = Metrics::new("soak");
+    let _exporter = metrics::serve(&args.metrics, &metrics).await?;
+    let dashboard = dashboard::show(&args.metrics, &metrics);
+    // The table would be drawn over; with the dashboard it is printed when the run is over.
+    let live = dashboard.is_none();
+    info!(bucket = bucket.name(), duration = args.duration, concurrency = args.concurrency, "starting soak");
+
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(args.duration);
+    let (samples_tx, mut samples_rx) = mpsc::unbounded_channel();
+    let bucket_name: Arc<str> = bucket.name().into();
+    let mut workers = JoinSet::new();
+    for worker in 0..args.concurrency {
+        let (client, bucket, samples_tx) = (client.clone(), bucket_name.clone(), samples_tx.clone());
+        let metrics = metrics.clone();
+        workers.spawn(async move {
+            let mut cycle = 0;
+            while Instant::now() < deadline {
+                if !run_cycle(&client, &bucket, seed, worker, cycle, &metrics, &samples_tx).await {
+                    return;
+                }
+                cycle += 1;
+            }
+        });
+    }
+    drop(samples_tx);
+
+    if live {
+        print_header();
+    }
+    let period = Duration::from_secs(args.interval);
+    let mut report = time::interval_at(started + period, period);
+    let mut intervals = Vec::new();
+    let mut current = Vec::new();
+    let mut running = true;
+    while running {
+        tokio::select! {
+            sample = samples_rx.recv() => match sample {
+                Some(sample) => {
+                    current.push(sample);
+                    continue;
+                }
+                // Every worker has reached the deadline.
+                None => running = false,
+            },
+            _ = report.tick() => {}
+            _ = dashboard::interrupted(dashboard.as_ref()) => {
+                // Cycles still running would race the removal of the bucket.
+                workers.shutdown().await;
+                return Err("interrupted by Ctrl-C".into());
+            }
+        }
+        let corrupted = verify_reference(client, bucket.name(), &reference).await;
+        let interval = summarize(started.elapsed(), &current, corrupted);
+        if live {
+            print_interval(&interval);
+        }
+        intervals.push(interval);
+        current.clear();
+    }
+    drop(dashboard);
+    if !live {
+        print_header();
+        intervals.iter().for_each(print_interval);
+    }
+
+    // The last row only covers the cycles that were still running at the deadline.
+    print_trend(&intervals[..intervals.len() - 1]);
+    if let Err(e) = bucket.cleanup().await {
+        warn!(error = ?e, "could not remove soak bucket");
+    }
+
+    let errors: usize = intervals.iter().map(|i| i.errors).sum();
+    let corrupted = intervals.iter().filter(|i| i.corrupted > 0).count();
+    if errors > 0 || corrupted > 0 {
+        return Err(format!(
+            "{} request(s) failed, reference objects were corrupted at {} report(s)",
+            errors, corrupted
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// PUT, GET, LIST and DELETE of one object. Returns false once nobody is listening any more.
+async fn run_cycle(
+    client: &Client,
+    bucket: &str,
+    seed: u64,
+    worker: usize,
+    cycle: usize,
+    metrics: &Metrics,
+    samples: &mpsc::UnboundedSender<Sample>,
+) -> bool {
+    let prefix = format!("worker-{}/", worker);
+    let key = format!("{}{}", prefix, cycle);
+    let payload = Payload::new(seed, &key, SIZES[cycle % SIZES.len()]);
+
+    for op in [Op::Put, Op::Get, Op::List, Op::Delete] {
+        let started = Instant::now();
+        metrics.start();
+        let result: Result<(), BoxError> = async {
+            match op {
+                Op::Put => {
+                    let body = ByteStream::from(payload.bytes());
+                    client.put_object().bucket(bucket).key(&key).body(body).send().await?;
+                }
+                Op::Get => {
+                    let resp = client.get_object().bucket(bucket).key(&key).send().await?;
+                    progress::verify_download(resp.body, &payload).await?;
+                }
+                Op::List => {
+                    client.list_objects_v2().bucket(bucket).prefix(&prefix).send().await?;
+                }
+                Op::Delete => {
+                    client.delete_object().bucket(bucket).key(&key).send().await?;
+                }
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(e) = &result {
+            warn!(op = op.name(), key, error = ?e, "soak request failed");
+        }
+        let sample = Sample { op, latency: started.elapsed(), ok: result.is_ok() };
+        metrics.record(op, sample.latency, sample.ok);
+        if samples.send(sample).is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Reads every reference object back and counts those that no longer match.
+async fn verify_reference(client: &Client, bucket: &str, reference: &[(String, Payload)]) -> usize {
+    let mut corrupted = 0;
+    for (key, payload) in reference {
+        let result: Result<(), BoxError> = async {
+            let resp = client.get_object().bucket(bucket).key(key).send().await?;
+            progress::verify_download(resp.body, payload).await?;
+            Ok(())
+        }
+        .await;
+        if let Err(e) = result {
+            warn!(key, error = ?e, "reference object failed verification");
+            corrupted += 1;
+        }
+    }
+    corrupted
+}
+
+fn summarize(elapsed: Duration, samples: &[Sample], corrupted: usize) -> Interval {
+    let mut latencies: Vec<Duration> = samples.iter().filter(|s| s.ok).map(|s| s.latency).collect();
+    latencies.sort();
+    let failed: Vec<&Sample> = samples.iter().filter(|s| !s.ok).collect();
+    for op in [Op::Put, Op::Get, Op::List, Op::Delete] {
+        let count = failed.iter().filter(|s| s.op == op).count();
+        if count > 0 {
+            warn!(op = op.name(), count, "requests failed in this interval");
+        }
+    }
+    Interval {
+        elapsed,
+        requests: samples.len(),
+        errors: failed.len(),
+        p50: percentile(&latencies, 50.0),
+        p99: percentile(&latencies, 99.0),
+        corrupted,
+    }
+}
+
+fn error_rate(interval: &Interval) -> f64 {
+    match interval.requests {
+        0 => 0.0,
+        n => interval.errors as f64 * 100.0 / n as f64,
+    }
+}
+
+fn print_header() {
+    println!(
+        "{:>9} {:>8} {:>7} {:>7} {:>9} {:>9} {:>10}",
+        "elapsed", "requests", "errors", "rate", "p50 ms", "p99 ms", "corrupted"
+    );
+}
+
+fn print_interval(interval: &Interval) {
+    println!(
+        "{:>8}s {:>8} {:>7} {:>6.2}% {:>9.2} {:>9.2} {:>10}",
+        interval.elapsed.as_secs(),
+        interval.requests,
+        interval.errors,
+        error_rate(interval),
+        millis(interval.p50),
+        millis(interval.p99),
+        interval.corrupted,
+    );
+}
+
+/// Least-squares slope of the latency and error rate over the run, per hour.
+fn print_trend(intervals: &[Interval]) {
+    if intervals.len() < 2 {
+        println!("too few intervals for a trend; run longer or lower --interval");
+        return;
+    }
+    let hours: Vec<f64> = intervals.iter().map(|i| i.elapsed.as_secs_f64() / 3600.0).collect();
+    let p50: Vec<f64> = intervals.iter().map(|i| millis(i.p50)).collect();
+    let p99: Vec<f64> = intervals.iter().map(|i| millis(i.p99)).collect();
+    let errors: Vec<f64> = intervals.iter().map(error_rate).collect();
+    println!();
+    println!(
+        "trend per hour: p50 {:+.2} ms, p99 {:+.2} ms, error rate {:+.3} points",
+        slope(&hours, &p50),
+        slope(&hours, &p99),
+        slope(&hours, &errors)
+    );
+}
+
+fn slope(x: &[f64], y: &[f64]) -> f64 {
+    let n = x.len() as f64;
+    let (mean_x, mean_y) = (x.iter().sum::<f64>() / n, y.iter().sum::<f64>() / n);
+    let covariance: f64 = x.iter().zip(y).map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = x.iter().map(|x| (x - mean_x).powi(2)).sum();
+    if variance == 0.0 {
+        0.0
+    } else {
+        covariance / variance
+    }
+}
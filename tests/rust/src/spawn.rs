@@ -0,0 +1,232 @@
+//! Starts the PHP server in a throwaway container for the length of a run.
+//!
+//! The repository is mounted read-only and served by PHP's built-in web server. Buckets and the
+//! server's activity log live on an anonymous volume, so every run starts from empty storage and
+//! nothing is left behind once the container is removed.
+
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use clap::ValueEnum;
+use tracing::{debug, info, warn};
+
+use crate::cli::{ConnectionArgs, SpawnArgs};
+use crate::guard::BoxError;
+use crate::naming::RunId;
+
+/// Port PHP listens on inside the container.
+const CONTAINER_PORT: u16 = 8000;
+const APP_DIR: &str = "/srv/app";
+const STATE_DIR: &str = "/srv/state";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Orchestrator {
+    /// `docker run`
+    Docker,
+    /// `docker compose`, with a generated compose file
+    Compose,
+}
+
+/// A running server container, removed together with its storage on drop.
+pub struct SpawnedServer {
+    orchestrator: Orchestrator,
+    /// Container name, or project name for compose.
+    name: String,
+    compose_file: Option<PathBuf>,
+    endpoint: String,
+    stopped: bool,
+}
+
+impl SpawnedServer {
+    /// Starts the container. The server needs a moment before it accepts connections.
+    pub async fn start(
+        args: &SpawnArgs,
+        connection: &ConnectionArgs,
+        run_id: RunId,
+    ) -> Result<Self, BoxError> {
+        let source = args.server_dir.canonicalize().map_err(|e| {
+            format!("server sources not found at {}: {}", args.server_dir.display(), e)
+        })?;
+        let port = free_port()?;
+        let name = format!("s3test-{}", run_id);
+        let env = [
+            ("ACCESS_KEY", connection.access_key.as_str()),
+            ("SECRET_KEY", connection.secret_key.as_str()),
+            ("STORAGE_ROOT", "../state/data"),
+            ("LOG_FILE", "../state/activities.log"),
+            // The built-in server handles one request at a time unless told otherwise.
+            ("PHP_CLI_SERVER_WORKERS", "4"),
+        ];
+        let script = format!(
+            "mkdir -p {state}/data && exec php -S 0.0.0.0:{port} -t {app} {app}/index.php",
+            state = STATE_DIR,
+            port = CONTAINER_PORT,
+            app = APP_DIR,
+        );
+
+        let mut server = SpawnedServer {
+            orchestrator: args.spawn_with,
+            name,
+            compose_file: None,
+            endpoint: format!("http://127.0.0.1:{}", port),
+            stopped: false,
+        };
+        match args.spawn_with {
+            Orchestrator::Docker => {
+                let mut cmd = docker(["run", "--detach", "--name", &server.name]);
+                cmd.arg("--publish")
+                    .arg(format!("127.0.0.1:{}:{}", port, CONTAINER_PORT))
+                    .arg("--volume")
+                    .arg(format!("{}:{}:ro", source.display(), APP_DIR))
+                    .arg("--mount")
+                    .arg(format!("type=volume,dst={}", STATE_DIR));
+                for (key, value) in env {
+                    cmd.arg("--env").arg(format!("{}={}", key, value));
+                }
+                cmd.args([args.server_image.as_str(), "sh", "-c", &script]);
+                run_checked(cmd).await?;
+            }
+            Orchestrator::Compose => {
+                let file = std::env::temp_dir().join(format!("{}-compose.json", server.name));
+                std::fs::write(&file, compose_file(args, &source, port, &env, &script))?;
+                server.compose_file = Some(file);
+                run_checked(server.compose(["up", "--detach"])).await?;
+            }
+        }
+        info!(name = %server.name, endpoint = %server.endpoint, "started server container");
+        Ok(server)
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Prints the container output and the server's activity log, for diagnosing failed runs.
+    pub async fn print_logs(&self) {
+        let activity_log = format!("{}/activities.log", STATE_DIR);
+        let (logs, activity) = match self.orchestrator {
+            Orchestrator::Docker => (
+                docker(["logs", &self.name]),
+                docker(["exec", &self.name, "cat", &activity_log]),
+            ),
+            Orchestrator::Compose => (
+                self.compose(["logs", "--no-color"]),
+                self.compose(["exec", "-T", "server", "cat", &activity_log]),
+            ),
+        };
+
+        for (title, cmd) in [("container output", logs), ("activity log", activity)] {
+            println!("\n=== server {} ===", title);
+            match output(cmd).await {
+                Ok(text) => println!("{}", text.trim_end()),
+                Err(e) => println!("(unavailable: {})", e),
+            }
+        }
+    }
+
+    /// Removes the container and its storage volume.
+    pub async fn stop(mut self) -> Result<(), BoxError> {
+        self.stopped = true;
+        let result = run_checked(self.stop_command()).await;
+        self.remove_compose_file();
+        result?;
+        info!(name = %self.name, "removed server container");
+        Ok(())
+    }
+
+    fn stop_command(&self) -> Command {
+        match self.orchestrator {
+            Orchestrator::Docker => docker(["rm", "--force", "--volumes", &self.name]),
+            Orchestrator::Compose => self.compose(["down", "--volumes"]),
+        }
+    }
+
+    fn compose<const N: usize>(&self, args: [&str; N]) -> Command {
+        let mut cmd = docker(["compose", "--project-name", &self.name]);
+        if let Some(file) = &self.compose_file {
+            cmd.arg("--file").arg(file);
+        }
+        cmd.args(args);
+        cmd
+    }
+
+    fn remove_compose_file(&self) {
+        if let Some(file) = &self.compose_file {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+}
+
+impl Drop for SpawnedServer {
+    fn drop(&mut self) {
+        if self.stopped {
+            return;
+        }
+        // Drop can't await, so fall back to a blocking command.
+        match self.stop_command().stdout(Stdio::null()).stderr(Stdio::null()).status() {
+            Ok(status) if status.success() => info!(name = %self.name, "removed server container"),
+            Ok(status) => warn!(name = %self.name, %status, "removing server container failed"),
+            Err(e) => warn!(name = %self.name, error = %e, "removing server container failed"),
+        }
+        self.remove_compose_file();
+    }
+}
+
+/// Asks the OS for a port that is free right now. Another process could grab it before docker
+/// binds it, but that is unlikely on a developer machine or CI runner.
+fn free_port() -> Result<u16, BoxError> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+/// A compose file equivalent to the `docker run` invocation. JSON is valid YAML, so serde_json
+/// can write it.
+fn compose_file(args: &SpawnArgs, source: &Path, port: u16, env: &[(&str, &str)], script: &str) -> String {
+    let environment: serde_json::Map<String, serde_json::Value> =
+        env.iter().map(|(k, v)| (k.to_string(), (*v).into())).collect();
+    let file = serde_json::json!({
+        "services": {
+            "server": {
+                "image": args.server_image,
+                "command": ["sh", "-c", script],
+                "ports": [format!("127.0.0.1:{}:{}", port, CONTAINER_PORT)],
+                "environment": environment,
+                "volumes": [
+                    format!("{}:{}:ro", source.display(), APP_DIR),
+                    format!("state:{}", STATE_DIR),
+                ],
+            }
+        },
+        "volumes": { "state": {} },
+    });
+    serde_json::to_string_pretty(&file).unwrap()
+}
+
+fn docker<const N: usize>(args: [&str; N]) -> Command {
+    let mut cmd = Command::new("docker");
+    cmd.args(args);
+    cmd
+}
+
+async fn run_checked(cmd: Command) -> Result<(), BoxError> {
+    output(cmd).await.map(drop)
+}
+
+/// Runs a command to completion and returns its stdout and stderr, or an error naming the
+/// command if it failed.
+async fn output(mut cmd: Command) -> Result<String, BoxError> {
+    let program = format!("{:?}", cmd);
+    debug!(command = %program, "running");
+    cmd.stdin(Stdio::null());
+    let out = tokio::process::Command::from(cmd)
+        .output()
+        .await
+        .map_err(|e| format!("could not run {}: {}", program, e))?;
+    let mut text = String::from_utf8_lossy(&out.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&out.stderr));
+    if out.status.success() {
+        Ok(text)
+    } else {
+        Err(format!("{} failed with {}: {}", program, out.status, text.trim_end()).into())
+    }
+}
@@ -0,0 +1,93 @@
+//! Progress of a run, kept on disk so that `--resume` can pick up after an interruption.
+//!
+//! The state file is rewritten after every test with the verdict so far. A resumed run reuses
+//! the seed of the interrupted one and skips the tests that already passed; failed and
+//! unfinished tests run again.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::guard::BoxError;
+use crate::runner::Verdict;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RunState {
+    endpoint: String,
+    seed: u64,
+    tests: BTreeMap<String, TestState>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TestState {
+    passed: bool,
+    verdict: String,
+    duration_ms: u64,
+}
+
+/// The state of the current run and where it is saved.
+pub struct StateFile {
+    path: PathBuf,
+    state: RunState,
+}
+
+impl StateFile {
+    /// Starts a run from scratch, replacing any earlier state at `path`.
+    pub fn create(path: &Path, endpoint: &str, seed: u64) -> Result<Self, BoxError> {
+        let state = RunState { endpoint: endpoint.to_string(), seed, tests: BTreeMap::new() };
+        let file = StateFile { path: path.to_path_buf(), state };
+        file.save()?;
+        Ok(file)
+    }
+
+    /// Continues the run saved at `path`.
+    pub fn resume(path: &Path, endpoint: &str) -> Result<Self, BoxError> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read state file {} to resume from: {}", path.display(), e))?;
+        let state: RunState = serde_json::from_str(&json)
+            .map_err(|e| format!("state file {} is not valid: {}", path.display(), e))?;
+        if state.endpoint != endpoint {
+            warn!(previous = %state.endpoint, endpoint, "resuming a run that was against another endpoint");
+        }
+        Ok(StateFile { path: path.to_path_buf(), state })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.state.seed
+    }
+
+    /// How long `test` took when it passed in an earlier run, or None if it has yet to pass.
+    pub fn passed(&self, test: &str) -> Option<Duration> {
+        let test = self.state.tests.get(test).filter(|t| t.passed)?;
+        Some(Duration::from_millis(test.duration_ms))
+    }
+
+    /// The tests that ran to a verdict other than passed, in name order.
+    pub fn failed(&self) -> Vec<String> {
+        self.state.tests.iter().filter(|(_, t)| !t.passed).map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Records the outcome of `test` and saves the state straight away.
+    pub fn record(&mut self, test: &str, verdict: &Verdict, duration: Duration) -> Result<(), BoxError> {
+        let state = TestState {
+            passed: matches!(verdict, Verdict::Passed),
+            verdict: verdict.to_string(),
+            duration_ms: duration.as_millis() as u64,
+        };
+        self.state.tests.insert(test.to_string(), state);
+        self.save()
+    }
+
+    /// Writes to a temporary file first, so an interruption never leaves half a state file.
+    fn save(&self) -> Result<(), BoxError> {
+        let temporary = self.path.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(&self.state)?)
+            .map_err(|e| format!("cannot write state file {}: {}", temporary.display(), e))?;
+        fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+//! The conformance suite as a library call, for Rust projects that embed or fork the PHP server
+//! and want to run the checks from their own code instead of through the `s3test` binary:
+//!
+//! ```no_run
+//! # async fn check() -> Result<(), s3_rust_client::guard::BoxError> {
+//! use s3_rust_client::S3ConformanceSuite;
+//!
+//! let report = S3ConformanceSuite::new("http://localhost:8080")
+//!     .with_credentials("FAKEACCESS", "FAKESECRET")
+//!     .only("crud::")
+//!     .run()
+//!     .await?;
+//! for failure in report.failures() {
+//!     eprintln!("{}: {}", failure.name, failure.verdict);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! What the builder does not set is what `s3test run` defaults to; unlike the binary, the
+//! suite does not read `S3TEST_*` environment variables.
+
+use std::time::Duration;
+
+use clap::{CommandFactory, FromArgMatches};
+use rand::Rng;
+
+use crate::cleanup;
+use crate::cli::{self, Cli};
+use crate::guard::BoxError;
+use crate::health;
+use crate::naming::RunId;
+use crate::runner::{self, TestContext, TestResult};
+use crate::scenarios;
+use crate::version::Version;
+
+/// A run of the conformance scenarios against one endpoint, set up step by step.
+pub struct S3ConformanceSuite {
+    endpoint: String,
+    cli: Cli,
+}
+
+/// What a run of the suite found.
+pub struct SuiteReport {
+    /// The seed the run was made with; [`S3ConformanceSuite::with_seed`] repeats the run.
+    pub seed: u64,
+    /// One result per scenario, in the order they ran.
+    pub results: Vec<TestResult>,
+    /// The buckets the scenarios left on the server after tearing down, with what is in them.
+    pub leftovers: Vec<String>,
+}
+
+impl SuiteReport {
+    /// Whether every scenario passed or was skipped, and nothing was left on the server.
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none() && self.leftovers.is_empty()
+    }
+
+    /// The results of the scenarios that failed or timed out.
+    pub fn failures(&self) -> impl Iterator<Item = &TestResult> {
+        self.results.iter().filter(|r| r.verdict.is_failure())
+    }
+}
+
+impl S3ConformanceSuite {
+    /// The suite against `endpoint`, e.g. `http://localhost:8080`, with the settings `s3test
+    /// run` defaults to.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        let command = Cli::command().mut_args(|arg| arg.env(None));
+        let matches = command.try_get_matches_from(["s3test"]).expect("the defaults parse");
+        let cli = Cli::from_arg_matches(&matches).expect("the defaults parse");
+        S3ConformanceSuite { endpoint: endpoint.into(), cli }
+    }
+
+    /// Signs requests with this access key and secret instead of `FAKEACCESS`/`FAKESECRET`.
+    pub fn with_credentials(mut self, access_key: impl Into<String>, secret_key: impl Into<String>) -> Self {
+        self.cli.connection.access_key = access_key.into();
+        self.cli.connection.secret_key = secret_key.into();
+        self
+    }
+
+    /// Signs requests for this region instead of `us-east-1`.
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.cli.connection.region = region.into();
+        self
+    }
+
+    /// Runs only the scenarios whose name contains `text`, such as `multipart::`; call it again
+    /// to select more.
+    pub fn only(mut self, text: impl Into<String>) -> Self {
+        self.cli.run.only.push(text.into());
+        self
+    }
+
+    /// Generates bodies and orders the scenarios with this seed, to repeat an earlier run.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.cli.run.seed = Some(seed);
+        self
+    }
+
+    /// Runs the scenarios in the order they are declared instead of shuffled by the seed.
+    pub fn in_order(mut self) -> Self {
+        self.cli.run.in_order = true;
+        self
+    }
+
+    /// Fails a scenario that has not finished after `timeout` instead of 60 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.cli.run.timeout = timeout.as_secs().max(1);
+        self
+    }
+
+    /// Skips the scenarios that need a later release than `version` of the server, instead of
+    /// going by the version the server names.
+    pub fn with_server_version(mut self, version: Version) -> Self {
+        self.cli.run.server_version = Some(version);
+        self
+    }
+
+    /// Waits for the server to answer, runs the selected scenarios and reports how each went.
+    /// Fails only when the suite cannot run at all; failed scenarios are in the report.
+    pub async fn run(mut self) -> Result<SuiteReport, BoxError> {
+        let endpoint = cli::parse_endpoint(&self.endpoint)?;
+        self.cli.connection.endpoints = vec![endpoint.clone()];
+        self.cli.connection.endpoint = endpoint;
+        let (connection, retry, args) = (&self.cli.connection, &self.cli.retry, &self.cli.run);
+
+        let mut scenarios = scenarios::select(&args.only, &args.opted_in())?;
+        let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+        if !args.in_order {
+            scenarios::shuffle(&mut scenarios, seed);
+        }
+        let ctx = TestContext::connect(connection, retry, RunId::generate(), seed).await?;
+        let ctx = ctx.with_sts(&args.sts, connection);
+        let budget = Duration::from_secs(args.ready_timeout);
+        let interval = Duration::from_millis(args.ready_interval_ms);
+        health::wait_until_ready(&ctx.client, &connection.endpoint, budget, interval).await?;
+        let server = runner::server_version(&ctx, &scenarios, args, None).await;
+        let results = runner::run_all(&ctx, None, &scenarios, args, server.as_ref(), None, None).await;
+        let leftovers = cleanup::leftovers(&ctx).await;
+        Ok(SuiteReport { seed, results, leftovers })
+    }
+}
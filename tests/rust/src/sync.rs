@@ -0,0 +1,332 @@
+//! `s3test sync`: mirrors a local directory to a bucket prefix, or a bucket prefix to a local
+//! directory, like `aws s3 sync`. Both sides are listed and compared on size and ETag, so a
+//! second run with nothing changed sends nothing. For the server this is a realistic mix of
+//! paginated listings, uploads, downloads and deletes; for users, a way to move a tree of
+//! files in and out of it.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use md5::{Digest, Md5};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::cli::SyncArgs;
+use crate::guard::BoxError;
+
+/// One side of a sync: a local directory or `s3://BUCKET[/PREFIX]`.
+#[derive(Debug, Clone)]
+pub enum Location {
+    Local(PathBuf),
+    /// `prefix` is empty or ends with `/`.
+    Bucket { bucket: String, prefix: String },
+}
+
+impl FromStr for Location {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let Some(path) = s.strip_prefix("s3://") else {
+            return Ok(Location::Local(PathBuf::from(s)));
+        };
+        let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+        if bucket.is_empty() {
+            return Err(format!("expected s3://BUCKET[/PREFIX], got '{}'", s));
+        }
+        let mut prefix = prefix.to_string();
+        if !prefix.is_empty() && !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+        Ok(Location::Bucket { bucket: bucket.to_string(), prefix })
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Local(dir) => write!(f, "{}", dir.display()),
+            Location::Bucket { bucket, prefix } => write!(f, "s3://{}/{}", bucket, prefix),
+        }
+    }
+}
+
+/// A file or an object, under its name relative to the root of the sync.
+#[derive(Debug)]
+struct Entry {
+    size: u64,
+    /// Objects only; local files are hashed when there is an ETag to compare with.
+    etag: Option<String>,
+}
+
+/// Every file under `root`, by its path relative to it with `/` separators.
+fn list_local(root: &Path) -> Result<BTreeMap<String, Entry>, BoxError> {
+    let mut files = BTreeMap::new();
+    if !root.exists() {
+        return Ok(files);
+    }
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let dir = root.join(&relative);
+        for entry in fs::read_dir(&dir).map_err(|e| format!("cannot list {}: {}", dir.display(), e))? {
+            let entry = entry?;
+            let path = relative.join(entry.file_name());
+            let kind = entry.file_type()?;
+            if kind.is_dir() {
+                pending.push(path);
+            } else if kind.is_file() {
+                let Some(name) = path.to_str() else {
+                    warn!(path = %path.display(), "skipping a file whose name is not UTF-8");
+                    continue;
+                };
+                let name = name.replace(std::path::MAIN_SEPARATOR, "/");
+                files.insert(name, Entry { size: entry.metadata()?.len(), etag: None });
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Every object under `prefix`, by its key without the prefix. Directory markers, keys ending
+/// with `/`, have no file to map to and are left out.
+async fn list_bucket(
+    client: &Client,
+    bucket: &str,
+    prefix: &str,
+) -> Result<BTreeMap<String, Entry>, BoxError> {
+    let mut objects = BTreeMap::new();
+    let mut token = None;
+    loop {
+        let resp = client
+            .list_objects_v2()
+            .bucket(bucket)
+            .prefix(prefix)
+            .set_continuation_token(token)
+            .send()
+            .await?;
+        for object in resp.contents() {
+            let Some(name) = object.key().and_then(|k| k.strip_prefix(prefix)) else { continue };
+            if name.is_empty() || name.ends_with('/') {
+                continue;
+            }
+            let (size, etag) = (object.size().unwrap_or(0) as u64, object.e_tag().map(str::to_string));
+            objects.insert(name.to_string(), Entry { size, etag });
+        }
+        token = resp.next_continuation_token().map(str::to_string);
+        if token.is_none() {
+            return Ok(objects);
+        }
+    }
+}
+
+/// MD5 of a file in hex, which is what the ETag of an object uploaded in one piece is.
+fn md5_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Md5::new();
+    let mut buf = vec![0; 1024 * 1024];
+    loop {
+        match file.read(&mut buf)? {
+            0 => return Ok(hex::encode(hasher.finalize())),
+            n => hasher.update(&buf[..n]),
+        }
+    }
+}
+
+/// Whether the local file at `path` has the content of `object`. The ETag of a multipart
+/// upload is not the MD5 of the content, so for those the size alone decides.
+fn unchanged(path: &Path, file: &Entry, object: &Entry) -> Result<bool, BoxError> {
+    if file.size != object.size {
+        return Ok(false);
+    }
+    match object.etag.as_deref().map(|e| e.trim_matches('"')) {
+        Some(etag) if !etag.contains('-') => {
+            let md5 = md5_file(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+            Ok(md5 == etag)
+        }
+        _ => Ok(true),
+    }
+}
+
+/// A name relative to the root that stays under it once joined to a local directory.
+fn safe_name(name: &str) -> bool {
+    !name.starts_with('/') && name.split('/').all(|part| !part.is_empty() && part != "." && part != "..")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+    Upload,
+    Download,
+}
+
+enum Action {
+    Copy(String),
+    Delete(String),
+}
+
+pub async fn run(client: &Client, args: &SyncArgs) -> Result<(), BoxError> {
+    let (dir, bucket, prefix, direction) = match (&args.source, &args.destination) {
+        (Location::Local(dir), Location::Bucket { bucket, prefix }) => {
+            (dir, bucket, prefix, Direction::Upload)
+        }
+        (Location::Bucket { bucket, prefix }, Location::Local(dir)) => {
+            (dir, bucket, prefix, Direction::Download)
+        }
+        _ => {
+            let message = "one of the source and the destination must be a local directory, the other \
+                 s3://BUCKET[/PREFIX]";
+            return Err(message.into());
+        }
+    };
+    if direction == Direction::Upload && !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()).into());
+    }
+
+    let files = list_local(dir)?;
+    let objects = list_bucket(client, bucket, prefix).await?;
+    let (from, to) = match direction {
+        Direction::Upload => (&files, &objects),
+        Direction::Download => (&objects, &files),
+    };
+    let mut actions = Vec::new();
+    let mut unchanged_count = 0;
+    for (name, source) in from {
+        if direction == Direction::Download && !safe_name(name) {
+            let key = format!("{}{}", prefix, name);
+            warn!(key, "skipping an object whose key is not a safe file name");
+            continue;
+        }
+        let path = dir.join(name);
+        let same = match (direction, to.get(name)) {
+            (_, None) => false,
+            (Direction::Upload, Some(object)) => unchanged(&path, source, object)?,
+            (Direction::Download, Some(file)) => unchanged(&path, file, source)?,
+        };
+        if same {
+            unchanged_count += 1;
+        } else {
+            actions.push(Action::Copy(name.clone()));
+        }
+    }
+    if args.delete {
+        let extra = to.keys().filter(|name| !from.contains_key(*name));
+        actions.extend(extra.map(|name| Action::Delete(name.clone())));
+    }
+    let copies = actions.iter().filter(|a| matches!(a, Action::Copy(_))).count();
+    let deletes = actions.len() - copies;
+    let (source, destination) = (&args.source, &args.destination);
+    info!(%source, %destination, copies, deletes, unchanged = unchanged_count, "sync plan");
+
+    if args.dry_run {
+        for action in &actions {
+            match action {
+                Action::Copy(name) => println!("copy   {}", name),
+                Action::Delete(name) => println!("delete {}", name),
+            }
+        }
+        let summary = format!("{} to copy, {} to delete, {} unchanged", copies, deletes, unchanged_count);
+        println!("\n{}, nothing was changed", summary);
+        return Ok(());
+    }
+
+    let in_flight = Arc::new(Semaphore::new(args.concurrency as usize));
+    let mut tasks = JoinSet::new();
+    for action in actions {
+        let permit = in_flight.clone().acquire_owned().await?;
+        let client = client.clone();
+        let (dir, bucket, prefix) = (dir.clone(), bucket.clone(), prefix.clone());
+        tasks.spawn(async move {
+            let (name, result) = match action {
+                Action::Copy(name) => {
+                    let result = copy(&client, &dir, &bucket, &prefix, &name, direction).await;
+                    (name, result)
+                }
+                Action::Delete(name) => {
+                    let result = delete(&client, &dir, &bucket, &prefix, &name, direction).await;
+                    (name, result)
+                }
+            };
+            drop(permit);
+            (name, result)
+        });
+    }
+    let mut failed = 0;
+    while let Some(joined) = tasks.join_next().await {
+        let (name, result) = joined?;
+        if let Err(e) = result {
+            warn!(name, error = ?e, "sync of an entry failed");
+            failed += 1;
+        }
+    }
+    info!(copies, deletes, failed, "sync finished");
+    if failed > 0 {
+        return Err(format!("{} of {} change(s) failed", failed, copies + deletes).into());
+    }
+    Ok(())
+}
+
+async fn copy(
+    client: &Client,
+    dir: &Path,
+    bucket: &str,
+    prefix: &str,
+    name: &str,
+    direction: Direction,
+) -> Result<(), BoxError> {
+    let (path, key) = (dir.join(name), format!("{}{}", prefix, name));
+    match direction {
+        Direction::Upload => {
+            let body = ByteStream::from_path(&path)
+                .await
+                .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+            client.put_object().bucket(bucket).key(&key).body(body).send().await?;
+            info!(key, "uploaded");
+        }
+        // Written as it arrives, so that objects larger than memory can be mirrored.
+        Direction::Download => {
+            let mut resp = client.get_object().bucket(bucket).key(&key).send().await?;
+            let cannot_write = |e: io::Error| format!("cannot write {}: {}", path.display(), e);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await.map_err(cannot_write)?;
+            }
+            let mut out = tokio::fs::File::create(&path).await.map_err(cannot_write)?;
+            while let Some(chunk) = resp.body.try_next().await? {
+                out.write_all(&chunk).await.map_err(cannot_write)?;
+            }
+            out.flush().await.map_err(cannot_write)?;
+            info!(path = %path.display(), "downloaded");
+        }
+    }
+    Ok(())
+}
+
+async fn delete(
+    client: &Client,
+    dir: &Path,
+    bucket: &str,
+    prefix: &str,
+    name: &str,
+    direction: Direction,
+) -> Result<(), BoxError> {
+    match direction {
+        Direction::Upload => {
+            let key = format!("{}{}", prefix, name);
+            client.delete_object().bucket(bucket).key(&key).send().await?;
+            info!(key, "deleted");
+        }
+        Direction::Download => {
+            let path = dir.join(name);
+            let cannot_remove = |e: io::Error| format!("cannot remove {}: {}", path.display(), e);
+            tokio::fs::remove_file(&path).await.map_err(cannot_remove)?;
+            info!(path = %path.display(), "deleted");
+        }
+    }
+    Ok(())
+}
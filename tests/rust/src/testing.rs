@@ -0,0 +1,79 @@
+//! The scenarios as `cargo test` integration tests (`tests/scenarios.rs`), one multi-threaded
+//! `#[tokio::test]` each, so that nextest and IDE test runners can run and filter them.
+//!
+//! The tests read the server and every other setting from the `S3TEST_*` environment variables
+//! `s3test run` reads, plus `S3TEST_ARGS` for flags without one, e.g. `S3TEST_ARGS="--sts"`.
+//! Without `S3TEST_ENDPOINT` they pass without contacting anything, so that a plain
+//! `cargo test` needs no server. Each test fails on its own scenario's failure: `--allow-fail`,
+//! `--require` and profiles do not apply.
+//!
+//! ```text
+//! S3TEST_ENDPOINT=http://localhost:8080 cargo nextest run -E 'test(auth::)'
+//! ```
+
+use std::env;
+
+use clap::Parser;
+use rand::Rng;
+
+use crate::cleanup;
+use crate::cli::Cli;
+use crate::guard::BoxError;
+use crate::naming::RunId;
+use crate::runner::{self, TestContext, Verdict};
+use crate::scenarios;
+
+/// The names of all scenarios, for checking that every one of them has a test.
+pub fn scenario_names() -> Vec<&'static str> {
+    scenarios::all().iter().map(|s| s.name).collect()
+}
+
+/// Runs the scenario `name` as `s3test run` would against `S3TEST_ENDPOINT`, and fails with
+/// its verdict and seed if it does not pass, or with what it left on the server if it passed.
+/// An opt-in scenario whose flag is not in `S3TEST_ARGS` passes without running, as does
+/// everything without `S3TEST_ENDPOINT`.
+pub async fn run_scenario(name: &str) -> Result<(), BoxError> {
+    if env::var_os("S3TEST_ENDPOINT").is_none() {
+        eprintln!("skipping {}: S3TEST_ENDPOINT is not set", name);
+        return Ok(());
+    }
+    let extra = env::var("S3TEST_ARGS").unwrap_or_default();
+    let extra = shlex::split(&extra).ok_or("S3TEST_ARGS is not valid shell syntax")?;
+    let mut cli = Cli::try_parse_from(["s3test".to_string()].into_iter().chain(extra))?;
+    if cli.command.is_some() || cli.connection.endpoints.len() > 1 {
+        return Err("S3TEST_ARGS may only hold flags of `s3test run` for one endpoint".into());
+    }
+    // As Cli::parse_args does.
+    cli.connection.endpoint = cli.connection.endpoints[0].clone();
+    let args = &cli.run;
+
+    let scenario = scenarios::all().into_iter().find(|s| s.name == name);
+    let scenario = scenario.ok_or_else(|| format!("no scenario is named {}", name))?;
+    if let Some((_, flag)) = scenarios::held_back_by(&scenario, &args.opted_in()) {
+        eprintln!("skipping {}: it only runs with {} in S3TEST_ARGS", name, flag);
+        return Ok(());
+    }
+    let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+    let ctx = TestContext::connect(&cli.connection, &cli.retry, RunId::generate(), seed).await?;
+    let ctx = ctx.with_sts(&args.sts, &cli.connection);
+    let scenarios = [scenario];
+    let server = runner::server_version(&ctx, &scenarios, args, None).await;
+    let results = runner::run_all(&ctx, None, &scenarios, args, server.as_ref(), None, None).await;
+    let leftovers = match args.keep_data {
+        true => Vec::new(),
+        false => cleanup::leftovers(&ctx).await,
+    };
+    if let Verdict::Passed = results[0].verdict {
+        if !leftovers.is_empty() {
+            return Err(format!("left on the server after teardown: {}", leftovers.join("; ")).into());
+        }
+    }
+    match &results[0].verdict {
+        Verdict::Passed => Ok(()),
+        Verdict::Skipped(reason) => {
+            eprintln!("skipping {}: {}", name, reason);
+            Ok(())
+        }
+        verdict => Err(format!("{} (replay with S3TEST_SEED={})", verdict, seed).into()),
+    }
+}
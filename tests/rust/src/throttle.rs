@@ -0,0 +1,123 @@
+//! Bandwidth limits for connections the harness opens (`--max-upload-rate`,
+//! `--max-download-rate`), to see how the PHP server copes with slow clients: requests whose
+//! body trickles in and responses that are read slower than the server writes them.
+//!
+//! Each connection gets its own budget per direction. Bytes are let through in slices of about
+//! a tenth of a second's worth, so a limited transfer is steady rather than bursty.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+use crate::transport::Io;
+
+/// Bytes per second allowed in each direction; None means unlimited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rates {
+    pub upload: Option<u64>,
+    pub download: Option<u64>,
+}
+
+impl Rates {
+    pub fn is_limited(&self) -> bool {
+        self.upload.is_some() || self.download.is_some()
+    }
+}
+
+struct Limit {
+    rate: u64,
+    started: Instant,
+    /// Bytes let through since `started`.
+    passed: u64,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl Limit {
+    fn new(rate: u64) -> Self {
+        Limit { rate, started: Instant::now(), passed: 0, delay: None }
+    }
+
+    fn slice(&self) -> u64 {
+        (self.rate / 10).max(1)
+    }
+
+    /// How many of `wanted` bytes may pass now; pending until at least one may.
+    fn poll_allowance(&mut self, cx: &mut Context<'_>, wanted: usize) -> Poll<usize> {
+        loop {
+            if let Some(delay) = &mut self.delay {
+                ready!(delay.as_mut().poll(cx));
+                self.delay = None;
+            }
+            let earned = (self.started.elapsed().as_secs_f64() * self.rate as f64) as u64;
+            // Time spent idle must not turn into a burst later.
+            if earned.saturating_sub(self.passed) > self.slice() {
+                self.passed = earned - self.slice();
+            }
+            let allowance = earned.saturating_sub(self.passed);
+            if allowance > 0 {
+                return Poll::Ready(allowance.min(wanted as u64) as usize);
+            }
+            let due = Duration::from_secs_f64((self.passed + self.slice()) as f64 / self.rate as f64);
+            self.delay = Some(Box::pin(tokio::time::sleep_until(self.started + due)));
+        }
+    }
+
+    fn passed(&mut self, bytes: usize) {
+        self.passed += bytes as u64;
+    }
+}
+
+/// A stream whose reads and writes are held to [`Rates`].
+pub struct Throttled {
+    inner: Box<dyn Io>,
+    upload: Option<Limit>,
+    download: Option<Limit>,
+}
+
+impl Throttled {
+    pub fn new(inner: Box<dyn Io>, rates: Rates) -> Self {
+        Throttled { inner, upload: rates.upload.map(Limit::new), download: rates.download.map(Limit::new) }
+    }
+}
+
+impl AsyncRead for Throttled {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let Some(limit) = &mut this.download else {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        };
+        let allowed = ready!(limit.poll_allowance(cx, buf.remaining()));
+        let mut chunk = vec![0; allowed];
+        let mut limited = ReadBuf::new(&mut chunk);
+        ready!(Pin::new(&mut this.inner).poll_read(cx, &mut limited))?;
+        limit.passed(limited.filled().len());
+        buf.put_slice(limited.filled());
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for Throttled {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let Some(limit) = &mut this.upload else {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        };
+        let allowed = ready!(limit.poll_allowance(cx, buf.len()));
+        let written = ready!(Pin::new(&mut this.inner).poll_write(cx, &buf[..allowed]))?;
+        limit.passed(written);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,144 @@
+//! TLS for https:// endpoints.
+//!
+//! Certificates are checked against the system's roots plus whatever `--ca-cert` adds, which
+//! covers servers behind an internal or self-signed CA. `--danger-accept-invalid-certs` turns
+//! verification off entirely, for throwaway servers whose certificate nobody cares about.
+//! `--client-cert` and `--client-key` present a certificate of our own, for deployments behind
+//! a reverse proxy that requires mutual TLS.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use aws_smithy_http_client::tls::{TlsContext, TrustStore};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{aws_lc_rs, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use tracing::warn;
+
+use crate::cli::ConnectionArgs;
+use crate::guard::BoxError;
+
+/// The CA certificates in a PEM file, refusing files that contain none.
+fn load_ca(path: &Path) -> Result<Vec<u8>, BoxError> {
+    let pem = fs::read(path).map_err(|e| format!("cannot read CA certificate {}: {}", path.display(), e))?;
+    let count = CertificateDer::pem_slice_iter(&pem).filter(Result::is_ok).count();
+    if count == 0 {
+        return Err(format!("{} contains no PEM certificate", path.display()).into());
+    }
+    Ok(pem)
+}
+
+/// The SDK connector's TLS settings, for when certificates are verified.
+pub fn smithy_context(ca_cert: Option<&Path>) -> Result<TlsContext, BoxError> {
+    let mut trust_store = TrustStore::default();
+    if let Some(path) = ca_cert {
+        trust_store = trust_store.with_pem_certificate(load_ca(path)?);
+    }
+    Ok(TlsContext::builder().with_trust_store(trust_store).build()?)
+}
+
+/// The certificate chain and key given with `--client-cert` and `--client-key`.
+fn load_identity(
+    cert: &Path,
+    key: &Path,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), BoxError> {
+    let chain = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("cannot read client certificate {}: {}", cert.display(), e))?;
+    if chain.is_empty() {
+        return Err(format!("{} contains no PEM certificate", cert.display()).into());
+    }
+    let key = PrivateKeyDer::from_pem_file(key)
+        .map_err(|e| format!("cannot read client key {}: {}", key.display(), e))?;
+    Ok((chain, key))
+}
+
+/// The client configuration for connections the harness opens itself.
+pub fn client_config(args: &ConnectionArgs) -> Result<Arc<ClientConfig>, BoxError> {
+    let provider = Arc::new(aws_lc_rs::default_provider());
+    let builder =
+        ClientConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+    let builder = if args.danger_accept_invalid_certs {
+        builder.dangerous().with_custom_certificate_verifier(Arc::new(AcceptAnything(provider)))
+    } else {
+        let mut roots = RootCertStore::empty();
+        let native = rustls_native_certs::load_native_certs();
+        for e in &native.errors {
+            warn!(error = %e, "could not load some of the system's CA certificates");
+        }
+        roots.add_parsable_certificates(native.certs);
+        if let Some(path) = &args.ca_cert {
+            for cert in CertificateDer::pem_slice_iter(&load_ca(path)?) {
+                roots.add(cert?)?;
+            }
+        }
+        builder.with_root_certificates(roots)
+    };
+    let mut config = match (&args.client_cert, &args.client_key) {
+        (Some(cert), Some(key)) => {
+            let (chain, key) = load_identity(cert, key)?;
+            builder.with_client_auth_cert(chain, key).map_err(|e| format!("unusable client key: {}", e))?
+        }
+        _ => builder.with_no_client_auth(),
+    };
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// Runs the TLS handshake for `host` on an established connection.
+pub async fn connect(
+    stream: TcpStream,
+    host: &str,
+    config: Arc<ClientConfig>,
+) -> Result<TlsStream<TcpStream>, BoxError> {
+    let name = ServerName::try_from(host.trim_start_matches('[').trim_end_matches(']').to_string())?;
+    TlsConnector::from(config)
+        .connect(name, stream)
+        .await
+        .map_err(|e| format!("TLS handshake with {} failed: {}", host, e).into())
+}
+
+/// Accepts every certificate, but still checks that the handshake is signed with it.
+#[derive(Debug)]
+struct AcceptAnything(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnything {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
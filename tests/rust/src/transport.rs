@@ -0,0 +1,153 @@
+//! Connections the harness opens itself: directly or through `--proxy`, with TLS for https://
+//! endpoints. The raw client always uses them; the SDK only when its own connector cannot do
+//! what was asked for: a SOCKS proxy, `--danger-accept-invalid-certs`, a client certificate or
+//! a bandwidth limit.
+
+use std::sync::Arc;
+
+use aws_sdk_s3::primitives::SdkBody;
+use aws_smithy_runtime_api::client::http::{
+    HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpConnector,
+};
+use aws_smithy_runtime_api::client::orchestrator::{HttpRequest, HttpResponse};
+use aws_smithy_runtime_api::client::result::ConnectorError;
+use aws_smithy_runtime_api::client::runtime_components::RuntimeComponents;
+use http::Uri;
+use http_body_util::BodyExt;
+use hyper_util::rt::TokioIo;
+use rustls::ClientConfig;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::cli::ConnectionArgs;
+use crate::guard::BoxError;
+use crate::proxy::{self, Proxy, ProxyKind};
+use crate::throttle::{Rates, Throttled};
+use crate::tls;
+
+/// A byte stream to the server, plain or TLS.
+pub trait Io: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Io for T {}
+
+#[derive(Debug, Clone)]
+pub struct Transport {
+    proxy: Option<Proxy>,
+    tls: Arc<ClientConfig>,
+    rates: Rates,
+}
+
+impl Transport {
+    pub fn new(args: &ConnectionArgs) -> Result<Self, BoxError> {
+        Ok(Transport {
+            proxy: args.proxy.clone(),
+            tls: tls::client_config(args)?,
+            rates: Rates { upload: args.max_upload_rate, download: args.max_download_rate },
+        })
+    }
+
+    /// Whether the SDK has to send its requests through a `Transport`.
+    pub fn required_for_sdk(args: &ConnectionArgs) -> bool {
+        let socks = matches!(args.proxy.as_ref().map(|p| p.kind), Some(ProxyKind::Socks5 { .. }));
+        // The SDK's connector can neither skip verification, present a client certificate nor
+        // limit bandwidth.
+        let throttled = args.max_upload_rate.is_some() || args.max_download_rate.is_some();
+        socks || args.danger_accept_invalid_certs || args.client_cert.is_some() || throttled
+    }
+
+    /// Opens a connection to the host and port of `uri`.
+    pub async fn open(&self, uri: &Uri) -> Result<Box<dyn Io>, BoxError> {
+        Ok(self.open_with(uri, self.tls.clone()).await?.0)
+    }
+
+    /// Opens a connection offering the application `protocols` to https:// endpoints instead of
+    /// only HTTP/1.1, and returns the one the server picked; None without TLS or a choice.
+    pub async fn open_negotiated(
+        &self,
+        uri: &Uri,
+        protocols: &[&[u8]],
+    ) -> Result<(Box<dyn Io>, Option<Vec<u8>>), BoxError> {
+        let mut config = (*self.tls).clone();
+        config.alpn_protocols = protocols.iter().map(|p| p.to_vec()).collect();
+        self.open_with(uri, Arc::new(config)).await
+    }
+
+    async fn open_with(
+        &self,
+        uri: &Uri,
+        config: Arc<ClientConfig>,
+    ) -> Result<(Box<dyn Io>, Option<Vec<u8>>), BoxError> {
+        let https = match uri.scheme_str() {
+            Some("https") => true,
+            Some("http") | None => false,
+            Some(other) => return Err(format!("unsupported scheme '{}' in {}", other, uri).into()),
+        };
+        let host = uri.host().unwrap_or("localhost");
+        let port = uri.port_u16().unwrap_or(if https { 443 } else { 80 });
+        let stream = proxy::connect(self.proxy.as_ref(), &format!("{}:{}", host, port)).await?;
+        let (stream, protocol): (Box<dyn Io>, _) = match https {
+            true => {
+                let stream = tls::connect(stream, host, config).await?;
+                let protocol = stream.get_ref().1.alpn_protocol().map(<[u8]>::to_vec);
+                (Box::new(stream), protocol)
+            }
+            false => (Box::new(stream), None),
+        };
+        if !self.rates.is_limited() {
+            return Ok((stream, protocol));
+        }
+        Ok((Box::new(Throttled::new(stream, self.rates)), protocol))
+    }
+}
+
+/// [`HttpClient`] sending the SDK's requests over a [`Transport`], one connection per request.
+#[derive(Debug)]
+pub struct TransportClient {
+    transport: Transport,
+}
+
+impl TransportClient {
+    pub fn new(transport: Transport) -> Self {
+        TransportClient { transport }
+    }
+}
+
+impl HttpClient for TransportClient {
+    fn http_connector(&self, _: &HttpConnectorSettings, _: &RuntimeComponents) -> SharedHttpConnector {
+        SharedHttpConnector::new(TransportConnector { transport: self.transport.clone() })
+    }
+}
+
+#[derive(Debug)]
+struct TransportConnector {
+    transport: Transport,
+}
+
+impl HttpConnector for TransportConnector {
+    fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+        let transport = self.transport.clone();
+        HttpConnectorFuture::new(async move { send(&transport, request).await.map_err(ConnectorError::io) })
+    }
+}
+
+async fn send(transport: &Transport, request: HttpRequest) -> Result<HttpResponse, BoxError> {
+    let uri: Uri = request.uri().parse()?;
+    let stream = transport.open(&uri).await?;
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(connection);
+
+    let mut request = request.try_into_http1x()?;
+    // The SDK signs the Host header but leaves adding it to the connector, and hyper's
+    // connection-level API does not add one either.
+    if let Some(authority) = uri.authority() {
+        if !request.headers().contains_key(http::header::HOST) {
+            request.headers_mut().insert(http::header::HOST, authority.as_str().parse()?);
+        }
+    }
+    // The connection already leads to the server, so the request line only needs the path.
+    *request.uri_mut() = uri.path_and_query().map_or("/", |p| p.as_str()).parse()?;
+    let response = sender.send_request(request).await?;
+    let (parts, body) = response.into_parts();
+    let body = body.collect().await?.to_bytes();
+    let response = http::Response::from_parts(parts, SdkBody::from(body));
+    Ok(HttpResponse::try_from(response)?)
+}
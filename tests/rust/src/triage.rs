@@ -0,0 +1,123 @@
+//! Triage bundles: one directory per failed test with everything a bug report against the PHP
+//! server needs, so it can be zipped and attached as is.
+//!
+//! A bundle holds `summary.json` (verdict, endpoint, run ID, seed and the command that runs the
+//! test again with the same payloads), `exchanges.txt` with the captured HTTP traffic and
+//! curl commands to replay it, and `trace.log` with the test's own log events at debug level,
+//! whatever the verbosity of the run.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde_json::json;
+use tracing::Subscriber;
+use tracing_subscriber::field::MakeExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{fmt, EnvFilter, Layer};
+
+use crate::guard::BoxError;
+use crate::naming::RunId;
+use crate::runner::TestResult;
+
+tokio::task_local! {
+    static TRACE: Trace;
+}
+
+/// Log output of one test.
+#[derive(Debug, Clone, Default)]
+pub struct Trace(Arc<Mutex<Vec<u8>>>);
+
+impl Trace {
+    /// Runs `test` with its log events going to this trace as well.
+    pub async fn record<F: std::future::Future>(&self, test: F) -> F::Output {
+        TRACE.scope(self.clone(), test).await
+    }
+
+    pub fn take(&self) -> String {
+        String::from_utf8_lossy(&std::mem::take(&mut *self.0.lock().unwrap())).into_owned()
+    }
+}
+
+/// Appends to the trace of the test running on the current task, if any.
+struct TraceWriter;
+
+impl Write for TraceWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = TRACE.try_with(|trace| trace.0.lock().unwrap().extend_from_slice(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The layer that feeds [`Trace`]s.
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    // A field formatter of its own keeps the colored fields of the stderr layer, which
+    // tracing caches per span and formatter type, out of the trace.
+    let fields = fmt::format::debug_fn(|writer, field, value| match field.name() {
+        "message" => write!(writer, "{:?}", value),
+        name => write!(writer, "{}={:?}", name, value),
+    })
+    .delimited(" ");
+    fmt::layer()
+        .with_ansi(false)
+        .fmt_fields(fields)
+        .with_writer(|| TraceWriter)
+        .with_filter(EnvFilter::new("warn,s3test=debug,s3_rust_client=debug"))
+}
+
+/// Where the run took place, for the summaries.
+pub struct RunInfo<'a> {
+    pub endpoint: &'a str,
+    pub region: &'a str,
+    pub run_id: RunId,
+    pub seed: u64,
+}
+
+/// Writes a bundle under `dir/<run ID>/` for every test that failed and returns how
+/// many were written.
+pub fn write(dir: &Path, results: &[TestResult], run: &RunInfo) -> Result<usize, BoxError> {
+    let failed: Vec<_> = results.iter().filter(|r| r.verdict.is_failure()).collect();
+    for result in &failed {
+        let bundle = bundle_dir(dir, run.run_id, result.name);
+        fs::create_dir_all(&bundle)
+            .map_err(|e| format!("cannot create triage bundle {}: {}", bundle.display(), e))?;
+
+        let summary = json!({
+            "test": result.name,
+            "tags": result.tags,
+            "features": result.features,
+            "verdict": result.verdict.to_string(),
+            "duration_ms": result.duration.as_millis() as u64,
+            "endpoint": run.endpoint,
+            "region": run.region,
+            "run_id": run.run_id.to_string(),
+            "seed": run.seed,
+            "harness_version": env!("CARGO_PKG_VERSION"),
+            "reproduce": format!(
+                "s3test run --endpoint {} --seed {} --only {}",
+                run.endpoint, run.seed, result.name
+            ),
+        });
+        fs::write(bundle.join("summary.json"), serde_json::to_string_pretty(&summary)?)?;
+
+        let mut exchanges = String::new();
+        for (i, exchange) in result.exchanges.iter().enumerate() {
+            exchanges.push_str(&format!("--- #{} {}\n\n", i + 1, exchange));
+        }
+        fs::write(bundle.join("exchanges.txt"), exchanges)?;
+        fs::write(bundle.join("trace.log"), &result.trace)?;
+    }
+    Ok(failed.len())
+}
+
+fn bundle_dir(dir: &Path, run_id: RunId, test: &str) -> PathBuf {
+    dir.join(run_id.to_string()).join(test.replace("::", "__"))
+}
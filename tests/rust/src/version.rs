@@ -0,0 +1,155 @@
+//! The release of the PHP server under test, so that a scenario covering what a release added is
+//! skipped against older ones instead of failing. A server names its release in an
+//! `x-php-s3-server-version` response header or as the product `php-s3-server/VERSION` in
+//! `Server`; for one that does neither, `--server-version` says which it is. Without either,
+//! every scenario runs.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use http::Method;
+use tracing::{debug, info, warn};
+
+use crate::guard::BoxError;
+use crate::runner::{Scenario, TestContext};
+
+const HEADER: &str = "x-php-s3-server-version";
+/// Starts the `Server` header of a server that names its release there.
+const PRODUCT: &str = "php-s3-server/";
+
+/// A release number such as `1.4` or `1.4.2`, compared part by part with missing parts as zero,
+/// so that `1.4` and `1.4.0` are the same release. A pre-release or build suffix is ignored.
+#[derive(Debug, Clone)]
+pub struct Version(Vec<u64>);
+
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let release = s.trim().trim_start_matches('v');
+        let release = release.split(['-', '+']).next().unwrap_or_default();
+        let parts: Result<Vec<u64>, _> = release.split('.').map(str::parse).collect();
+        match parts {
+            Ok(parts) => Ok(Version(parts)),
+            Err(_) => Err(format!("'{}' is not a version like 1.4 or 1.4.2", s)),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<_> = self.0.iter().map(u64::to_string).collect();
+        f.write_str(&parts.join("."))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.0.len().max(other.0.len());
+        let part = |v: &Version, i: usize| v.0.get(i).copied().unwrap_or(0);
+        (0..len).map(|i| part(self, i).cmp(&part(other, i))).find(|o| o.is_ne()).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Version {}
+
+/// Asks the server which release it is with a GET of `/`, whose response headers say so if
+/// the server does. None for a server that does not, or did not answer.
+pub async fn probe(ctx: &TestContext) -> Option<Version> {
+    let resp = match ctx.raw.request(Method::GET, "/").send().await {
+        Ok(resp) => resp,
+        Err(e) => {
+            debug!(error = %e, "cannot ask the server for its version");
+            return None;
+        }
+    };
+    // A web server in front of PHP may send a Server header of its own, or list its product
+    // along with the PHP server's.
+    let product = resp
+        .headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("server"))
+        .flat_map(|(_, value)| value.split_whitespace())
+        .find_map(|product| product.strip_prefix(PRODUCT));
+    let Some(advertised) = resp.header(HEADER).or(product) else {
+        info!("the server does not name its version, running tests regardless of the one they need");
+        return None;
+    };
+    match advertised.parse() {
+        Ok(version) => {
+            info!(%version, "server version");
+            Some(version)
+        }
+        Err(e) => {
+            warn!(error = %e, "ignoring the version the server names");
+            None
+        }
+    }
+}
+
+/// Checks the release `scenario` needs, for one that is about to be registered.
+pub fn check(scenario: &Scenario) -> Result<(), BoxError> {
+    if let Some(required) = scenario.min_version {
+        let message = |e| format!("{} needs an invalid server version: {}", scenario.name, e);
+        required.parse::<Version>().map_err(message)?;
+    }
+    Ok(())
+}
+
+/// Why `scenario` cannot run against the `server` release, if it needs a later one.
+pub fn unmet(scenario: &Scenario, server: Option<&Version>) -> Option<String> {
+    let required: Version = scenario.min_version?.parse().ok()?;
+    let server = server?;
+    (*server < required).then(|| format!("needs server {} or later, this is {}", required, server))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_and_displays() {
+        assert_eq!(version("1.4.2").to_string(), "1.4.2");
+        assert_eq!(version(" v1.4 ").to_string(), "1.4");
+        assert_eq!(version("2.0.0-rc.1").to_string(), "2.0.0");
+        assert_eq!(version("1.4+build.7").to_string(), "1.4");
+    }
+
+    #[test]
+    fn refuses_what_is_not_a_version() {
+        for s in ["", "one", "1..2", "1.x", "-rc"] {
+            assert!(s.parse::<Version>().is_err(), "{:?} parsed", s);
+        }
+    }
+
+    #[test]
+    fn missing_parts_are_zero() {
+        assert_eq!(version("1.4"), version("1.4.0"));
+        assert_eq!(version("1"), version("1.0.0"));
+        assert!(version("1.4") < version("1.4.1"));
+    }
+
+    #[test]
+    fn parts_compare_as_numbers() {
+        assert!(version("1.10") > version("1.9"));
+        assert!(version("2") > version("1.99.99"));
+        assert!(version("1.4.2-rc") == version("1.4.2"));
+    }
+}
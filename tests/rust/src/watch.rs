@@ -0,0 +1,86 @@
+//! Rerunning the suite whenever the PHP sources change (`--watch`).
+//!
+//! The tree is polled rather than watched through the OS: a PHP source tree is small, and
+//! polling behaves the same on every platform and inside bind mounts. Editors tend to write a
+//! file in several steps, so a change only counts once the tree has been quiet for a moment.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use tracing::{debug, info};
+
+use crate::guard::BoxError;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long the tree must stay unchanged before the suite runs again.
+const SETTLE: Duration = Duration::from_millis(300);
+
+type Listing = BTreeMap<PathBuf, (SystemTime, u64)>;
+
+/// Modification time and size of every file under `root`, skipping hidden entries such as
+/// `.git`, whose churn has nothing to do with the server's behavior.
+fn list(root: &Path) -> Result<Listing, BoxError> {
+    let mut listing = Listing::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = fs::read_dir(&dir).map_err(|e| format!("cannot watch {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            // Files can disappear between listing the directory and reading their metadata.
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                listing.insert(entry.path(), (metadata.modified()?, metadata.len()));
+            }
+        }
+    }
+    Ok(listing)
+}
+
+/// Waits until a file under `root` is added, removed or modified. Returns false if the user
+/// pressed Ctrl-C instead, so the caller can still clean up.
+pub async fn wait_for_change(root: &Path) -> Result<bool, BoxError> {
+    info!(path = %root.display(), "watching for changes, press Ctrl-C to stop");
+    let before = list(root)?;
+    let watching = async {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let mut now = list(root)?;
+            if now == before {
+                continue;
+            }
+            loop {
+                tokio::time::sleep(SETTLE).await;
+                let settled = list(root)?;
+                if settled == now {
+                    break;
+                }
+                now = settled;
+            }
+            let changed: Vec<_> = now
+                .iter()
+                .filter(|(path, stamp)| before.get(*path) != Some(stamp))
+                .map(|(path, _)| path)
+                .chain(before.keys().filter(|path| !now.contains_key(*path)))
+                .collect();
+            for path in &changed {
+                debug!(path = %path.display(), "changed");
+            }
+            info!(files = changed.len(), "sources changed, running again");
+            return Ok::<_, BoxError>(true);
+        }
+    };
+    tokio::select! {
+        changed = watching => changed,
+        _ = tokio::signal::ctrl_c() => Ok(false),
+    }
+}
@@ -0,0 +1,149 @@
+//! A connection on which a test writes request bytes and reads responses itself, for testing
+//! connection handling: HTTP/1.0, `Connection: close` and pipelining, none of which hyper lets
+//! a client control.
+//!
+//! Responses are parsed just enough to find where each one ends, so that a server sending
+//! fewer body bytes than it announced, or running two responses together, shows up as an
+//! error rather than as a hang or a garbled next response.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::Method;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+use crate::guard::BoxError;
+use crate::rawhttp::RawResponse;
+use crate::transport::Io;
+
+/// Longest status or header line accepted.
+const MAX_LINE: usize = 16 * 1024;
+
+pub struct Connection {
+    stream: BufReader<Box<dyn Io>>,
+}
+
+/// A response read off a [`Connection`].
+#[derive(Debug)]
+pub struct WireResponse {
+    /// `HTTP/1.0` or `HTTP/1.1`, as on the status line.
+    pub version: String,
+    /// The body had neither a length nor chunked encoding and ran until the server closed.
+    pub close_delimited: bool,
+    pub response: RawResponse,
+}
+
+impl Connection {
+    pub fn new(stream: Box<dyn Io>) -> Self {
+        Connection { stream: BufReader::new(stream) }
+    }
+
+    /// Writes `bytes`, which may hold several requests, in one go.
+    pub async fn send(&mut self, bytes: &[u8]) -> Result<(), BoxError> {
+        let stream = self.stream.get_mut();
+        stream.write_all(bytes).await?;
+        stream.flush().await?;
+        Ok(())
+    }
+
+    /// Reads the next response, for a request made with `method`.
+    pub async fn read_response(&mut self, method: &Method) -> Result<WireResponse, BoxError> {
+        let status_line = self.read_line().await?.ok_or("connection closed before a response")?;
+        let mut parts = status_line.splitn(3, ' ');
+        let version = parts.next().unwrap_or_default().to_string();
+        let status: u16 = match (version.starts_with("HTTP/1."), parts.next().map(str::parse)) {
+            (true, Some(Ok(status))) => status,
+            _ => return Err(format!("malformed status line {:?}", status_line).into()),
+        };
+
+        let mut headers = Vec::new();
+        loop {
+            let line = self.read_line().await?.ok_or("connection closed in the response headers")?;
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line.split_once(':').ok_or_else(|| format!("malformed header {:?}", line))?;
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+        let mut response = RawResponse { status, headers, body: Bytes::new() };
+
+        let bodiless = *method == Method::HEAD || status < 200 || status == 204 || status == 304;
+        let chunked = response.header("transfer-encoding").is_some_and(|v| v.eq_ignore_ascii_case("chunked"));
+        let length = response
+            .header("content-length")
+            .map(|value| value.parse::<usize>().map_err(|_| format!("bad Content-Length {:?}", value)))
+            .transpose()?;
+        let close_delimited = !bodiless && !chunked && length.is_none();
+        response.body = match (bodiless, chunked, length) {
+            (true, _, _) => Bytes::new(),
+            (false, true, _) => self.read_chunked().await?,
+            (false, false, Some(length)) => self.read_exact(length).await?,
+            (false, false, None) => {
+                let mut body = Vec::new();
+                self.stream.read_to_end(&mut body).await?;
+                body.into()
+            }
+        };
+        Ok(WireResponse { version, close_delimited, response })
+    }
+
+    /// Closes the sending half, so that the server sees the end of what was sent, while its
+    /// response can still be read.
+    pub async fn finish_sending(&mut self) -> Result<(), BoxError> {
+        self.stream.get_mut().shutdown().await?;
+        Ok(())
+    }
+
+    /// Whether the server closes the connection within `within`, without sending anything more.
+    pub async fn closed_by_server(&mut self, within: Duration) -> Result<bool, BoxError> {
+        let mut byte = [0; 1];
+        match tokio::time::timeout(within, self.stream.read(&mut byte)).await {
+            Err(_) => Ok(false),
+            Ok(Ok(0)) => Ok(true),
+            Ok(Ok(_)) => Err(format!("unexpected byte {:?} after the response", byte[0] as char).into()),
+            // A reset is as good as a close.
+            Ok(Err(_)) => Ok(true),
+        }
+    }
+
+    /// The next CRLF-terminated line without its terminator, or None at end of stream.
+    async fn read_line(&mut self) -> Result<Option<String>, BoxError> {
+        let mut line = Vec::new();
+        let read = (&mut self.stream).take(MAX_LINE as u64).read_until(b'\n', &mut line).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if !line.ends_with(b"\n") {
+            return Err(format!("line longer than {} bytes or cut off", MAX_LINE).into());
+        }
+        let line = String::from_utf8_lossy(&line);
+        Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+    }
+
+    async fn read_exact(&mut self, length: usize) -> Result<Bytes, BoxError> {
+        let mut body = Vec::with_capacity(length);
+        (&mut self.stream).take(length as u64).read_to_end(&mut body).await?;
+        if body.len() < length {
+            return Err(format!("connection closed after {} of {} body bytes", body.len(), length).into());
+        }
+        Ok(body.into())
+    }
+
+    async fn read_chunked(&mut self) -> Result<Bytes, BoxError> {
+        let mut body = Vec::new();
+        loop {
+            let line = self.read_line().await?.ok_or("connection closed in a chunked body")?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| format!("bad chunk size {:?}", line))?;
+            if size == 0 {
+                // Trailers, up to the blank line.
+                while !self.read_line().await?.ok_or("connection closed in the trailers")?.is_empty() {}
+                return Ok(body.into());
+            }
+            body.extend_from_slice(&self.read_exact(size).await?);
+            if self.read_line().await?.is_none_or(|line| !line.is_empty()) {
+                return Err("chunk not followed by CRLF".into());
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Expected failures (`--xfail FILE`): tests the PHP server is known to fail, kept in a JSON
+//! file next to the server so that CI stays green while they are being fixed, and reports the
+//! ones that start passing so their entry can go.
+//!
+//! ```json
+//! {
+//!   "tests": [
+//!     { "select": "test=faults::dropped_part", "reason": "no multipart uploads yet" },
+//!     { "select": "suite=http2", "reason": "served by php -S", "versions": ["1.0", "1.1"] }
+//!   ]
+//! }
+//! ```
+//!
+//! `select` takes the same selectors as `--allow-fail`. An entry with `versions` only applies
+//! when the server is one of them, by `--server-version` or by the version it names; `1.1` and
+//! `1.1.0` are the same version.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::guard::BoxError;
+use crate::profile::Deviation;
+use crate::scenarios::{self, Selector};
+use crate::version::Version;
+
+#[derive(Debug, Deserialize)]
+struct XfailFile {
+    tests: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Entry {
+    select: String,
+    reason: String,
+    #[serde(default)]
+    versions: Vec<String>,
+}
+
+/// An entry of an xfail file.
+pub struct Xfail {
+    pub deviation: Deviation,
+    /// The releases of the server the entry applies to, every release when empty.
+    versions: Vec<Version>,
+}
+
+impl Xfail {
+    /// Whether the entry applies to `version` of the server; one for some releases only does not
+    /// apply to a server of unknown release.
+    pub fn applies(&self, version: Option<&Version>) -> bool {
+        self.versions.is_empty() || version.is_some_and(|v| self.versions.contains(v))
+    }
+
+    /// Whether the entry depends on the release of the server.
+    pub fn is_versioned(&self) -> bool {
+        !self.versions.is_empty()
+    }
+}
+
+/// The entries of the file at `path`.
+pub fn load(path: &Path) -> Result<Vec<Xfail>, BoxError> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| format!("cannot read xfail file {}: {}", path.display(), e))?;
+    let file: XfailFile = serde_json::from_str(&json)
+        .map_err(|e| format!("xfail file {} is not valid: {}", path.display(), e))?;
+
+    let all = scenarios::all();
+    let mut xfails = Vec::new();
+    for entry in file.tests {
+        let selector: Selector = entry.select.parse().map_err(|e| format!("{}: {}", path.display(), e))?;
+        // Checked against every scenario, not just those being run, so that --only does not
+        // make the entries for other tests look like typos.
+        if !all.iter().any(|s| selector.matches(s.name, s.tags)) {
+            return Err(format!("{}: {} matches none of the scenarios", path.display(), selector).into());
+        }
+        let versions: Vec<Version> = entry
+            .versions
+            .iter()
+            .map(|v| v.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        xfails.push(Xfail { deviation: Deviation { selector, reason: entry.reason }, versions });
+    }
+    Ok(xfails)
+}
@@ -0,0 +1,62 @@
+//! Every scenario as a test of its own, named as the scenario is: `cargo test auth::` runs the
+//! auth suite. See `s3_rust_client::testing` for the settings.
+
+use s3_rust_client::testing::scenario_names;
+
+macro_rules! scenarios {
+    ($($suite:ident { $($test:ident),* $(,)? })*) => {
+        $(
+            mod $suite {
+                use s3_rust_client::guard::BoxError;
+                use s3_rust_client::testing::run_scenario;
+
+                // Teardown from `Drop` and `properties::*` block in place, which only the
+                // multi-threaded runtime allows.
+                $(
+                    #[tokio::test(flavor = "multi_thread")]
+                    async fn $test() -> Result<(), BoxError> {
+                        run_scenario(concat!(stringify!($suite), "::", stringify!($test))).await
+                    }
+                )*
+            }
+        )*
+
+        const NAMES: &[&str] = &[$($(concat!(stringify!($suite), "::", stringify!($test)),)*)*];
+    };
+}
+
+scenarios! {
+    crud { round_trip }
+    content { images }
+    keys { path_segments, url_encoding }
+    caching { if_none_match }
+    headers { head_get_parity, metadata_limit, non_ascii_metadata, header_case }
+    cors { preflight_without_cors }
+    robustness {
+        missing_host, duplicate_headers, short_content_length, long_content_length, chunked_upload,
+        unsigned_request,
+    }
+    auth {
+        session_token, signing_modes, host_signing, query_canonicalization, sigv4_cases,
+        date_precedence, malformed_authorization, wrong_credentials, signing_region, tenant_isolation,
+    }
+    presigned { signed_headers, post_policy, empty_unsigned }
+    sts { assume_role, expiry }
+    connection { http_1_0, connection_close, pipelined, slow_upload }
+    http2 { probe }
+    faults { dropped_upload, dropped_part, truncated_download, delayed_response }
+    properties { round_trip_bytes, listing_model }
+    consistency { read_after_write }
+    races { delete_during_download }
+    multipart { concurrent_parts }
+    stress { many_buckets, copy_fan_out, deep_listing, large_listing, mixed_sizes, ranged_download }
+}
+
+#[test]
+fn every_scenario_has_a_test() {
+    let mut tests = NAMES.to_vec();
+    let mut scenarios = scenario_names();
+    tests.sort();
+    scenarios.sort();
+    assert_eq!(tests, scenarios, "tests/scenarios.rs is out of step with the scenario registry");
+}